@@ -1,16 +1,17 @@
 #![no_std]
 
+use badged_ipc::{BootStatusSender, Caller, Client, IrqHandler};
 use ferros::cap::{role, CNodeRole};
-use ferros::userland::{Caller, InterruptConsumer, Producer, RetypeForSetup};
+use ferros::userland::{Producer, RetypeForSetup};
 use ferros::vspace::{shared_status, MappedMemoryRegion};
 use imx6_hal::pac::{
     typenum::{op, U1, U12},
-    uart1::{self, UART1},
+    uart1::UART1,
 };
 use net_types::IpcUdpTransmitBuffer;
 
-/// Expected badge value on IRQ notifications
-pub type IrqBadgeBits = uart1::Irq;
+/// Badge bit of the UART interrupt on the bound notification
+pub const UART_IRQ_BIT: usize = 1 << 0;
 
 /// 4K console buffer
 pub type ConsoleBufferSizeBits = U12;
@@ -21,8 +22,8 @@ pub struct ProcParams<Role: CNodeRole> {
     /// Console UART/serial
     pub uart: UART1,
 
-    /// Interrupt consumer for the console UART
-    pub int_consumer: InterruptConsumer<uart1::Irq, Role>,
+    /// Console UART interrupt, on the bound notification
+    pub uart_irq: IrqHandler<Role>,
 
     /// IPC to the IOMUX driver
     pub iomux_caller: Caller<iomux::Request, iomux::Response, Role>,
@@ -30,15 +31,24 @@ pub struct ProcParams<Role: CNodeRole> {
     /// IPC to the storage driver
     pub storage_caller: Caller<
         persistent_storage::Request,
-        Result<persistent_storage::Response, persistent_storage::ErrorCode>,
+        Result<persistent_storage::Response, persistent_storage::Error>,
         Role,
     >,
 
+    /// Pin requests to the GPIO driver
+    pub gpio_client: Client<Role>,
+
     /// Producer of UDP messages destined to the TCP/IP driver
     pub udp_producer: Producer<Role, IpcUdpTransmitBuffer>,
 
     /// Console buffer memory
     pub console_buffer: MappedMemoryRegion<ConsoleBufferSizeBits, shared_status::Exclusive>,
+
+    /// Heartbeats to the watchdog service, sent on each of its ticks
+    pub heartbeat_producer: Producer<Role, watchdog::Message>,
+
+    /// Boot status reporting to the root task
+    pub boot_status: BootStatusSender<Role>,
 }
 
 impl RetypeForSetup for ProcParams<role::Local> {
@@ -3,13 +3,12 @@
 
 use selfe_runtime as _;
 
-use console::ProcParams;
+use ::gpio::GpioClient;
+use badged_ipc::{Caller, BROADCAST_BADGE};
+use console::{ProcParams, UART_IRQ_BIT};
 use core::fmt::{self, Write as WriteFmt};
 use debug_logger::DebugLogger;
-use ferros::{
-    cap::role,
-    userland::{Caller, Producer},
-};
+use ferros::{cap::role, userland::Producer};
 use imx6_hal::embedded_hal::serial::Read;
 use imx6_hal::{
     pac::uart1::UART1,
@@ -23,53 +22,68 @@ static LOGGER: DebugLogger = DebugLogger;
 #[allow(improper_ctypes_definitions)]
 #[no_mangle]
 pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
+    let restarted = unsafe { process_restart::reset_statics() }.unwrap();
     log::set_logger(&LOGGER)
         .map(|()| log::set_max_level(DebugLogger::max_log_level_from_env()))
         .unwrap();
 
-    log::debug!("[console] Process started");
+    log::debug!("[console] Process started restarted={}", restarted);
 
     // Configure UART1 IO
-    let resp = params
+    match params
         .iomux_caller
         .blocking_call(&iomux::Request::ConfigureUart1)
-        .unwrap();
-    log::debug!("[console] Configured UART1 IO resp={:?}", resp);
+    {
+        Ok(resp) => log::debug!("[console] Configured UART1 IO resp={:?}", resp),
+        Err(e) => log::warn!("[console] Failed to configure UART1 IO {:?}", e),
+    }
 
-    let int_consumer = params.int_consumer;
+    let uart_irq = params.uart_irq;
     let mut serial = Serial::new(params.uart);
     serial.listen(SerialEvent::Receive);
     let context = Context {
         serial,
         storage_caller: params.storage_caller,
         udp_producer: params.udp_producer,
+        gpio: GpioClient::new(params.gpio_client),
     };
 
     let mut console_buffer_mem = params.console_buffer;
     console_buffer_mem.flush().unwrap();
     let console_buffer = console_buffer_mem.as_mut_slice();
     console_buffer.fill(0);
-    let state = Runner::new(&ROOT_MENU, console_buffer, context);
+    let mut state = Runner::new(&ROOT_MENU, console_buffer, context);
+
+    let heartbeat = watchdog::Client::register("console", params.heartbeat_producer);
+
+    params.boot_status.ready();
 
     // TODO - this info is only if running on QEMU, otherwise it's the UART1 serial
     // port
     log::info!("[console] Run 'telnet 0.0.0.0 8888' to connect to the console interface (QEMU)");
-    int_consumer.consume(state, move |mut state| {
-        if let Ok(b) = state.context.serial.read() {
-            state.input_byte(b);
+    loop {
+        let bits = uart_irq.wait();
+        if bits & BROADCAST_BADGE != 0 {
+            heartbeat.heartbeat();
         }
-        state
-    })
+        if bits & UART_IRQ_BIT != 0 {
+            if let Ok(b) = state.context.serial.read() {
+                state.input_byte(b);
+            }
+            uart_irq.ack();
+        }
+    }
 }
 
 pub struct Context {
     serial: Serial<UART1>,
     storage_caller: Caller<
         persistent_storage::Request,
-        Result<persistent_storage::Response, persistent_storage::ErrorCode>,
+        Result<persistent_storage::Response, persistent_storage::Error>,
         role::Local,
     >,
     udp_producer: Producer<role::Local, IpcUdpTransmitBuffer>,
+    gpio: GpioClient,
 }
 
 impl fmt::Write for Context {
@@ -104,6 +118,23 @@ const ROOT_MENU: Menu<Context> = Menu {
                             ],
                         },
                     },
+                    &Item {
+                        command: "set",
+                        help: Some(storage::set::HELP),
+                        item_type: ItemType::Callback {
+                            function: storage::set::cmd,
+                            parameters: &[
+                                Parameter::Mandatory {
+                                    parameter_name: "key",
+                                    help: Some("The entry's key string"),
+                                },
+                                Parameter::Mandatory {
+                                    parameter_name: "value",
+                                    help: Some("The entry's value string"),
+                                },
+                            ],
+                        },
+                    },
                     &Item {
                         command: "get",
                         help: Some(storage::get::HELP),
@@ -126,6 +157,46 @@ const ROOT_MENU: Menu<Context> = Menu {
                             }],
                         },
                     },
+                    &Item {
+                        command: "ls",
+                        help: Some(storage::ls::HELP),
+                        item_type: ItemType::Callback {
+                            function: storage::ls::cmd,
+                            parameters: &[],
+                        },
+                    },
+                    &Item {
+                        command: "begin",
+                        help: Some(storage::begin::HELP),
+                        item_type: ItemType::Callback {
+                            function: storage::begin::cmd,
+                            parameters: &[],
+                        },
+                    },
+                    &Item {
+                        command: "commit",
+                        help: Some(storage::commit::HELP),
+                        item_type: ItemType::Callback {
+                            function: storage::commit::cmd,
+                            parameters: &[],
+                        },
+                    },
+                    &Item {
+                        command: "abort",
+                        help: Some(storage::abort::HELP),
+                        item_type: ItemType::Callback {
+                            function: storage::abort::cmd,
+                            parameters: &[],
+                        },
+                    },
+                    &Item {
+                        command: "stats",
+                        help: Some(storage::stats::HELP),
+                        item_type: ItemType::Callback {
+                            function: storage::stats::cmd,
+                            parameters: &[],
+                        },
+                    },
                     &Item {
                         command: "gc",
                         help: Some(storage::gc::HELP),
@@ -139,6 +210,98 @@ const ROOT_MENU: Menu<Context> = Menu {
                 exit: None,
             }),
         },
+        &Item {
+            command: "gpio",
+            help: Some("Enter the GPIO sub-menu."),
+            item_type: ItemType::Menu(&Menu {
+                label: "gpio",
+                items: &[
+                    &Item {
+                        command: "claim",
+                        help: Some(gpio::claim::HELP),
+                        item_type: ItemType::Callback {
+                            function: gpio::claim::cmd,
+                            parameters: &[
+                                Parameter::Mandatory {
+                                    parameter_name: "pin",
+                                    help: Some("The pin, as <bank>.<pin>"),
+                                },
+                                Parameter::Mandatory {
+                                    parameter_name: "mode",
+                                    help: Some("in, in-up, in-down, out-low or out-high"),
+                                },
+                            ],
+                        },
+                    },
+                    &Item {
+                        command: "release",
+                        help: Some(gpio::release::HELP),
+                        item_type: ItemType::Callback {
+                            function: gpio::release::cmd,
+                            parameters: &[Parameter::Mandatory {
+                                parameter_name: "pin",
+                                help: Some("The pin, as <bank>.<pin>"),
+                            }],
+                        },
+                    },
+                    &Item {
+                        command: "set",
+                        help: Some(gpio::set::HELP),
+                        item_type: ItemType::Callback {
+                            function: gpio::set::cmd,
+                            parameters: &[
+                                Parameter::Mandatory {
+                                    parameter_name: "pin",
+                                    help: Some("The pin, as <bank>.<pin>"),
+                                },
+                                Parameter::Mandatory {
+                                    parameter_name: "level",
+                                    help: Some("0 or 1"),
+                                },
+                            ],
+                        },
+                    },
+                    &Item {
+                        command: "get",
+                        help: Some(gpio::get::HELP),
+                        item_type: ItemType::Callback {
+                            function: gpio::get::cmd,
+                            parameters: &[Parameter::Mandatory {
+                                parameter_name: "pin",
+                                help: Some("The pin, as <bank>.<pin>"),
+                            }],
+                        },
+                    },
+                    &Item {
+                        command: "subscribe",
+                        help: Some(gpio::subscribe::HELP),
+                        item_type: ItemType::Callback {
+                            function: gpio::subscribe::cmd,
+                            parameters: &[
+                                Parameter::Mandatory {
+                                    parameter_name: "pin",
+                                    help: Some("The pin, as <bank>.<pin>"),
+                                },
+                                Parameter::Mandatory {
+                                    parameter_name: "edge",
+                                    help: Some("rising, falling or both"),
+                                },
+                            ],
+                        },
+                    },
+                    &Item {
+                        command: "events",
+                        help: Some(gpio::events::HELP),
+                        item_type: ItemType::Callback {
+                            function: gpio::events::cmd,
+                            parameters: &[],
+                        },
+                    },
+                ],
+                entry: None,
+                exit: None,
+            }),
+        },
         &Item {
             command: "net",
             help: Some("Enter the network sub-menu."),
@@ -186,9 +349,9 @@ fn enter_root_menu(_menu: &Menu<Context>, context: &mut Context) {
 
 mod storage {
     use super::*;
-    use persistent_storage::{ErrorCode, Key, Request, Response, Value};
+    use persistent_storage::{Error, Key, Request, Response, Value};
 
-    fn print_resp(context: &mut Context, resp: &Result<Response, ErrorCode>) {
+    fn print_resp(context: &mut Context, resp: &Result<Response, Error>) {
         if let Ok(r) = resp {
             writeln!(context.serial, "{}", r).unwrap();
         } else {
@@ -196,13 +359,25 @@ mod storage {
         }
     }
 
+    /// Call the storage driver, `None` once the failure is printed if the
+    /// call itself fails
+    fn call(context: &mut Context, req: &Request) -> Option<Result<Response, Error>> {
+        match context.storage_caller.blocking_call(req) {
+            Ok(resp) => Some(resp),
+            Err(e) => {
+                writeln!(context.serial, "Storage call failed {:?}", e).unwrap();
+                None
+            }
+        }
+    }
+
     pub mod append {
         use super::*;
 
         pub const HELP: &str = "Appends the key/value pair to storage.
 
   Example:
-  append my-key my-data";
+  append console/my-key my-data";
 
         pub fn cmd(
             _menu: &Menu<Context>,
@@ -219,12 +394,34 @@ mod storage {
                 value
             );
 
-            let resp = context
-                .storage_caller
-                .blocking_call(&Request::AppendKey(key, value))
-                .expect("Failed to perform a blocking_call");
+            if let Some(resp) = call(context, &Request::AppendKey(key, value)) {
+                print_resp(context, &resp);
+            }
+        }
+    }
+
+    pub mod set {
+        use super::*;
+
+        pub const HELP: &str = "Sets the key's value, replacing any existing one.
+
+  Example:
+  set console/my-key my-data";
 
-            print_resp(context, &resp);
+        pub fn cmd(
+            _menu: &Menu<Context>,
+            item: &Item<Context>,
+            args: &[&str],
+            context: &mut Context,
+        ) {
+            let key = Key::from(menu::argument_finder(item, args, "key").unwrap().unwrap());
+            let value = Value::from(menu::argument_finder(item, args, "value").unwrap().unwrap());
+
+            log::debug!("[console] Set storage item key='{}' value='{}'", key, value);
+
+            if let Some(resp) = call(context, &Request::SetKey(key, value)) {
+                print_resp(context, &resp);
+            }
         }
     }
 
@@ -234,7 +431,7 @@ mod storage {
         pub const HELP: &str = "Retrieves the value for the given key from storage.
 
   Example:
-  get my-key";
+  get console/my-key";
 
         pub fn cmd(
             _menu: &Menu<Context>,
@@ -246,12 +443,9 @@ mod storage {
 
             log::debug!("[console] Get storage value for key='{}'", key);
 
-            let resp = context
-                .storage_caller
-                .blocking_call(&Request::Get(key))
-                .expect("Failed to perform a blocking_call");
-
-            print_resp(context, &resp);
+            if let Some(resp) = call(context, &Request::Get(key)) {
+                print_resp(context, &resp);
+            }
         }
     }
 
@@ -261,7 +455,7 @@ mod storage {
         pub const HELP: &str = "Invalidates the key in storage.
 
   Example:
-  invalidate my-key";
+  invalidate console/my-key";
 
         pub fn cmd(
             _menu: &Menu<Context>,
@@ -273,12 +467,154 @@ mod storage {
 
             log::debug!("[console] Invalidate storage key='{}'", key);
 
-            let resp = context
-                .storage_caller
-                .blocking_call(&Request::InvalidateKey(key))
-                .expect("Failed to perform a blocking_call");
+            if let Some(resp) = call(context, &Request::InvalidateKey(key)) {
+                print_resp(context, &resp);
+            }
+        }
+    }
+
+    pub mod ls {
+        use super::*;
+
+        pub const HELP: &str = "Lists the keys in storage with their value sizes.
 
-            print_resp(context, &resp);
+  Example:
+  ls";
+
+        pub fn cmd(
+            _menu: &Menu<Context>,
+            _item: &Item<Context>,
+            _args: &[&str],
+            context: &mut Context,
+        ) {
+            log::debug!("[console] List storage keys");
+
+            let mut cursor = Some(0);
+            while let Some(c) = cursor {
+                let resp = match call(context, &Request::ListKeys { cursor: c }) {
+                    Some(resp) => resp,
+                    None => return,
+                };
+
+                match resp {
+                    Ok(Response::Keys { keys, next_cursor }) => {
+                        for k in keys.iter() {
+                            writeln!(context.serial, "{}  {} bytes", k.key, k.value_len).unwrap();
+                        }
+                        cursor = next_cursor;
+                    }
+                    _ => {
+                        print_resp(context, &resp);
+                        cursor = None;
+                    }
+                }
+            }
+        }
+    }
+
+    pub mod begin {
+        use super::*;
+
+        pub const HELP: &str = "Begins a transaction, following appends, sets and invalidates are
+  staged until commit or abort.
+
+  Example:
+  begin";
+
+        pub fn cmd(
+            _menu: &Menu<Context>,
+            _item: &Item<Context>,
+            _args: &[&str],
+            context: &mut Context,
+        ) {
+            log::debug!("[console] Begin storage transaction");
+
+            if let Some(resp) = call(context, &Request::BeginTransaction) {
+                print_resp(context, &resp);
+            }
+        }
+    }
+
+    pub mod commit {
+        use super::*;
+
+        pub const HELP: &str = "Applies the transaction's staged changes, all of them or none.
+
+  Example:
+  commit";
+
+        pub fn cmd(
+            _menu: &Menu<Context>,
+            _item: &Item<Context>,
+            _args: &[&str],
+            context: &mut Context,
+        ) {
+            log::debug!("[console] Commit storage transaction");
+
+            if let Some(resp) = call(context, &Request::Commit) {
+                print_resp(context, &resp);
+            }
+        }
+    }
+
+    pub mod abort {
+        use super::*;
+
+        pub const HELP: &str = "Drops the transaction's staged changes.
+
+  Example:
+  abort";
+
+        pub fn cmd(
+            _menu: &Menu<Context>,
+            _item: &Item<Context>,
+            _args: &[&str],
+            context: &mut Context,
+        ) {
+            log::debug!("[console] Abort storage transaction");
+
+            if let Some(resp) = call(context, &Request::Abort) {
+                print_resp(context, &resp);
+            }
+        }
+    }
+
+    pub mod stats {
+        use super::*;
+
+        pub const HELP: &str = "Shows the storage usage and the erases of each region since boot.
+
+  Example:
+  stats";
+
+        pub fn cmd(
+            _menu: &Menu<Context>,
+            _item: &Item<Context>,
+            _args: &[&str],
+            context: &mut Context,
+        ) {
+            log::debug!("[console] Storage stats");
+
+            let resp = match call(context, &Request::Stats) {
+                Some(resp) => resp,
+                None => return,
+            };
+
+            match resp {
+                Ok(Response::Stats(s)) => {
+                    writeln!(context.serial, "total        {} bytes", s.total_bytes).unwrap();
+                    writeln!(context.serial, "live         {} bytes", s.live_bytes).unwrap();
+                    writeln!(context.serial, "reclaimable  {} bytes", s.reclaimable_bytes).unwrap();
+                    writeln!(context.serial, "free         {} bytes", s.free_bytes()).unwrap();
+                    writeln!(context.serial, "objects      {}", s.objects).unwrap();
+                    write!(context.serial, "erases since boot").unwrap();
+                    for n in s.erases_since_boot.iter() {
+                        write!(context.serial, " {}", n).unwrap();
+                    }
+                    writeln!(context.serial).unwrap();
+                }
+                _ => print_resp(context, &resp),
+            }
         }
     }
 
@@ -298,12 +634,200 @@ mod storage {
         ) {
             log::debug!("[console] Garbage collect storage");
 
-            let resp = context
-                .storage_caller
-                .blocking_call(&Request::GarbageCollect)
-                .expect("Failed to perform a blocking_call");
+            if let Some(resp) = call(context, &Request::GarbageCollect) {
+                print_resp(context, &resp);
+            }
+        }
+    }
+}
+
+mod gpio {
+    use super::*;
+    use ::gpio::{Direction, Edge, PinId, Pull};
+
+    fn pin_arg(item: &Item<Context>, args: &[&str], context: &mut Context) -> Option<PinId> {
+        let arg = menu::argument_finder(item, args, "pin").unwrap().unwrap();
+        let pin = arg
+            .split_once('.')
+            .and_then(|(bank, pin)| Some(PinId::new(bank.parse().ok()?, pin.parse().ok()?)))
+            .filter(PinId::is_valid);
+        if pin.is_none() {
+            writeln!(context.serial, "Invalid pin '{}'", arg).unwrap();
+        }
+        pin
+    }
+
+    fn print_result<T: fmt::Debug>(context: &mut Context, result: &Result<T, ::gpio::Error>) {
+        writeln!(context.serial, "{:?}", result).unwrap();
+    }
+
+    pub mod claim {
+        use super::*;
+
+        pub const HELP: &str = "Claims a pin as an input or output.
+
+  Example:
+  claim 4.5 in-up";
+
+        pub fn cmd(
+            _menu: &Menu<Context>,
+            item: &Item<Context>,
+            args: &[&str],
+            context: &mut Context,
+        ) {
+            let pin = match pin_arg(item, args, context) {
+                Some(p) => p,
+                None => return,
+            };
+            let dir = match menu::argument_finder(item, args, "mode").unwrap().unwrap() {
+                "in" => Direction::Input { pull: Pull::None },
+                "in-up" => Direction::Input { pull: Pull::Up },
+                "in-down" => Direction::Input { pull: Pull::Down },
+                "out-low" => Direction::Output { high: false },
+                "out-high" => Direction::Output { high: true },
+                mode => {
+                    writeln!(context.serial, "Invalid mode '{}'", mode).unwrap();
+                    return;
+                }
+            };
+
+            log::debug!("[console] Claim {} as {:?}", pin, dir);
+
+            let result = context.gpio.claim(pin, dir);
+            print_result(context, &result);
+        }
+    }
 
-            print_resp(context, &resp);
+    pub mod release {
+        use super::*;
+
+        pub const HELP: &str = "Releases a claimed pin.
+
+  Example:
+  release 4.5";
+
+        pub fn cmd(
+            _menu: &Menu<Context>,
+            item: &Item<Context>,
+            args: &[&str],
+            context: &mut Context,
+        ) {
+            if let Some(pin) = pin_arg(item, args, context) {
+                log::debug!("[console] Release {}", pin);
+                let result = context.gpio.release(pin);
+                print_result(context, &result);
+            }
+        }
+    }
+
+    pub mod set {
+        use super::*;
+
+        pub const HELP: &str = "Drives a claimed output pin high or low.
+
+  Example:
+  set 1.9 1";
+
+        pub fn cmd(
+            _menu: &Menu<Context>,
+            item: &Item<Context>,
+            args: &[&str],
+            context: &mut Context,
+        ) {
+            let pin = match pin_arg(item, args, context) {
+                Some(p) => p,
+                None => return,
+            };
+            let high = menu::argument_finder(item, args, "level").unwrap().unwrap() != "0";
+
+            log::debug!("[console] Set {} high={}", pin, high);
+
+            let result = context.gpio.set_level(pin, high);
+            print_result(context, &result);
+        }
+    }
+
+    pub mod get {
+        use super::*;
+
+        pub const HELP: &str = "Reads the level of a claimed pin.
+
+  Example:
+  get 4.5";
+
+        pub fn cmd(
+            _menu: &Menu<Context>,
+            item: &Item<Context>,
+            args: &[&str],
+            context: &mut Context,
+        ) {
+            if let Some(pin) = pin_arg(item, args, context) {
+                log::debug!("[console] Get {}", pin);
+                let result = context.gpio.level(pin);
+                print_result(context, &result);
+            }
+        }
+    }
+
+    pub mod subscribe {
+        use super::*;
+
+        pub const HELP: &str = "Records the edges of a claimed input pin, see events.
+
+  Example:
+  subscribe 4.5 falling";
+
+        pub fn cmd(
+            _menu: &Menu<Context>,
+            item: &Item<Context>,
+            args: &[&str],
+            context: &mut Context,
+        ) {
+            let pin = match pin_arg(item, args, context) {
+                Some(p) => p,
+                None => return,
+            };
+            let edge = match menu::argument_finder(item, args, "edge").unwrap().unwrap() {
+                "rising" => Edge::Rising,
+                "falling" => Edge::Falling,
+                "both" => Edge::RisingFalling,
+                edge => {
+                    writeln!(context.serial, "Invalid edge '{}'", edge).unwrap();
+                    return;
+                }
+            };
+
+            log::debug!("[console] Subscribe {} on {:?}", pin, edge);
+
+            let result = context.gpio.subscribe(pin, edge);
+            print_result(context, &result);
+        }
+    }
+
+    pub mod events {
+        use super::*;
+
+        pub const HELP: &str = "Prints the pending events of the subscribed pins.
+
+  Example:
+  events";
+
+        pub fn cmd(
+            _menu: &Menu<Context>,
+            _item: &Item<Context>,
+            _args: &[&str],
+            context: &mut Context,
+        ) {
+            loop {
+                match context.gpio.next_event() {
+                    Ok(Some(e)) => writeln!(context.serial, "{} high={}", e.pin, e.high).unwrap(),
+                    Ok(None) => break,
+                    Err(e) => {
+                        writeln!(context.serial, "{:?}", e).unwrap();
+                        break;
+                    }
+                }
+            }
         }
     }
 }
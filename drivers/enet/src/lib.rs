@@ -1,5 +1,6 @@
 #![no_std]
 
+use badged_ipc::{BootStatusSender, Caller};
 use ferros::cap::{role, CNodeRole};
 use ferros::userland::{Consumer1, Producer, RetypeForSetup};
 use ferros::vspace::{shared_status, MappedMemoryRegion};
@@ -36,8 +37,21 @@ pub struct ProcParams<Role: CNodeRole> {
     /// NOTE: currently expects to be mapped *not* cacheable
     pub dma_mem: MappedMemoryRegion<EthDmaMemSizeInBits, shared_status::Exclusive>,
 
-    /// Hardware MAC address
+    /// IPC to the storage driver, used to read the runtime MAC address
+    pub storage_caller: Caller<
+        persistent_storage::Request,
+        Result<persistent_storage::Response, persistent_storage::Error>,
+        Role,
+    >,
+
+    /// Default hardware MAC address, overridden by the `net.mac` storage key
     pub mac_addr: EthernetAddress,
+
+    /// Heartbeats to the watchdog service, sent from the periodic ENET timer
+    pub heartbeat_producer: Producer<Role, watchdog::Message>,
+
+    /// Boot status reporting to the root task
+    pub boot_status: BootStatusSender<Role>,
 }
 
 impl RetypeForSetup for ProcParams<role::Local> {
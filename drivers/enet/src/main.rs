@@ -3,24 +3,28 @@
 
 use selfe_runtime as _;
 
+use badged_ipc::Caller;
 use debug_logger::DebugLogger;
 use enet::ProcParams;
 use ferros::cap::role;
 use ferros::userland::Producer;
 use imx6_hal::enet::{uncached_memory_region::UncachedMemoryRegion, Enet};
 use imx6_hal::pac::typenum::Unsigned;
-use net_types::IpcEthernetFrame;
+use net_types::{EthernetAddress, IpcEthernetFrame, Ipv4Address, NetConfig};
+use persistent_storage::{Error, ErrorCode, Key, Request, Response};
+use watchdog::HEARTBEAT_PERIOD_MS;
 
 static LOGGER: DebugLogger = DebugLogger;
 
 #[allow(improper_ctypes_definitions)]
 #[no_mangle]
 pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
+    let restarted = unsafe { process_restart::reset_statics() }.unwrap();
     log::set_logger(&LOGGER)
         .map(|()| log::set_max_level(DebugLogger::max_log_level_from_env()))
         .unwrap();
 
-    log::debug!("[enet-driver] Process started");
+    log::debug!("[enet-driver] Process started restarted={}", restarted);
 
     let dma_mem = params.dma_mem;
     dma_mem.flush().unwrap();
@@ -41,23 +45,35 @@ pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
     log::trace!("[enet-driver] Descriptor pool {}", desc_mem);
     log::trace!("[enet-driver] Packet pool {}", pkt_mem);
 
-    let mut enet = Enet::new(params.enet, params.mac_addr, desc_mem, pkt_mem).unwrap();
+    // The MAC address must match the one used by the TCP/IP driver,
+    // which reads the same net.mac key
+    let mac_addr = load_mac_addr(&params.storage_caller, params.mac_addr);
+    log::debug!("[enet-driver] MAC={}", mac_addr);
+
+    let mut enet = Enet::new(params.enet, mac_addr, desc_mem, pkt_mem).unwrap();
 
     enet.reset();
 
     // TODO - ipc to do the clock configs and IOMUX'ing
 
     enet.init();
+    enet.start_periodic_timer(HEARTBEAT_PERIOD_MS);
+
+    let heartbeat = watchdog::Client::register("enet-driver", params.heartbeat_producer);
+
+    params.boot_status.ready();
 
     struct State {
         enet: Enet,
         producer: Producer<role::Local, IpcEthernetFrame>,
+        heartbeat: watchdog::Client,
     }
 
     let producer_qlen = params.producer.capacity();
     let initial_state = State {
         enet,
         producer: params.producer,
+        heartbeat,
     };
 
     params.consumer.consume(
@@ -66,10 +82,14 @@ pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
             // Non-queue IRQ wakeup event
             log::trace!("[enet-driver] IRQ wakeup");
 
-            let rx_ready = state.enet.ack_irqs();
+            let irqs = state.enet.ack_irqs();
+
+            if irqs.timer {
+                state.heartbeat.heartbeat();
+            }
 
             // Attempt to drain up to qlen worth of packets from the rx ring
-            if rx_ready {
+            if irqs.rx_frame {
                 for _ in 0..producer_qlen {
                     let mut rx_frame = IpcEthernetFrame::new();
                     let bytes_recvd = state.enet.receive(|pkt| {
@@ -104,3 +124,35 @@ pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
         },
     );
 }
+
+/// Override the build-time default MAC address with the `net.mac` key, if
+/// present in storage
+fn load_mac_addr(
+    storage_caller: &Caller<Request, Result<Response, Error>, role::Local>,
+    mac_addr: EthernetAddress,
+) -> EthernetAddress {
+    // Only the MAC address is used here, the TCP/IP driver owns the rest
+    let mut net_config = NetConfig::new(mac_addr, Ipv4Address::default());
+    let key = NetConfig::KEY_MAC;
+    let resp = storage_caller.blocking_call(&Request::Get(Key::from(key)));
+    match resp {
+        Ok(Ok(Response::Value(value))) => {
+            // Get only returns UTF-8 values
+            let value = value.as_str().unwrap_or_default();
+            match net_config.set(key, value) {
+                Ok(()) => log::debug!("[enet-driver] Using {}={}", key, value),
+                Err(e) => log::warn!(
+                    "[enet-driver] Ignoring invalid {}='{}', {:?}",
+                    key,
+                    value,
+                    e
+                ),
+            }
+        }
+        Ok(Ok(r)) => log::warn!("[enet-driver] Unexpected response for {}, {}", key, r),
+        Ok(Err(Error::Storage(ErrorCode::KeyNotFound))) => (),
+        Ok(Err(e)) => log::warn!("[enet-driver] Failed to read {}, {:?}", key, e),
+        Err(e) => log::warn!("[enet-driver] Storage call for {} failed, {:?}", key, e),
+    }
+    net_config.mac_addr
+}
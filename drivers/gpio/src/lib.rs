@@ -0,0 +1,337 @@
+#![no_std]
+
+use badged_ipc::{BootStatusSender, Caller, Client, IrqHandler, Message, Server};
+use core::fmt;
+use ferros::cap::{role, CNodeRole};
+use ferros::userland::{Producer, RetypeForSetup};
+use imx6_hal::gpio::{Floating, Input, Output, PadControl, PullDown, PullUp, PushPull};
+use imx6_hal::pac::gpio::{GPIO1, GPIO2, GPIO4, GPIO5, GPIO6, GPIO7};
+
+pub use imx6_hal::gpio::Edge;
+
+/// GPIO banks are numbered 1 through 7
+pub const NUM_BANKS: usize = 7;
+
+pub const PINS_PER_BANK: u8 = 32;
+
+/// A pin in one of the GPIO banks, e.g. GPIO3_IO19 is `PinId::new(3, 19)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinId {
+    pub bank: u8,
+    pub pin: u8,
+}
+
+impl PinId {
+    pub const fn new(bank: u8, pin: u8) -> Self {
+        PinId { bank, pin }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.bank >= 1 && usize::from(self.bank) <= NUM_BANKS && self.pin < PINS_PER_BANK
+    }
+
+    fn to_word(self) -> usize {
+        (usize::from(self.bank) << 8) | usize::from(self.pin)
+    }
+
+    fn from_word(word: usize) -> Self {
+        PinId {
+            bank: (word >> 8) as u8,
+            pin: word as u8,
+        }
+    }
+}
+
+impl fmt::Display for PinId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GPIO{}_IO{:02}", self.bank, self.pin)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pull {
+    None,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Input {
+        pull: Pull,
+    },
+    /// Push pull output driven to the given initial level
+    Output {
+        high: bool,
+    },
+}
+
+impl Direction {
+    pub fn is_output(&self) -> bool {
+        matches!(self, Direction::Output { .. })
+    }
+
+    /// IOMUXC pad control value for the pin mode
+    pub fn pad_control(&self) -> u32 {
+        match self {
+            Direction::Input { pull: Pull::None } => Input::<Floating>::PAD_CONTROL,
+            Direction::Input { pull: Pull::Up } => Input::<PullUp>::PAD_CONTROL,
+            Direction::Input { pull: Pull::Down } => Input::<PullDown>::PAD_CONTROL,
+            Direction::Output { .. } => Output::<PushPull>::PAD_CONTROL,
+        }
+    }
+}
+
+/// Edge detected on a subscribed pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    pub pin: PinId,
+    /// Pad level when the interrupt was handled
+    pub high: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    /// Take exclusive ownership of a pin
+    Claim(PinId, Direction),
+    Release(PinId),
+    SetLevel(PinId, bool),
+    GetLevel(PinId),
+    /// Signal the client's event notification on `Edge`, input pins only
+    Subscribe(PinId, Edge),
+    Unsubscribe(PinId),
+    /// Take the oldest pending event
+    NextEvent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    Done,
+    Level(bool),
+    Event(Option<Event>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    InvalidRequest,
+    InvalidPin,
+    /// The pin's bank isn't owned by the GPIO driver
+    Unavailable,
+    AlreadyClaimed,
+    NotClaimed,
+    NotAnInput,
+    NotAnOutput,
+    /// The iomux driver couldn't mux the pin's pad to GPIO
+    PadUnavailable,
+}
+
+const REQ_CLAIM: usize = 1;
+const REQ_RELEASE: usize = 2;
+const REQ_SET_LEVEL: usize = 3;
+const REQ_GET_LEVEL: usize = 4;
+const REQ_SUBSCRIBE: usize = 5;
+const REQ_UNSUBSCRIBE: usize = 6;
+const REQ_NEXT_EVENT: usize = 7;
+
+const RESP_DONE: usize = 1;
+const RESP_LEVEL: usize = 2;
+const RESP_EVENT: usize = 3;
+const RESP_NO_EVENT: usize = 4;
+const RESP_ERROR: usize = 5;
+
+impl Request {
+    /// Message label and data words
+    pub fn encode(&self) -> (usize, [usize; 2]) {
+        match *self {
+            Request::Claim(pin, dir) => {
+                let dir = match dir {
+                    Direction::Input { pull: Pull::None } => 0,
+                    Direction::Output { high } => 1 + usize::from(high),
+                    Direction::Input { pull: Pull::Up } => 3,
+                    Direction::Input { pull: Pull::Down } => 4,
+                };
+                (REQ_CLAIM, [pin.to_word(), dir])
+            }
+            Request::Release(pin) => (REQ_RELEASE, [pin.to_word(), 0]),
+            Request::SetLevel(pin, high) => (REQ_SET_LEVEL, [pin.to_word(), high.into()]),
+            Request::GetLevel(pin) => (REQ_GET_LEVEL, [pin.to_word(), 0]),
+            Request::Subscribe(pin, edge) => {
+                let edge = match edge {
+                    Edge::Rising => 0,
+                    Edge::Falling => 1,
+                    Edge::RisingFalling => 2,
+                };
+                (REQ_SUBSCRIBE, [pin.to_word(), edge])
+            }
+            Request::Unsubscribe(pin) => (REQ_UNSUBSCRIBE, [pin.to_word(), 0]),
+            Request::NextEvent => (REQ_NEXT_EVENT, [0, 0]),
+        }
+    }
+
+    pub fn decode(msg: &Message) -> Option<Self> {
+        let (w0, w1) = match *msg.data() {
+            [w0, w1, ..] => (w0, w1),
+            _ => return None,
+        };
+        let pin = PinId::from_word(w0);
+        Some(match msg.label {
+            REQ_CLAIM => match w1 {
+                0 => Request::Claim(pin, Direction::Input { pull: Pull::None }),
+                1 | 2 => Request::Claim(pin, Direction::Output { high: w1 == 2 }),
+                3 => Request::Claim(pin, Direction::Input { pull: Pull::Up }),
+                4 => Request::Claim(pin, Direction::Input { pull: Pull::Down }),
+                _ => return None,
+            },
+            REQ_RELEASE => Request::Release(pin),
+            REQ_SET_LEVEL => Request::SetLevel(pin, w1 != 0),
+            REQ_GET_LEVEL => Request::GetLevel(pin),
+            REQ_SUBSCRIBE => match w1 {
+                0 => Request::Subscribe(pin, Edge::Rising),
+                1 => Request::Subscribe(pin, Edge::Falling),
+                2 => Request::Subscribe(pin, Edge::RisingFalling),
+                _ => return None,
+            },
+            REQ_UNSUBSCRIBE => Request::Unsubscribe(pin),
+            REQ_NEXT_EVENT => Request::NextEvent,
+            _ => return None,
+        })
+    }
+}
+
+impl Response {
+    /// Message label and data words of a result
+    pub fn encode(result: &Result<Response, Error>) -> (usize, [usize; 2]) {
+        match *result {
+            Ok(Response::Done) => (RESP_DONE, [0, 0]),
+            Ok(Response::Level(high)) => (RESP_LEVEL, [high.into(), 0]),
+            Ok(Response::Event(Some(e))) => (RESP_EVENT, [e.pin.to_word(), e.high.into()]),
+            Ok(Response::Event(None)) => (RESP_NO_EVENT, [0, 0]),
+            Err(e) => (RESP_ERROR, [e as usize, 0]),
+        }
+    }
+
+    pub fn decode(msg: &Message) -> Result<Response, Error> {
+        let (w0, w1) = match *msg.data() {
+            [w0, w1, ..] => (w0, w1),
+            _ => return Err(Error::InvalidRequest),
+        };
+        match msg.label {
+            RESP_DONE => Ok(Response::Done),
+            RESP_LEVEL => Ok(Response::Level(w0 != 0)),
+            RESP_EVENT => Ok(Response::Event(Some(Event {
+                pin: PinId::from_word(w0),
+                high: w1 != 0,
+            }))),
+            RESP_NO_EVENT => Ok(Response::Event(None)),
+            RESP_ERROR => Err(match w0 {
+                1 => Error::InvalidPin,
+                2 => Error::Unavailable,
+                3 => Error::AlreadyClaimed,
+                4 => Error::NotClaimed,
+                5 => Error::NotAnInput,
+                6 => Error::NotAnOutput,
+                7 => Error::PadUnavailable,
+                _ => Error::InvalidRequest,
+            }),
+            _ => Err(Error::InvalidRequest),
+        }
+    }
+}
+
+/// Client side of the GPIO driver
+pub struct GpioClient {
+    client: Client<role::Local>,
+}
+
+impl GpioClient {
+    pub fn new(client: Client<role::Local>) -> Self {
+        GpioClient { client }
+    }
+
+    pub fn call(&self, req: Request) -> Result<Response, Error> {
+        let (label, data) = req.encode();
+        Response::decode(&self.client.call(label, &data))
+    }
+
+    pub fn claim(&self, pin: PinId, dir: Direction) -> Result<(), Error> {
+        self.call(Request::Claim(pin, dir)).map(|_| ())
+    }
+
+    pub fn release(&self, pin: PinId) -> Result<(), Error> {
+        self.call(Request::Release(pin)).map(|_| ())
+    }
+
+    pub fn set_level(&self, pin: PinId, high: bool) -> Result<(), Error> {
+        self.call(Request::SetLevel(pin, high)).map(|_| ())
+    }
+
+    pub fn level(&self, pin: PinId) -> Result<bool, Error> {
+        match self.call(Request::GetLevel(pin))? {
+            Response::Level(high) => Ok(high),
+            _ => Err(Error::InvalidRequest),
+        }
+    }
+
+    pub fn subscribe(&self, pin: PinId, edge: Edge) -> Result<(), Error> {
+        self.call(Request::Subscribe(pin, edge)).map(|_| ())
+    }
+
+    pub fn unsubscribe(&self, pin: PinId) -> Result<(), Error> {
+        self.call(Request::Unsubscribe(pin)).map(|_| ())
+    }
+
+    pub fn next_event(&self) -> Result<Option<Event>, Error> {
+        match self.call(Request::NextEvent)? {
+            Response::Event(e) => Ok(e),
+            _ => Err(Error::InvalidRequest),
+        }
+    }
+
+    /// Block until the driver signals events, see `next_event`
+    pub fn wait_events(&self) {
+        self.client.wait_events();
+    }
+}
+
+/// NOTE: GPIO3 stays with spi-bus, which toggles the SPI chip selects
+/// around every transaction
+#[repr(C)]
+pub struct ProcParams<Role: CNodeRole> {
+    pub gpio1: GPIO1,
+    pub gpio2: GPIO2,
+    pub gpio4: GPIO4,
+    pub gpio5: GPIO5,
+    pub gpio6: GPIO6,
+    pub gpio7: GPIO7,
+
+    /// Combined interrupts for pins 0-15 and 16-31 of each bank, in the
+    /// bound notification badge bit order, see system.toml
+    pub gpio1_irq_low: IrqHandler<Role>,
+    pub gpio1_irq_high: IrqHandler<Role>,
+    pub gpio2_irq_low: IrqHandler<Role>,
+    pub gpio2_irq_high: IrqHandler<Role>,
+    pub gpio4_irq_low: IrqHandler<Role>,
+    pub gpio4_irq_high: IrqHandler<Role>,
+    pub gpio5_irq_low: IrqHandler<Role>,
+    pub gpio5_irq_high: IrqHandler<Role>,
+    pub gpio6_irq_low: IrqHandler<Role>,
+    pub gpio6_irq_high: IrqHandler<Role>,
+    pub gpio7_irq_low: IrqHandler<Role>,
+    pub gpio7_irq_high: IrqHandler<Role>,
+
+    /// Requests from clients, each with its own badge
+    pub server: Server<Role>,
+
+    /// IPC to the IOMUX driver, which muxes and configures claimed pads
+    pub iomux_caller: Caller<iomux::Request, iomux::Response, Role>,
+
+    /// Heartbeats to the watchdog service, sent on each of its ticks
+    pub heartbeat_producer: Producer<Role, watchdog::Message>,
+
+    /// Boot status reporting to the root task
+    pub boot_status: BootStatusSender<Role>,
+}
+
+impl RetypeForSetup for ProcParams<role::Local> {
+    type Output = ProcParams<role::Child>;
+}
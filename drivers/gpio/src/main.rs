@@ -0,0 +1,334 @@
+#![no_std]
+#![no_main]
+
+use selfe_runtime as _;
+
+use badged_ipc::{Caller, IrqHandler, BROADCAST_BADGE, NOTIFICATION_BADGE};
+use debug_logger::DebugLogger;
+use ferros::cap::role;
+use gpio::{
+    Direction, Edge, Error, Event, PinId, ProcParams, Request, Response, NUM_BANKS, PINS_PER_BANK,
+};
+use imx6_hal::gpio::Bank;
+
+static LOGGER: DebugLogger = DebugLogger;
+
+type IomuxCaller = Caller<iomux::Request, iomux::Response, role::Local>;
+
+/// GPIO7 only has IO00 through IO13
+const GPIO7_PINS: u8 = 14;
+
+/// Events not yet taken by their clients, oldest are dropped first
+const MAX_PENDING_EVENTS: usize = 32;
+
+#[allow(improper_ctypes_definitions)]
+#[no_mangle]
+pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
+    let restarted = unsafe { process_restart::reset_statics() }.unwrap();
+    log::set_logger(&LOGGER)
+        .map(|()| log::set_max_level(DebugLogger::max_log_level_from_env()))
+        .unwrap();
+
+    log::debug!("[gpio] Process started restarted={}", restarted);
+
+    let mut banks: [Option<Bank>; NUM_BANKS] = Default::default();
+    banks[0] = Some(Bank::from(params.gpio1));
+    banks[1] = Some(Bank::from(params.gpio2));
+    banks[3] = Some(Bank::from(params.gpio4));
+    banks[4] = Some(Bank::from(params.gpio5));
+    banks[5] = Some(Bank::from(params.gpio6));
+    banks[6] = Some(Bank::from(params.gpio7));
+
+    // Same order as the bound interrupts in system.toml, bit n of the
+    // notification badge is irqs[n]
+    let irqs = [
+        params.gpio1_irq_low,
+        params.gpio1_irq_high,
+        params.gpio2_irq_low,
+        params.gpio2_irq_high,
+        params.gpio4_irq_low,
+        params.gpio4_irq_high,
+        params.gpio5_irq_low,
+        params.gpio5_irq_high,
+        params.gpio6_irq_low,
+        params.gpio6_irq_high,
+        params.gpio7_irq_low,
+        params.gpio7_irq_high,
+    ];
+
+    let mut broker = Broker {
+        banks,
+        claims: [[None; PINS_PER_BANK as usize]; NUM_BANKS],
+        events: EventQueue::new(),
+    };
+
+    let heartbeat = watchdog::Client::register("gpio", params.heartbeat_producer);
+
+    params.boot_status.ready();
+
+    let server = params.server;
+    let iomux = params.iomux_caller;
+    let mut msg = server.recv();
+    loop {
+        msg = if msg.is_notification() {
+            if msg.badge & BROADCAST_BADGE != 0 {
+                heartbeat.heartbeat();
+            }
+            let bits = msg.badge & !(NOTIFICATION_BADGE | BROADCAST_BADGE);
+            if bits != 0 {
+                broker.handle_interrupts(&server, bits, &irqs);
+            }
+            server.recv()
+        } else {
+            let result = match Request::decode(&msg) {
+                Some(req) => {
+                    log::trace!("[gpio] Client {} request {:?}", msg.badge, req);
+                    broker.handle_request(&iomux, msg.badge, req)
+                }
+                None => Err(Error::InvalidRequest),
+            };
+            if let Err(e) = result {
+                log::debug!("[gpio] Client {} request failed {:?}", msg.badge, e);
+            }
+            let (label, data) = Response::encode(&result);
+            server.reply_recv(label, &data)
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Claim {
+    /// Badge of the owning client
+    owner: usize,
+    output: bool,
+    edge: Option<Edge>,
+}
+
+struct Broker {
+    /// Indexed by bank number - 1, `None` for banks owned elsewhere
+    banks: [Option<Bank>; NUM_BANKS],
+    claims: [[Option<Claim>; PINS_PER_BANK as usize]; NUM_BANKS],
+    events: EventQueue,
+}
+
+impl Broker {
+    fn handle_request(
+        &mut self,
+        iomux: &IomuxCaller,
+        client: usize,
+        req: Request,
+    ) -> Result<Response, Error> {
+        match req {
+            Request::Claim(pin, dir) => {
+                let (bank, claim) = self.pin_mut(pin)?;
+                if claim.is_some() {
+                    return Err(Error::AlreadyClaimed);
+                }
+                if let Direction::Output { high } = dir {
+                    // Latch the level first so the pad doesn't glitch
+                    bank.set_level(pin.pin, high);
+                }
+                bank.set_direction(pin.pin, dir.is_output());
+                // Muxed last, once the GPIO side is set up
+                if let Err(e) = configure_pad(iomux, pin, dir) {
+                    bank.set_direction(pin.pin, false);
+                    return Err(e);
+                }
+                *claim = Some(Claim {
+                    owner: client,
+                    output: dir.is_output(),
+                    edge: None,
+                });
+                log::debug!("[gpio] Client {} claimed {} as {:?}", client, pin, dir);
+                Ok(Response::Done)
+            }
+            Request::Release(pin) => {
+                let (bank, claim) = self.owned_pin_mut(client, pin)?;
+                bank.set_interrupt(pin.pin, None);
+                bank.set_direction(pin.pin, false);
+                *claim = None;
+                self.events.remove_pin(pin);
+                log::debug!("[gpio] Client {} released {}", client, pin);
+                Ok(Response::Done)
+            }
+            Request::SetLevel(pin, high) => {
+                let (bank, claim) = self.owned_pin_mut(client, pin)?;
+                if !claim.map_or(false, |c| c.output) {
+                    return Err(Error::NotAnOutput);
+                }
+                bank.set_level(pin.pin, high);
+                Ok(Response::Done)
+            }
+            Request::GetLevel(pin) => {
+                let (bank, _) = self.owned_pin_mut(client, pin)?;
+                Ok(Response::Level(bank.level(pin.pin)))
+            }
+            Request::Subscribe(pin, edge) => {
+                let (bank, claim) = self.owned_pin_mut(client, pin)?;
+                let claim = claim.as_mut().ok_or(Error::NotClaimed)?;
+                if claim.output {
+                    return Err(Error::NotAnInput);
+                }
+                bank.set_interrupt(pin.pin, Some(edge));
+                claim.edge = Some(edge);
+                Ok(Response::Done)
+            }
+            Request::Unsubscribe(pin) => {
+                let (bank, claim) = self.owned_pin_mut(client, pin)?;
+                bank.set_interrupt(pin.pin, None);
+                if let Some(claim) = claim.as_mut() {
+                    claim.edge = None;
+                }
+                self.events.remove_pin(pin);
+                Ok(Response::Done)
+            }
+            Request::NextEvent => Ok(Response::Event(self.events.take(client))),
+        }
+    }
+
+    /// Records an event for every subscribed pin whose interrupt fired and
+    /// signals its owner, then re-enables the interrupts in `bits`
+    fn handle_interrupts(
+        &mut self,
+        server: &badged_ipc::Server<role::Local>,
+        bits: usize,
+        irqs: &[IrqHandler<role::Local>],
+    ) {
+        for (b, bank) in self.banks.iter_mut().enumerate() {
+            let bank = match bank {
+                Some(bank) => bank,
+                None => continue,
+            };
+            let pending = bank.take_interrupts();
+            if pending == 0 {
+                continue;
+            }
+            for pin in (0..PINS_PER_BANK).filter(|i| pending & (1 << i) != 0) {
+                let claim = match self.claims[b][usize::from(pin)] {
+                    Some(claim) => claim,
+                    None => continue,
+                };
+                let event = Event {
+                    pin: PinId::new(b as u8 + 1, pin),
+                    high: bank.level(pin),
+                };
+                log::trace!("[gpio] {:?} for client {}", event, claim.owner);
+                if self.events.push(claim.owner, event) {
+                    log::warn!("[gpio] Event queue full, dropped the oldest event");
+                }
+                server.signal(claim.owner);
+            }
+        }
+
+        for (n, irq) in irqs.iter().enumerate() {
+            if bits & (1 << n) != 0 {
+                irq.ack();
+            }
+        }
+    }
+
+    fn pin_mut(&mut self, pin: PinId) -> Result<(&mut Bank, &mut Option<Claim>), Error> {
+        let pins = if pin.bank == 7 {
+            GPIO7_PINS
+        } else {
+            PINS_PER_BANK
+        };
+        if !pin.is_valid() || pin.pin >= pins {
+            return Err(Error::InvalidPin);
+        }
+        let b = usize::from(pin.bank - 1);
+        let bank = self.banks[b].as_mut().ok_or(Error::Unavailable)?;
+        Ok((bank, &mut self.claims[b][usize::from(pin.pin)]))
+    }
+
+    fn owned_pin_mut(
+        &mut self,
+        client: usize,
+        pin: PinId,
+    ) -> Result<(&mut Bank, &mut Option<Claim>), Error> {
+        let (bank, claim) = self.pin_mut(pin)?;
+        if claim.map_or(false, |c| c.owner == client) {
+            Ok((bank, claim))
+        } else {
+            Err(Error::NotClaimed)
+        }
+    }
+}
+
+/// Fixed size FIFO of events, tagged with the badge of the client they're for
+struct EventQueue {
+    events: [Option<(usize, Event)>; MAX_PENDING_EVENTS],
+}
+
+impl EventQueue {
+    fn new() -> Self {
+        EventQueue {
+            events: [None; MAX_PENDING_EVENTS],
+        }
+    }
+
+    /// Returns true if the oldest event was dropped to make room
+    fn push(&mut self, client: usize, event: Event) -> bool {
+        let dropped = self.events.iter().all(|e| e.is_some());
+        if dropped {
+            self.events.rotate_left(1);
+            self.events[MAX_PENDING_EVENTS - 1] = None;
+        }
+        if let Some(slot) = self.events.iter_mut().find(|e| e.is_none()) {
+            *slot = Some((client, event));
+        }
+        dropped
+    }
+
+    /// Removes and returns the client's oldest event
+    fn take(&mut self, client: usize) -> Option<Event> {
+        let i = self
+            .events
+            .iter()
+            .position(|e| matches!(e, Some((c, _)) if *c == client))?;
+        let (_, event) = self.events[i].take()?;
+        self.compact();
+        Some(event)
+    }
+
+    fn remove_pin(&mut self, pin: PinId) {
+        for e in self.events.iter_mut() {
+            if matches!(e, Some((_, ev)) if ev.pin == pin) {
+                *e = None;
+            }
+        }
+        self.compact();
+    }
+
+    /// Keeps the pending events at the front, in order
+    fn compact(&mut self) {
+        let mut n = 0;
+        for i in 0..MAX_PENDING_EVENTS {
+            if let Some(e) = self.events[i].take() {
+                self.events[n] = Some(e);
+                n += 1;
+            }
+        }
+    }
+}
+
+/// Muxes the pin's pad to GPIO with the pad control `dir` expects, pulls
+/// included
+fn configure_pad(iomux: &IomuxCaller, pin: PinId, dir: Direction) -> Result<(), Error> {
+    let req = iomux::Request::ConfigureGpio {
+        bank: pin.bank,
+        pin: pin.pin,
+        pad_control: dir.pad_control(),
+    };
+    match iomux.blocking_call(&req) {
+        Ok(iomux::Response::GpioConfigured) => Ok(()),
+        resp => {
+            log::warn!(
+                "[gpio] Failed to configure the pad of {} resp={:?}",
+                pin,
+                resp
+            );
+            Err(Error::PadUnavailable)
+        }
+    }
+}
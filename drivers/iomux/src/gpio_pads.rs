@@ -0,0 +1,58 @@
+//! Pads that can be muxed to a GPIO pin, all of them are ALT5
+//!
+//! Only covers pins of the banks owned by the gpio driver that are routed
+//! somewhere on the sabrelite, add pads here as they're needed.
+
+use imx6_hal::pac::iomuxc::{MuxControl, IOMUXC};
+
+macro_rules! gpio_pads {
+    ($(($bank:literal, $pin:literal) => ($mux:ident, $pad:ident),)+) => {
+        /// Muxes the pad of GPIO`bank`_IO`pin` to GPIO and applies
+        /// `pad_control`, returns false if the pin has no known pad
+        pub fn configure(iomuxc: &mut IOMUXC, bank: u8, pin: u8, pad_control: u32) -> bool {
+            match (bank, pin) {
+                $(
+                    ($bank, $pin) => {
+                        log::trace!(
+                            "[iomux] {}__GPIO{}_IO{:02} pad_control={:#X}",
+                            stringify!($pad),
+                            bank,
+                            pin,
+                            pad_control
+                        );
+                        // Pad control first, so the pad comes up configured
+                        unsafe { iomuxc.$pad.write(pad_control) };
+                        iomuxc.$mux.modify(MuxControl::MuxMode::ALT5);
+                        true
+                    }
+                )+
+                _ => false,
+            }
+        }
+    };
+}
+
+gpio_pads!(
+    (1, 0) => (sw_mux_ctl_pad_gpio00, sw_pad_ctl_pad_mux00),
+    (1, 1) => (sw_mux_ctl_pad_gpio01, sw_pad_ctl_pad_mux01),
+    (1, 2) => (sw_mux_ctl_pad_gpio02, sw_pad_ctl_pad_mux02),
+    (1, 3) => (sw_mux_ctl_pad_gpio03, sw_pad_ctl_pad_mux03),
+    (1, 4) => (sw_mux_ctl_pad_gpio04, sw_pad_ctl_pad_mux04),
+    (1, 5) => (sw_mux_ctl_pad_gpio05, sw_pad_ctl_pad_mux05),
+    (1, 6) => (sw_mux_ctl_pad_gpio06, sw_pad_ctl_pad_mux06),
+    (1, 7) => (sw_mux_ctl_pad_gpio07, sw_pad_ctl_pad_mux07),
+    (1, 8) => (sw_mux_ctl_pad_gpio08, sw_pad_ctl_pad_mux08),
+    (1, 9) => (sw_mux_ctl_pad_gpio09, sw_pad_ctl_pad_mux09),
+    (2, 0) => (sw_mux_ctl_pad_nand_data00, sw_pad_ctl_pad_nand_data00),
+    (2, 1) => (sw_mux_ctl_pad_nand_data01, sw_pad_ctl_pad_nand_data01),
+    (2, 2) => (sw_mux_ctl_pad_nand_data02, sw_pad_ctl_pad_nand_data02),
+    (2, 3) => (sw_mux_ctl_pad_nand_data03, sw_pad_ctl_pad_nand_data03),
+    (2, 4) => (sw_mux_ctl_pad_nand_data04, sw_pad_ctl_pad_nand_data04),
+    (2, 5) => (sw_mux_ctl_pad_nand_data05, sw_pad_ctl_pad_nand_data05),
+    (2, 6) => (sw_mux_ctl_pad_nand_data06, sw_pad_ctl_pad_nand_data06),
+    (2, 7) => (sw_mux_ctl_pad_nand_data07, sw_pad_ctl_pad_nand_data07),
+    (4, 5) => (sw_mux_ctl_pad_gpio19, sw_pad_ctl_pad_mux19),
+    (7, 11) => (sw_mux_ctl_pad_gpio16, sw_pad_ctl_pad_mux16),
+    (7, 12) => (sw_mux_ctl_pad_gpio17, sw_pad_ctl_pad_mux17),
+    (7, 13) => (sw_mux_ctl_pad_gpio18, sw_pad_ctl_pad_mux18),
+);
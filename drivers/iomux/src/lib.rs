@@ -1,25 +1,39 @@
 #![no_std]
 
+use badged_ipc::{BootStatusSender, Responder};
 use ferros::cap::{role, CNodeRole};
-use ferros::userland::{Responder, RetypeForSetup};
+use ferros::userland::{Producer, RetypeForSetup};
 use imx6_hal::pac::iomuxc::IOMUXC;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Request {
     ConfigureEcSpi1,
     ConfigureUart1,
+    /// Mux the pad of GPIO`bank`_IO`pin` to GPIO and apply `pad_control`,
+    /// see `imx6_hal::gpio::PadControl`
+    ConfigureGpio {
+        bank: u8,
+        pin: u8,
+        pad_control: u32,
+    },
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Response {
     EcSpi1Configured,
     Uart1Configured,
+    GpioConfigured,
+    /// The GPIO has no pad in the driver's table
+    UnknownGpioPad,
 }
 
 #[repr(C)]
 pub struct ProcParams<Role: CNodeRole> {
     pub iomuxc: IOMUXC,
     pub responder: Responder<Request, Response, Role>,
+    /// Heartbeats to the watchdog service, sent on each of its ticks
+    pub heartbeat_producer: Producer<Role, watchdog::Message>,
+    pub boot_status: BootStatusSender<Role>,
 }
 
 impl RetypeForSetup for ProcParams<role::Local> {
@@ -3,27 +3,33 @@
 
 use selfe_runtime as _;
 
+use badged_ipc::BROADCAST_BADGE;
 use debug_logger::DebugLogger;
 use ferros::cap::role;
 use imx6_hal::pac::{iomuxc::*, typenum};
 use iomux::{ProcParams, Request, Response};
 
+mod gpio_pads;
+
 static LOGGER: DebugLogger = DebugLogger;
 
 #[allow(improper_ctypes_definitions)]
 #[no_mangle]
 pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
+    let restarted = unsafe { process_restart::reset_statics() }.unwrap();
     log::set_logger(&LOGGER)
         .map(|()| log::set_max_level(DebugLogger::max_log_level_from_env()))
         .unwrap();
 
-    log::debug!("[iomux] Process started");
+    log::debug!("[iomux] Process started restarted={}", restarted);
 
     let mut iomuxc = params.iomuxc;
+    let heartbeat = watchdog::Client::register("iomux", params.heartbeat_producer);
+
+    params.boot_status.ready();
 
-    params
-        .responder
-        .reply_recv(move |req| {
+    params.responder.reply_recv_with_signals(
+        move |_badge, req| {
             log::debug!("[iomux] Processing request {:?}", req);
             match req {
                 Request::ConfigureEcSpi1 => {
@@ -90,13 +96,24 @@ pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
                         .modify(PadControl::Bits::Field::new(0x1B0B1).unwrap());
                     Response::Uart1Configured
                 }
+                Request::ConfigureGpio {
+                    bank,
+                    pin,
+                    pad_control,
+                } => {
+                    if gpio_pads::configure(&mut iomuxc, bank, pin, pad_control) {
+                        Response::GpioConfigured
+                    } else {
+                        log::warn!("[iomux] No pad known for GPIO{}_IO{:02}", bank, pin);
+                        Response::UnknownGpioPad
+                    }
+                }
             }
-        })
-        .expect("Could not set up a reply_recv");
-
-    unsafe {
-        loop {
-            selfe_sys::seL4_Yield();
-        }
-    }
+        },
+        move |bits| {
+            if bits & BROADCAST_BADGE != 0 {
+                heartbeat.heartbeat();
+            }
+        },
+    )
 }
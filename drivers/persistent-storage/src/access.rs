@@ -0,0 +1,96 @@
+//! Client access to keys and namespace quotas
+//!
+//! Callers are told apart by their badge, which picks their grants from
+//! `CLIENTS`. A key belongs to the namespace whose prefix it starts with,
+//! the quota counts the names and values of the namespace's stored keys.
+
+use crate::FlashController;
+use flash_storage::values::{self, ValueBuffer};
+use flash_storage::{Journal, KeyIndex};
+use persistent_storage::{
+    Access, Error, ErrorCode, Key, Namespace, StorageClient, Value, CLIENTS, NAMESPACES,
+    REGION_SIZE_BYTES,
+};
+use tickv::TicKV;
+
+pub fn client(badge: usize) -> Option<&'static StorageClient> {
+    badge.checked_sub(1).and_then(|i| CLIENTS.get(i))
+}
+
+/// The caller's access to `key`, `None` if it has none
+pub fn access(badge: usize, key: &Key) -> Option<Access> {
+    client(badge)?
+        .grants
+        .iter()
+        .find(|g| key.starts_with(g.prefix))
+        .map(|g| g.access)
+}
+
+pub fn check_read(badge: usize, key: &Key) -> Result<(), Error> {
+    access(badge, key)
+        .map(|_| ())
+        .ok_or(Error::PermissionDenied)
+}
+
+pub fn check_write(badge: usize, key: &Key) -> Result<(), Error> {
+    match access(badge, key) {
+        Some(Access::ReadWrite) => Ok(()),
+        _ => Err(Error::PermissionDenied),
+    }
+}
+
+/// Fails with `Error::PermissionDenied` unless the caller can write some
+/// key, for requests on the whole store rather than a key
+pub fn check_any_write(badge: usize) -> Result<(), Error> {
+    let client = client(badge).ok_or(Error::PermissionDenied)?;
+    if client.grants.iter().any(|g| g.access == Access::ReadWrite) {
+        Ok(())
+    } else {
+        Err(Error::PermissionDenied)
+    }
+}
+
+fn namespace(key: &Key) -> Option<&'static Namespace> {
+    NAMESPACES.iter().find(|n| key.starts_with(n.prefix))
+}
+
+/// Fails with `Error::QuotaExceeded` if storing `value` under `key` would
+/// take its namespace over quota, once the changes `staged` in the journal
+/// are applied. Keys whose value can't be read don't count
+pub fn check_quota(
+    tickv: &TicKV<FlashController, REGION_SIZE_BYTES>,
+    key_index: &KeyIndex,
+    staged: Option<&Journal>,
+    key: &Key,
+    value: &Value,
+    buf: &mut ValueBuffer,
+) -> Result<(), Error> {
+    let ns = namespace(key).ok_or(Error::PermissionDenied)?;
+    let counts = |k: &Key| k.starts_with(ns.prefix) && k != key;
+    // The staged value of `k`, `Some(None)` if it's staged to be invalidated
+    let staged_value =
+        |k: &Key| staged.and_then(|j| j.staged().find(|(s, _)| s == k).map(|(_, v)| v));
+    let mut used = key.len() + value.len();
+    for k in key_index.keys().filter(|k| counts(k)) {
+        match staged_value(k) {
+            Some(Some(v)) => used += k.len() + v.len(),
+            Some(None) => (),
+            None => match values::get(tickv, k, key_index.value_slot(k), buf) {
+                Ok(v) => used += k.len() + v.len(),
+                Err(ErrorCode::KeyNotFound) | Err(ErrorCode::CorruptData) => (),
+                Err(e) => return Err(e.into()),
+            },
+        }
+    }
+    // Keys the transaction adds
+    for (k, v) in staged.into_iter().flat_map(|j| j.staged()) {
+        if let Some(v) = v.filter(|_| counts(&k) && !key_index.contains(&k)) {
+            used += k.len() + v.len();
+        }
+    }
+    if used > ns.quota_bytes {
+        Err(Error::QuotaExceeded)
+    } else {
+        Ok(())
+    }
+}
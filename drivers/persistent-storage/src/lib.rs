@@ -1,38 +1,242 @@
 #![no_std]
 
+use badged_ipc::{BootStatusSender, Client, IrqHandler, Responder};
 use core::fmt;
 use ferros::cap::{role, CNodeRole};
-use ferros::userland::{Caller, Responder, RetypeForSetup};
+use ferros::userland::{Producer, RetypeForSetup};
 use ferros::vspace::{shared_status, MappedMemoryRegion};
-use heapless::String;
+use flash_storage::{journal, MAX_REGIONS};
+pub use flash_storage::{
+    ErrorCode, Key, SetCode, SuccessCode, Value, MAX_KEYS, MAX_KEY_SIZE, MAX_VALUE_SIZE,
+};
+use heapless::Vec;
 use imx6_hal::pac::{
-    ecspi1::ECSPI1,
-    gpio::GPIO3,
+    epit::epit1::EPIT1,
     typenum::{op, U1, U12},
 };
-pub use tickv::{success_codes::SuccessCode, ErrorCode};
 
-pub const MAX_KEY_SIZE: usize = 32;
-pub type Key = String<MAX_KEY_SIZE>;
+/// Keys per `ListKeys` response, keeps it no larger than one carrying a
+/// `Value`
+pub const KEYS_PER_PAGE: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyInfo {
+    pub key: Key,
+    pub value_len: usize,
+}
+
+/// Keys starting with `prefix`, their names and values take up to
+/// `quota_bytes` together
+#[derive(Debug)]
+pub struct Namespace {
+    pub prefix: &'static str,
+    pub quota_bytes: usize,
+}
 
-pub const MAX_VALUE_SIZE: usize = 256;
-pub type Value = String<MAX_VALUE_SIZE>;
+pub const NAMESPACES: [Namespace; 2] = [
+    // The network configuration, see net_types::NetConfig
+    Namespace {
+        prefix: "net.",
+        quota_bytes: 256,
+    },
+    Namespace {
+        prefix: "console/",
+        quota_bytes: 2048,
+    },
+];
 
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Access {
+    ReadOnly,
+    ReadWrite,
+}
+
+/// A client's access to the namespace with `prefix`
+#[derive(Debug)]
+pub struct Grant {
+    pub prefix: &'static str,
+    pub access: Access,
+}
+
+#[derive(Debug)]
+pub struct StorageClient {
+    pub name: &'static str,
+    pub grants: &'static [Grant],
+}
+
+/// Clients of the storage, indexed by caller badge - 1, the root task
+/// checks the order against the storage callers in system.toml through
+/// `CLIENT_NAMES`. Keys outside a client's grants fail with
+/// `Error::PermissionDenied` and aren't listed for it, so do
+/// `GarbageCollect`, the transaction requests and `Stats` unless it has a
+/// `ReadWrite` grant. Unknown badges are denied everything
+pub const CLIENTS: [StorageClient; 3] = [
+    StorageClient {
+        name: "enet",
+        grants: &[Grant {
+            prefix: "net.",
+            access: Access::ReadOnly,
+        }],
+    },
+    StorageClient {
+        name: "tcpip",
+        grants: &[Grant {
+            prefix: "net.",
+            access: Access::ReadOnly,
+        }],
+    },
+    StorageClient {
+        name: "console",
+        grants: &[
+            Grant {
+                prefix: "net.",
+                access: Access::ReadWrite,
+            },
+            Grant {
+                prefix: "console/",
+                access: Access::ReadWrite,
+            },
+        ],
+    },
+];
+
+/// Names of `CLIENTS` in badge order
+pub const CLIENT_NAMES: [&str; CLIENTS.len()] = {
+    let mut names = [""; CLIENTS.len()];
+    let mut i = 0;
+    while i < names.len() {
+        names[i] = CLIENTS[i].name;
+        i += 1;
+    }
+    names
+};
+
+/// Storage usage, see `Request::Stats`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    /// Size of the storage partition
+    pub total_bytes: usize,
+    /// Taken by valid objects, the stored keys and values along with their
+    /// names and the storage's own bookkeeping
+    pub live_bytes: usize,
+    /// Taken by invalidated objects, `GarbageCollect` reclaims them
+    pub reclaimable_bytes: usize,
+    /// Number of valid objects
+    pub objects: usize,
+    /// Erases of each region since boot, they aren't kept across boots
+    pub erases_since_boot: Vec<u32, MAX_REGIONS>,
+}
+
+impl Stats {
+    /// Erased space left for new objects
+    pub fn free_bytes(&self) -> usize {
+        self.total_bytes
+            .saturating_sub(self.live_bytes + self.reclaimable_bytes)
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes, {} live, {} reclaimable, {} free, {} objects, erases since boot {:?}",
+            self.total_bytes,
+            self.live_bytes,
+            self.reclaimable_bytes,
+            self.free_bytes(),
+            self.objects,
+            self.erases_since_boot.as_slice()
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// TicKV failed
+    Storage(ErrorCode),
+    /// `BeginTransaction` while a transaction is open
+    TransactionOpen,
+    /// `Commit` or `Abort` without an open transaction
+    NoTransaction,
+    /// The staged changes don't fit in the journal
+    TransactionFull,
+    /// The caller's transaction timed out and was aborted. Its appends,
+    /// sets, invalidates, `Commit` and `Abort` fail with this until it
+    /// begins another
+    TransactionExpired,
+    /// The key is outside the caller's grants, or it only has read access.
+    /// Also for requests on the whole store from a caller with no
+    /// `ReadWrite` grant, and for any request from an unknown badge
+    PermissionDenied,
+    /// The key's namespace would take up more than its quota
+    QuotaExceeded,
+}
+
+impl From<ErrorCode> for Error {
+    fn from(e: ErrorCode) -> Self {
+        Error::Storage(e)
+    }
+}
+
+impl From<journal::Error> for Error {
+    fn from(e: journal::Error) -> Self {
+        match e {
+            journal::Error::Storage(e) => Error::Storage(e),
+            journal::Error::TransactionOpen => Error::TransactionOpen,
+            journal::Error::NoTransaction => Error::NoTransaction,
+            journal::Error::TransactionFull => Error::TransactionFull,
+        }
+    }
+}
+
+/// `AppendKey` and `Get` are the string API over `AppendBytes` and
+/// `GetBytes`, `Get` fails with `ErrorCode::CorruptData` if the value
+/// isn't UTF-8
 #[derive(Debug, Clone, PartialEq)]
 pub enum Request {
     AppendKey(Key, Value),
     Get(Key),
+    AppendBytes(Key, Value),
+    GetBytes(Key),
+    /// Insert the key or replace its value, either the old or the new value
+    /// survives a power cut
+    SetKey(Key, Value),
     InvalidateKey(Key),
     GarbageCollect,
+    /// Up to `KEYS_PER_PAGE` keys from `cursor`, start at 0 and continue
+    /// from the `next_cursor` of the previous response
+    ListKeys {
+        cursor: usize,
+    },
+    /// Stage the caller's appends, sets and invalidates that follow until
+    /// `Commit`, which applies all of them or, if power is lost, none.
+    /// Reads see the committed values, there's one transaction at a time
+    /// across all callers. It's aborted if the caller makes no request for
+    /// `TRANSACTION_TIMEOUT_TICKS`, see `Error::TransactionExpired`
+    BeginTransaction,
+    Commit,
+    Abort,
+    /// Usage of the partition, see `Stats`
+    Stats,
 }
 
 impl fmt::Display for Request {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Request::AppendKey(k, v) => write!(f, "AppendKey({}, {})", k.as_str(), v.as_str()),
+            Request::AppendKey(k, v) => write!(f, "AppendKey({}, {})", k.as_str(), v),
             Request::Get(k) => write!(f, "Get({})", k.as_str()),
+            Request::AppendBytes(k, v) => {
+                write!(f, "AppendBytes({}, {} bytes)", k.as_str(), v.len())
+            }
+            Request::GetBytes(k) => write!(f, "GetBytes({})", k.as_str()),
+            Request::SetKey(k, v) => write!(f, "SetKey({}, {})", k.as_str(), v),
             Request::InvalidateKey(k) => write!(f, "InvalidateKey({})", k.as_str()),
             Request::GarbageCollect => write!(f, "GarbageCollect"),
+            Request::ListKeys { cursor } => write!(f, "ListKeys({})", cursor),
+            Request::BeginTransaction => write!(f, "BeginTransaction"),
+            Request::Commit => write!(f, "Commit"),
+            Request::Abort => write!(f, "Abort"),
+            Request::Stats => write!(f, "Stats"),
         }
     }
 }
@@ -42,22 +246,66 @@ impl fmt::Display for Request {
 pub enum Response {
     KeyAppended(SuccessCode),
     Value(Value),
+    Bytes(Value),
+    KeySet(SetCode),
     KeyInvalidated(SuccessCode),
     GarbageCollected(usize),
+    Keys {
+        keys: Vec<KeyInfo, KEYS_PER_PAGE>,
+        /// `None` once there are no more keys
+        next_cursor: Option<usize>,
+    },
+    TransactionBegun,
+    /// The change will be applied on `Commit`
+    Staged,
+    /// Number of changes applied
+    Committed(usize),
+    /// Number of changes dropped
+    Aborted(usize),
+    Stats(Stats),
 }
 
 impl fmt::Display for Response {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Response::KeyAppended(sc) => write!(f, "KeyAppended({:?})", sc),
-            Response::Value(v) => write!(f, "Value({})", v.as_str()),
+            Response::Value(v) => write!(f, "Value({})", v),
+            Response::Bytes(v) => write!(f, "Bytes({} bytes, {:X})", v.len(), v),
+            Response::KeySet(sc) => write!(f, "KeySet({:?})", sc),
             Response::KeyInvalidated(sc) => write!(f, "KeyInvalidated({:?})", sc),
             Response::GarbageCollected(size) => write!(f, "GarbageCollected({} bytes freed)", size),
+            Response::Keys { keys, next_cursor } => {
+                write!(f, "Keys(")?;
+                for (i, k) in keys.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {} bytes", k.key.as_str(), k.value_len)?;
+                }
+                match next_cursor {
+                    Some(c) => write!(f, ", next {})", c),
+                    None => write!(f, ")"),
+                }
+            }
+            Response::TransactionBegun => write!(f, "TransactionBegun"),
+            Response::Staged => write!(f, "Staged"),
+            Response::Committed(n) => write!(f, "Committed({} changes)", n),
+            Response::Aborted(n) => write!(f, "Aborted({} changes)", n),
+            Response::Stats(s) => write!(f, "Stats({})", s),
         }
     }
 }
 
-/// 4K buffer for persistent storage in flash (1 sector)
+/// Persistent storage is a partition of TicKV regions at the end of the
+/// flash, each a multiple of the flash's sector size
+pub const REGION_SIZE_BYTES: usize = 4096;
+
+/// The partition is this fraction of the detected flash capacity, the
+/// smallest top area the block protect bits can leave unlocked, capped at
+/// `flash_storage::MAX_REGIONS` regions
+pub const PARTITION_FRACTION: usize = 32;
+
+/// 4K buffer TicKV reads a region into
 pub type StorageBufferSizeBits = U12;
 pub type StorageBufferSizeBytes = op! { U1 << StorageBufferSizeBits };
 
@@ -65,14 +313,36 @@ pub type StorageBufferSizeBytes = op! { U1 << StorageBufferSizeBits };
 pub type ScratchpadBufferSizeBits = U12;
 pub type ScratchpadBufferSizeBytes = op! { U1 << ScratchpadBufferSizeBits };
 
+/// 4K buffer a transaction's changes are staged in
+pub type JournalBufferSizeBits = U12;
+pub type JournalBufferSizeBytes = op! { U1 << JournalBufferSizeBits };
+
+/// Largest journal, half a region so it fits alongside other objects
+pub const MAX_JOURNAL_SIZE: usize = REGION_SIZE_BYTES / 2;
+
+/// Watchdog ticks an open transaction is kept without a request from its
+/// caller, so one left by a client which restarted doesn't hold the lock
+/// for good
+pub const TRANSACTION_TIMEOUT_TICKS: usize = 3;
+
 #[repr(C)]
 pub struct ProcParams<Role: CNodeRole> {
-    pub spi: ECSPI1,
-    pub gpio3: GPIO3,
-    pub iomux_caller: Caller<iomux::Request, iomux::Response, Role>,
-    pub responder: Responder<Request, Result<Response, ErrorCode>, Role>,
+    /// The SPI NOR flash on the spi-bus
+    pub spi_client: Client<Role>,
+    pub spi_buffer: spi_bus::TransactionBuffer,
+    /// Paces the flash busy polling
+    pub epit: EPIT1,
+    pub epit_irq: IrqHandler<Role>,
+    /// Put the flash in deep power-down once it's been idle for a watchdog
+    /// tick period, on parts that support it
+    pub flash_idle_power_down: bool,
+    pub responder: Responder<Request, Result<Response, Error>, Role>,
     pub storage_buffer: MappedMemoryRegion<StorageBufferSizeBits, shared_status::Exclusive>,
     pub scratchpad_buffer: MappedMemoryRegion<ScratchpadBufferSizeBits, shared_status::Exclusive>,
+    pub journal_buffer: MappedMemoryRegion<JournalBufferSizeBits, shared_status::Exclusive>,
+    /// Heartbeats to the watchdog service, sent on each of its ticks
+    pub heartbeat_producer: Producer<Role, watchdog::Message>,
+    pub boot_status: BootStatusSender<Role>,
 }
 
 impl RetypeForSetup for ProcParams<role::Local> {
@@ -3,39 +3,52 @@
 
 use selfe_runtime as _;
 
-use crate::flash_controller::SpiNorFlashController;
+use badged_ipc::{IrqHandler, BROADCAST_BADGE};
+use core::cell::Cell;
 use core::convert::TryInto;
 use core::hash::{Hash, Hasher};
-use core::str;
 use debug_logger::DebugLogger;
 use ferros::cap::role;
+use flash_storage::values::{self, Slot, ValueBuffer, FORMAT_VERSION, VALUE_HEADER_SIZE};
+use flash_storage::{usage, Journal, KeyIndex, SpiNorFlashController, MAX_REGIONS};
 use imx6_hal::{
-    gpio::GpioExt,
-    pac::typenum::Unsigned,
-    spi::Spi,
-    spi_nor_flash::{SpiNorFlash, ERASE_SIZE_BYTES},
+    delay::Delay, pac::epit::epit1::EPIT1, pac::typenum::Unsigned, spi_nor_flash::SpiNorFlash,
+    wait::Wait,
 };
 use persistent_storage::{
-    ProcParams, Request, Response, StorageBufferSizeBytes, Value, MAX_VALUE_SIZE,
+    Error, Key, KeyInfo, ProcParams, Request, Response, Stats, StorageBufferSizeBytes, CLIENTS,
+    MAX_JOURNAL_SIZE, MAX_VALUE_SIZE, PARTITION_FRACTION, REGION_SIZE_BYTES,
+    TRANSACTION_TIMEOUT_TICKS,
 };
 use siphasher::sip::SipHasher;
+use spi_bus::SpiDevice;
 use static_assertions::const_assert_eq;
 use tickv::{ErrorCode, TicKV, MAIN_KEY};
 
-mod flash_controller;
+mod access;
 
 static LOGGER: DebugLogger = DebugLogger;
 
-const_assert_eq!(StorageBufferSizeBytes::USIZE, ERASE_SIZE_BYTES);
+type Flash = SpiNorFlash<SpiDevice, Delay<EPIT1, EpitIrq>>;
+type FlashController<'a> = SpiNorFlashController<'a, Flash, REGION_SIZE_BYTES>;
+
+const_assert_eq!(StorageBufferSizeBytes::USIZE, REGION_SIZE_BYTES);
+
+/// A warning is logged when the erased space drops below this
+const LOW_SPACE_BYTES: usize = 2 * REGION_SIZE_BYTES;
 
 #[allow(improper_ctypes_definitions)]
 #[no_mangle]
 pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
+    let restarted = unsafe { process_restart::reset_statics() }.unwrap();
     log::set_logger(&LOGGER)
         .map(|()| log::set_max_level(DebugLogger::max_log_level_from_env()))
         .unwrap();
 
-    log::debug!("[persistent-storage] Process started",);
+    log::debug!(
+        "[persistent-storage] Process started restarted={}",
+        restarted
+    );
 
     log::debug!(
         "[persistent-storage] storage vaddr=0x{:X} size={}",
@@ -49,8 +62,8 @@ pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
         params.scratchpad_buffer.size_bytes()
     );
 
-    // Local storage for a Value on the stack
-    let mut value_buffer: [u8; MAX_VALUE_SIZE] = [0; MAX_VALUE_SIZE];
+    // Local storage for a stored Value on the stack
+    let mut value_buffer: ValueBuffer = [0; VALUE_HEADER_SIZE + MAX_VALUE_SIZE];
 
     // Scratchpad mem to deal with flash sub-page size writes (read-modify-write)
     let mut scratchpad_buffer = params.scratchpad_buffer;
@@ -59,84 +72,459 @@ pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
     // TickV expects an array ref
     let mut storage_buffer = params.storage_buffer;
     let storage_buffer_slice = storage_buffer.as_mut_slice();
-    let storage_buffer_array: &mut [u8; ERASE_SIZE_BYTES] =
+    let storage_buffer_array: &mut [u8; REGION_SIZE_BYTES] =
         storage_buffer_slice.try_into().unwrap();
 
-    // Configure ECSPI1 IO
-    let resp = params
-        .iomux_caller
-        .blocking_call(&iomux::Request::ConfigureEcSpi1)
-        .unwrap();
-    log::debug!("[persistent-storage] Configured ECSPI1 IO resp={:?}", resp);
+    let spi = SpiDevice::new(params.spi_client, params.spi_buffer);
 
-    let gpio = params.gpio3.split();
-    let spi_nor_cs_pin = gpio.bank3.p3_19.into_push_pull_output();
-    let spi = Spi::new(params.spi);
+    let delay = Delay::new(params.epit).with_wait(EpitIrq(params.epit_irq));
 
-    let spi_nor_flash = SpiNorFlash::init(spi, spi_nor_cs_pin).unwrap();
-    let flash = SpiNorFlashController::new(spi_nor_flash, scratchpad_buffer_slice).unwrap();
+    let spi_nor_flash = match SpiNorFlash::init(spi, delay) {
+        Ok(f) => f,
+        Err(e) => {
+            log::error!(
+                "[persistent-storage] Failed to initialize the flash {:?}",
+                e
+            );
+            params.boot_status.failed();
+            halt();
+        }
+    };
+    let regions = storage_regions(spi_nor_flash.geometry().capacity_bytes);
+    let flash = match FlashController::new(spi_nor_flash, scratchpad_buffer_slice, regions) {
+        Ok(f) => f,
+        Err(e) => {
+            log::error!(
+                "[persistent-storage] Failed to set up the storage region {:?}",
+                e
+            );
+            params.boot_status.failed();
+            halt();
+        }
+    };
 
-    let tickv = TicKV::<SpiNorFlashController, ERASE_SIZE_BYTES>::new(
+    let partition_size = flash.partition_size_bytes();
+    let tickv = TicKV::<FlashController, REGION_SIZE_BYTES>::new(
         flash,
         storage_buffer_array,
-        StorageBufferSizeBytes::USIZE,
+        partition_size,
     );
 
+    // A partition of another format version doesn't have this main key,
+    // TicKV erases it and starts over rather than misreading its values
     let mut hasher = SipHasher::new();
-    MAIN_KEY.hash(&mut hasher);
-    tickv.initalise(hasher.finish()).unwrap();
-
-    params
-        .responder
-        .reply_recv(move |req| {
-            log::debug!("[persistent-storage] Processing request {}", req);
-            let resp = match req {
-                Request::AppendKey(key, value) => {
-                    let key_hash = get_hashed_key(key.as_bytes());
-                    tickv
-                        .append_key(key_hash, value.as_bytes())
-                        .map(Response::KeyAppended)
-                }
-                Request::Get(key) => {
-                    let key_hash = get_hashed_key(key.as_bytes());
-                    value_buffer.fill(0);
-                    match tickv.get_key(key_hash, &mut value_buffer) {
-                        Ok(_sc) => {
-                            // Make sure it's UTF-8
-                            if let Ok(s) = str::from_utf8(&value_buffer) {
-                                Ok(Response::Value(Value::from(s)))
-                            } else {
-                                Err(ErrorCode::CorruptData)
-                            }
-                        }
-                        Err(ec) => Err(ec),
-                    }
-                }
-                Request::InvalidateKey(key) => {
-                    let key_hash = get_hashed_key(key.as_bytes());
-                    tickv.invalidate_key(key_hash).map(Response::KeyInvalidated)
-                }
-                Request::GarbageCollect => tickv.garbage_collect().map(Response::GarbageCollected),
-            };
+    (MAIN_KEY, FORMAT_VERSION).hash(&mut hasher);
+    log::debug!(
+        "[persistent-storage] Storage format version {}",
+        FORMAT_VERSION
+    );
+    if let Err(e) = tickv.initalise(hasher.finish()) {
+        log::error!("[persistent-storage] Failed to initialize storage {:?}", e);
+        params.boot_status.failed();
+        halt();
+    }
+
+    let mut key_index = KeyIndex::load(&tickv, &mut value_buffer);
+
+    let mut journal_buffer = params.journal_buffer;
+    let mut journal = Journal::new(&mut journal_buffer.as_mut_slice()[..MAX_JOURNAL_SIZE]);
+    if let Err(e) = recover_journal(&tickv, &mut journal, &mut key_index, &mut value_buffer) {
+        log::warn!(
+            "[persistent-storage] Failed to finish a transaction {:?}",
+            e
+        );
+    }
+
+    let mut state = State {
+        key_index,
+        journal,
+        value_buffer,
+        expired: [false; CLIENTS.len()],
+        low_space: false,
+    };
+    check_free_space(&tickv, &mut state);
+
+    let heartbeat = watchdog::Client::register("persistent-storage", params.heartbeat_producer);
+
+    params.boot_status.ready();
+
+    let idle_power_down = params.flash_idle_power_down && {
+        let supported = tickv.controller.inner().supports_power_down();
+        if !supported {
+            log::info!("[persistent-storage] The flash doesn't support deep power-down");
+        }
+        supported
+    };
+    // Whether a request was handled since the last watchdog tick
+    let active = Cell::new(true);
+    // Watchdog ticks since the transaction's caller last made a request
+    let owner_idle_ticks = Cell::new(0);
+    let (tickv, active, owner_idle_ticks) = (&tickv, &active, &owner_idle_ticks);
+
+    params.responder.reply_recv_with_signals(
+        move |badge, req| {
+            active.set(true);
+            if owner_idle_ticks.get() >= TRANSACTION_TIMEOUT_TICKS {
+                expire_transaction(&mut state);
+            }
+            log::debug!(
+                "[persistent-storage] Processing request {} from {}",
+                req,
+                access::client(badge).map_or("unknown client", |c| c.name)
+            );
+            let resp = handle(tickv, &mut state, badge, req);
+            if state.journal.is_open_by(badge) {
+                owner_idle_ticks.set(0);
+            }
+            if let Ok(
+                Response::KeyAppended(_)
+                | Response::KeySet(_)
+                | Response::Committed(_)
+                | Response::GarbageCollected(_),
+            ) = resp
+            {
+                check_free_space(tickv, &mut state);
+            }
             if let Ok(r) = &resp {
                 log::debug!("[persistent-storage] Response {}", r);
             } else {
                 log::debug!("[persistent-storage] Response {:?}", resp);
             }
             resp
-        })
-        .expect("Could not set up a reply_recv")
-        .expect("Failure on reply_recv");
+        },
+        move |bits| {
+            if bits & BROADCAST_BADGE != 0 {
+                heartbeat.heartbeat();
+                owner_idle_ticks.set(owner_idle_ticks.get().saturating_add(1));
+                // The ticks double as the idle timer
+                if idle_power_down && !active.replace(false) {
+                    power_down(&tickv.controller);
+                }
+            }
+        },
+    )
+}
+
+/// Called once the flash has been idle for a whole watchdog tick period,
+/// the next request wakes it
+fn power_down(flash: &FlashController) {
+    if let Err(e) = flash.power_down() {
+        log::warn!(
+            "[persistent-storage] Failed to power down the flash {:?}",
+            e
+        );
+    }
+}
+
+/// Abort an open transaction whose caller has gone quiet, likely restarted,
+/// and remember it so its writes aren't applied outside of a transaction
+fn expire_transaction(state: &mut State) {
+    if let Some(owner) = state.journal.owner() {
+        if let Ok(n) = state.journal.abort(owner) {
+            state.expired[owner - 1] = true;
+            log::warn!(
+                "[persistent-storage] Aborted the idle transaction of {} with {} changes",
+                access::client(owner).map_or("unknown client", |c| c.name),
+                n
+            );
+        }
+    }
+}
+
+/// Apply a journal left pending by a commit which failed part way, or
+/// found at boot
+fn recover_journal(
+    tickv: &TicKV<FlashController, REGION_SIZE_BYTES>,
+    journal: &mut Journal,
+    key_index: &mut KeyIndex,
+    value_buffer: &mut ValueBuffer,
+) -> Result<(), Error> {
+    let n = journal.recover(tickv, key_index, value_buffer)?;
+    if n != 0 {
+        log::info!(
+            "[persistent-storage] Finished a transaction of {} changes",
+            n
+        );
+    }
+    Ok(())
+}
+
+/// Blocks on the EPIT1 interrupt, acking it first re-enables it after
+/// the previous wait
+struct EpitIrq(IrqHandler<role::Local>);
+
+impl Wait for EpitIrq {
+    const INTERRUPTS: bool = true;
+
+    fn wait(&mut self) {
+        self.0.ack();
+        self.0.wait();
+    }
+}
+
+struct State<'a> {
+    key_index: KeyIndex,
+    journal: Journal<'a>,
+    value_buffer: ValueBuffer,
+    /// Whether the transaction of each client, indexed by badge - 1, timed
+    /// out since it last began one, see `Error::TransactionExpired`
+    expired: [bool; CLIENTS.len()],
+    /// Whether the low space warning has been logged
+    low_space: bool,
+}
 
+/// Handle a request from the caller with `badge`
+fn handle(
+    tickv: &TicKV<FlashController, REGION_SIZE_BYTES>,
+    state: &mut State,
+    badge: usize,
+    req: Request,
+) -> Result<Response, Error> {
+    let State {
+        key_index,
+        journal,
+        value_buffer,
+        expired,
+        ..
+    } = state;
+    // Callers without a badge in CLIENTS get nothing, not even a recovery
+    access::client(badge).ok_or(Error::PermissionDenied)?;
+    // The caller's writes were meant for the transaction which timed out,
+    // none of them land on their own
+    let expired = &mut expired[badge - 1];
+    if *expired {
+        match req {
+            Request::AppendKey(..)
+            | Request::AppendBytes(..)
+            | Request::SetKey(..)
+            | Request::InvalidateKey(..)
+            | Request::Commit
+            | Request::Abort => return Err(Error::TransactionExpired),
+            _ => (),
+        }
+    }
+    // Nothing is read or written before a pending journal is applied,
+    // besides collecting garbage in case it's stuck on a full partition,
+    // and the stats which show it
+    let exempt = matches!(req, Request::GarbageCollect | Request::Stats);
+    if journal.is_pending() && !exempt {
+        recover_journal(tickv, journal, key_index, value_buffer)?;
+    }
+    let staging = journal.is_open_by(badge);
+    let resp = match req {
+        Request::AppendKey(key, value) | Request::AppendBytes(key, value) if staging => {
+            access::check_write(badge, &key)?;
+            let exists = match journal.staged_exists(&key) {
+                Some(exists) => exists,
+                None => exists(tickv, key_index, &key, value_buffer)?,
+            };
+            if exists {
+                return Err(ErrorCode::KeyAlreadyExists.into());
+            }
+            journal.check_room(key_index, &key)?;
+            access::check_quota(
+                tickv,
+                key_index,
+                Some(&*journal),
+                &key,
+                &value,
+                value_buffer,
+            )?;
+            journal.stage_set(&key, &value)?;
+            Response::Staged
+        }
+        Request::AppendKey(key, value) | Request::AppendBytes(key, value) => {
+            access::check_write(badge, &key)?;
+            key_index.check_room(&key)?;
+            access::check_quota(tickv, key_index, None, &key, &value, value_buffer)?;
+            let value_slot = key_index.value_slot(&key);
+            let sc = values::append(tickv, &key, value_slot, &value, value_buffer)?;
+            key_index.record(tickv, &key, Slot::Primary);
+            Response::KeyAppended(sc)
+        }
+        Request::SetKey(key, value) if staging => {
+            access::check_write(badge, &key)?;
+            journal.check_room(key_index, &key)?;
+            access::check_quota(
+                tickv,
+                key_index,
+                Some(&*journal),
+                &key,
+                &value,
+                value_buffer,
+            )?;
+            journal.stage_set(&key, &value)?;
+            Response::Staged
+        }
+        Request::SetKey(key, value) => {
+            access::check_write(badge, &key)?;
+            key_index.check_room(&key)?;
+            access::check_quota(tickv, key_index, None, &key, &value, value_buffer)?;
+            let value_slot = key_index.value_slot(&key);
+            let (sc, slot) = values::set(tickv, &key, value_slot, &value, value_buffer)?;
+            key_index.record(tickv, &key, slot);
+            Response::KeySet(sc)
+        }
+        Request::Get(key) => {
+            access::check_read(badge, &key)?;
+            let value = values::get(tickv, &key, key_index.value_slot(&key), value_buffer)?;
+            // Make sure it's UTF-8
+            if value.as_str().is_err() {
+                return Err(ErrorCode::CorruptData.into());
+            }
+            Response::Value(value)
+        }
+        Request::GetBytes(key) => {
+            access::check_read(badge, &key)?;
+            let value_slot = key_index.value_slot(&key);
+            Response::Bytes(values::get(tickv, &key, value_slot, value_buffer)?)
+        }
+        Request::InvalidateKey(key) if staging => {
+            access::check_write(badge, &key)?;
+            let exists = match journal.staged_exists(&key) {
+                Some(exists) => exists,
+                None => exists(tickv, key_index, &key, value_buffer)?,
+            };
+            if !exists {
+                return Err(ErrorCode::KeyNotFound.into());
+            }
+            journal.stage_invalidate(&key)?;
+            Response::Staged
+        }
+        Request::InvalidateKey(key) => {
+            access::check_write(badge, &key)?;
+            let sc = values::invalidate(tickv, &key, key_index.value_slot(&key))?;
+            key_index.forget(tickv, &key);
+            Response::KeyInvalidated(sc)
+        }
+        Request::GarbageCollect => {
+            access::check_any_write(badge)?;
+            Response::GarbageCollected(tickv.garbage_collect()?)
+        }
+        Request::ListKeys { cursor } => list_keys(tickv, key_index, badge, cursor, value_buffer)?,
+        Request::BeginTransaction => {
+            access::check_any_write(badge)?;
+            journal.begin(badge, tickv, key_index, value_buffer)?;
+            *expired = false;
+            Response::TransactionBegun
+        }
+        Request::Commit => {
+            access::check_any_write(badge)?;
+            Response::Committed(journal.commit(badge, tickv, key_index, value_buffer)?)
+        }
+        Request::Abort => {
+            access::check_any_write(badge)?;
+            Response::Aborted(journal.abort(badge)?)
+        }
+        Request::Stats => {
+            access::check_any_write(badge)?;
+            Response::Stats(stats(tickv, key_index, journal, value_buffer)?)
+        }
+    };
+    Ok(resp)
+}
+
+fn exists(
+    tickv: &TicKV<FlashController, REGION_SIZE_BYTES>,
+    key_index: &KeyIndex,
+    key: &Key,
+    buf: &mut ValueBuffer,
+) -> Result<bool, ErrorCode> {
+    match values::get(tickv, key, key_index.value_slot(key), buf) {
+        Ok(_) => Ok(true),
+        Err(ErrorCode::KeyNotFound) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// A page of the keys the caller with `badge` can read from `cursor`, with
+/// their value lengths, keys whose value can't be read are skipped
+fn list_keys(
+    tickv: &TicKV<FlashController, REGION_SIZE_BYTES>,
+    key_index: &KeyIndex,
+    badge: usize,
+    cursor: usize,
+    buf: &mut ValueBuffer,
+) -> Result<Response, ErrorCode> {
+    let mut keys = heapless::Vec::new();
+    let mut next_cursor = None;
+    let readable = key_index
+        .iter_from(cursor)
+        .filter(|(_, key)| access::access(badge, key).is_some());
+    for (slot, key) in readable {
+        if keys.is_full() {
+            next_cursor = Some(slot);
+            break;
+        }
+        match values::get(tickv, key, key_index.value_slot(key), buf) {
+            Ok(value) => {
+                let info = KeyInfo {
+                    key: key.clone(),
+                    value_len: value.len(),
+                };
+                // Can't fail, checked above
+                let _ = keys.push(info);
+            }
+            Err(e) => log::warn!("[persistent-storage] Failed to read key {} {:?}", key, e),
+        }
+    }
+    Ok(Response::Keys { keys, next_cursor })
+}
+
+/// Usage of the partition, anything written besides the live objects
+/// counts as reclaimable
+fn stats(
+    tickv: &TicKV<FlashController, REGION_SIZE_BYTES>,
+    key_index: &KeyIndex,
+    journal: &mut Journal,
+    buf: &mut ValueBuffer,
+) -> Result<Stats, ErrorCode> {
+    let usage = usage::live(tickv, key_index, journal, buf)?;
+    let flash = &tickv.controller;
+    Ok(Stats {
+        total_bytes: flash.partition_size_bytes(),
+        live_bytes: usage.live_bytes,
+        reclaimable_bytes: used_bytes(flash)?.saturating_sub(usage.live_bytes),
+        objects: usage.objects,
+        erases_since_boot: flash.erases_since_boot().iter().copied().collect(),
+    })
+}
+
+fn used_bytes(flash: &FlashController) -> Result<usize, ErrorCode> {
+    (0..flash.regions()).map(|r| flash.used_bytes(r)).sum()
+}
+
+/// Number of regions in the storage partition of a flash of
+/// `capacity_bytes`, see `PARTITION_FRACTION`
+fn storage_regions(capacity_bytes: usize) -> usize {
+    (capacity_bytes / PARTITION_FRACTION / REGION_SIZE_BYTES).clamp(1, MAX_REGIONS)
+}
+
+/// Warn once the erased space drops below `LOW_SPACE_BYTES`, and again
+/// the next time after it has been freed up
+fn check_free_space(tickv: &TicKV<FlashController, REGION_SIZE_BYTES>, state: &mut State) {
+    let flash = &tickv.controller;
+    let free = match used_bytes(flash) {
+        Ok(used) => flash.partition_size_bytes().saturating_sub(used),
+        Err(e) => {
+            log::warn!("[persistent-storage] Failed to read the used space {:?}", e);
+            return;
+        }
+    };
+    let low_space = free < LOW_SPACE_BYTES;
+    if low_space && !state.low_space {
+        log::warn!(
+            "[persistent-storage] Low on space, {} bytes free before garbage collection",
+            free
+        );
+    }
+    state.low_space = low_space;
+}
+
+fn halt() -> ! {
     unsafe {
         loop {
             selfe_sys::seL4_Yield();
         }
     }
 }
-
-fn get_hashed_key(unhashed_key: &[u8]) -> u64 {
-    let mut hash_function = SipHasher::new();
-    unhashed_key.hash(&mut hash_function);
-    hash_function.finish()
-}
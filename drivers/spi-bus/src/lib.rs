@@ -0,0 +1,237 @@
+#![no_std]
+
+use badged_ipc::{BootStatusSender, Caller, Client, IrqHandler, Server};
+use core::convert::TryInto;
+use core::mem;
+use ferros::cap::{role, CNodeRole};
+use ferros::userland::{Producer, RetypeForSetup};
+use ferros::vspace::{shared_status, MappedMemoryRegion};
+use imx6_hal::embedded_hal::blocking::spi::{self, Operation};
+use imx6_hal::pac::{
+    ecspi::ecspi1::ECSPI1,
+    gpio::GPIO3,
+    typenum::{op, U1, U13},
+};
+use imx6_hal::spi::Mode;
+use imx6_hal::spi_nor_flash;
+
+/// 8K transaction buffer shared between the broker and each client, room
+/// for a 4K transfer along with its command and the operation headers
+pub type TransactionBufferSizeBits = U13;
+pub type TransactionBufferSizeBytes = op! { U1 << TransactionBufferSizeBits };
+pub type TransactionBuffer = MappedMemoryRegion<TransactionBufferSizeBits, shared_status::Shared>;
+
+/// Maximum number of operations in a transaction
+pub const MAX_OPERATIONS: usize = 8;
+
+/// A device on the bus
+pub struct Device {
+    pub name: &'static str,
+    pub mode: Mode,
+    pub freq_hz: u32,
+    /// GPIO3 pin driving the active low chip select
+    pub cs_pin: u8,
+}
+
+/// Devices on ECSPI1, indexed by client badge - 1. The order must match
+/// the clients of the spi-bus service in system.toml
+pub const DEVICES: [Device; 1] = [Device {
+    name: "spi-nor-flash",
+    mode: spi_nor_flash::SPI_MODE,
+    freq_hz: spi_nor_flash::SPI_FREQ_HZ,
+    cs_pin: 19,
+}];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    InvalidRequest,
+    UnknownDevice,
+    TooManyOperations,
+    /// The operations don't fit in the transaction buffer
+    BufferTooSmall,
+    Spi,
+}
+
+/// Execute the operations in the client's transaction buffer, the data
+/// word is the number of operations
+pub const REQ_TRANSACTION: usize = 1;
+
+const RESP_OK: usize = 0;
+
+/// Operations are serialized as a kind and length header followed by
+/// the data, `Transfer` data is replaced with the received bytes
+const HEADER_SIZE: usize = 8;
+const OP_WRITE: u32 = 1;
+const OP_TRANSFER: u32 = 2;
+
+/// Response label of a transaction result
+pub fn result_to_label(result: Result<(), Error>) -> usize {
+    match result {
+        Ok(()) => RESP_OK,
+        Err(e) => e as usize + 1,
+    }
+}
+
+pub fn label_to_result(label: usize) -> Result<(), Error> {
+    match label {
+        RESP_OK => Ok(()),
+        2 => Err(Error::UnknownDevice),
+        3 => Err(Error::TooManyOperations),
+        4 => Err(Error::BufferTooSmall),
+        5 => Err(Error::Spi),
+        _ => Err(Error::InvalidRequest),
+    }
+}
+
+/// Serialize `ops` into a transaction buffer
+pub fn encode(buf: &mut [u8], ops: &[Operation<'_, u8>]) -> Result<(), Error> {
+    if ops.len() > MAX_OPERATIONS {
+        return Err(Error::TooManyOperations);
+    }
+    let mut offset = 0;
+    for op in ops.iter() {
+        let (kind, data): (u32, &[u8]) = match op {
+            Operation::Write(data) => (OP_WRITE, *data),
+            Operation::Transfer(data) => (OP_TRANSFER, &**data),
+        };
+        let end = offset + HEADER_SIZE + data.len();
+        if end > buf.len() {
+            return Err(Error::BufferTooSmall);
+        }
+        buf[offset..offset + 4].copy_from_slice(&kind.to_le_bytes());
+        buf[offset + 4..offset + 8].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        buf[offset + HEADER_SIZE..end].copy_from_slice(data);
+        offset = end;
+    }
+    Ok(())
+}
+
+/// Operations serialized in a transaction buffer, borrowing their data
+/// from it
+pub struct Operations<'a> {
+    buf: &'a mut [u8],
+    remaining: usize,
+}
+
+impl<'a> Operations<'a> {
+    pub fn new(buf: &'a mut [u8], count: usize) -> Self {
+        Operations {
+            buf,
+            remaining: count,
+        }
+    }
+}
+
+impl<'a> Iterator for Operations<'a> {
+    type Item = Result<Operation<'a, u8>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let buf = mem::take(&mut self.buf);
+        if buf.len() < HEADER_SIZE {
+            self.remaining = 0;
+            return Some(Err(Error::InvalidRequest));
+        }
+        let (header, rest) = buf.split_at_mut(HEADER_SIZE);
+        let kind = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let len = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+        if len > rest.len() {
+            self.remaining = 0;
+            return Some(Err(Error::InvalidRequest));
+        }
+        let (data, rest) = rest.split_at_mut(len);
+        self.buf = rest;
+
+        Some(match kind {
+            OP_WRITE => Ok(Operation::Write(data)),
+            OP_TRANSFER => Ok(Operation::Transfer(data)),
+            _ => {
+                self.remaining = 0;
+                Err(Error::InvalidRequest)
+            }
+        })
+    }
+}
+
+/// Client side of a device on the bus, the broker holds the device's chip
+/// select across each transaction
+pub struct SpiDevice {
+    client: Client<role::Local>,
+    buffer: TransactionBuffer,
+}
+
+impl SpiDevice {
+    pub fn new(client: Client<role::Local>, buffer: TransactionBuffer) -> Self {
+        SpiDevice { client, buffer }
+    }
+}
+
+impl spi::Transactional<u8> for SpiDevice {
+    type Error = Error;
+
+    fn exec<'a>(&mut self, operations: &mut [Operation<'a, u8>]) -> Result<(), Self::Error> {
+        let buf = self.buffer.as_mut_slice();
+        encode(buf, operations)?;
+
+        let resp = self.client.call(REQ_TRANSACTION, &[operations.len()]);
+        label_to_result(resp.label)?;
+
+        let results = Operations::new(buf, operations.len());
+        for (op, result) in operations.iter_mut().zip(results) {
+            if let (Operation::Transfer(dst), Ok(Operation::Transfer(src))) = (op, result) {
+                dst.copy_from_slice(src);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl spi::Transfer<u8> for SpiDevice {
+    type Error = Error;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+        spi::Transactional::exec(self, &mut [Operation::Transfer(&mut *words)])?;
+        Ok(words)
+    }
+}
+
+impl spi::Write<u8> for SpiDevice {
+    type Error = Error;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        spi::Transactional::exec(self, &mut [Operation::Write(words)])
+    }
+}
+
+#[repr(C)]
+pub struct ProcParams<Role: CNodeRole> {
+    pub spi: ECSPI1,
+
+    /// Transfers block on the RR and TC interrupts
+    pub spi_irq: IrqHandler<Role>,
+
+    /// Chip selects of the `DEVICES`
+    pub gpio3: GPIO3,
+
+    pub iomux_caller: Caller<iomux::Request, iomux::Response, Role>,
+
+    /// Transaction buffer of each of the `DEVICES`
+    pub flash_buffer: TransactionBuffer,
+
+    /// Transactions from clients, the badge identifies the device
+    pub server: Server<Role>,
+
+    /// Heartbeats to the watchdog service, sent on each of its ticks
+    pub heartbeat_producer: Producer<Role, watchdog::Message>,
+
+    /// Boot status reporting to the root task
+    pub boot_status: BootStatusSender<Role>,
+}
+
+impl RetypeForSetup for ProcParams<role::Local> {
+    type Output = ProcParams<role::Child>;
+}
@@ -0,0 +1,135 @@
+#![no_std]
+#![no_main]
+
+use selfe_runtime as _;
+
+use badged_ipc::{IrqHandler, BROADCAST_BADGE};
+use debug_logger::DebugLogger;
+use ferros::cap::role;
+use imx6_hal::embedded_hal::blocking::spi::Transactional;
+use imx6_hal::gpio::Bank;
+use imx6_hal::pac::ecspi::ecspi1::ECSPI1;
+use imx6_hal::spi::{ChipSelect, Spi};
+use imx6_hal::wait::Wait;
+use spi_bus::{
+    result_to_label, Error, Operations, ProcParams, DEVICES, MAX_OPERATIONS, REQ_TRANSACTION,
+};
+
+static LOGGER: DebugLogger = DebugLogger;
+
+#[allow(improper_ctypes_definitions)]
+#[no_mangle]
+pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
+    let restarted = unsafe { process_restart::reset_statics() }.unwrap();
+    log::set_logger(&LOGGER)
+        .map(|()| log::set_max_level(DebugLogger::max_log_level_from_env()))
+        .unwrap();
+
+    log::debug!("[spi-bus] Process started restarted={}", restarted);
+
+    // Configure ECSPI1 IO
+    match params
+        .iomux_caller
+        .blocking_call(&iomux::Request::ConfigureEcSpi1)
+    {
+        Ok(resp) => log::debug!("[spi-bus] Configured ECSPI1 IO resp={:?}", resp),
+        Err(e) => log::warn!("[spi-bus] Failed to configure ECSPI1 IO {:?}", e),
+    }
+
+    // Deassert every chip select before touching the bus
+    let mut cs = Bank::from(params.gpio3);
+    for dev in DEVICES.iter() {
+        cs.set_level(dev.cs_pin, true);
+        cs.set_direction(dev.cs_pin, true);
+    }
+
+    // Chip selects are GPIOs, the controller's SS0 isn't routed to a pad
+    let dev = &DEVICES[0];
+    let spi = Spi::new(params.spi, dev.mode, dev.freq_hz, ChipSelect::Cs0)
+        .with_wait(SpiIrq(params.spi_irq));
+
+    let mut bus = Bus {
+        spi,
+        cs,
+        current_device: 0,
+    };
+
+    let mut buffers = [params.flash_buffer];
+
+    let heartbeat = watchdog::Client::register("spi-bus", params.heartbeat_producer);
+
+    params.boot_status.ready();
+
+    let server = params.server;
+    let mut msg = server.recv();
+    loop {
+        if msg.is_notification() {
+            if msg.badge & BROADCAST_BADGE != 0 {
+                heartbeat.heartbeat();
+            }
+            msg = server.recv();
+            continue;
+        }
+        let result = match (msg.label, msg.data()) {
+            (REQ_TRANSACTION, [count, ..]) => {
+                let device = msg.badge.wrapping_sub(1);
+                match buffers.get_mut(device) {
+                    Some(buf) => bus.transaction(device, buf.as_mut_slice(), *count),
+                    None => Err(Error::UnknownDevice),
+                }
+            }
+            _ => Err(Error::InvalidRequest),
+        };
+        if let Err(e) = result {
+            log::debug!("[spi-bus] Client {} transaction failed {:?}", msg.badge, e);
+        }
+        msg = server.reply_recv(result_to_label(result), &[]);
+    }
+}
+
+/// Blocks on the ECSPI1 interrupt, acking it first re-enables it after
+/// the previous wait
+struct SpiIrq(IrqHandler<role::Local>);
+
+impl Wait for SpiIrq {
+    const INTERRUPTS: bool = true;
+
+    fn wait(&mut self) {
+        self.0.ack();
+        self.0.wait();
+    }
+}
+
+struct Bus {
+    spi: Spi<ECSPI1, SpiIrq>,
+    cs: Bank,
+    /// Index of the device the controller is configured for
+    current_device: usize,
+}
+
+impl Bus {
+    /// Runs the operations in `buf` with the device's chip select held low,
+    /// the received data replaces the `Transfer` data
+    fn transaction(&mut self, device: usize, buf: &mut [u8], count: usize) -> Result<(), Error> {
+        let dev = DEVICES.get(device).ok_or(Error::UnknownDevice)?;
+        if count > MAX_OPERATIONS {
+            return Err(Error::TooManyOperations);
+        }
+
+        // Reject malformed transactions before selecting the device
+        Operations::new(buf, count).try_for_each(|op| op.map(|_| ()))?;
+
+        if self.current_device != device {
+            log::trace!("[spi-bus] Switching to {}", dev.name);
+            self.spi.configure(dev.mode, dev.freq_hz, ChipSelect::Cs0);
+            self.current_device = device;
+        }
+
+        let spi = &mut self.spi;
+        self.cs.set_level(dev.cs_pin, false);
+        let result = Operations::new(buf, count)
+            .try_for_each(|op| spi.exec(&mut [op?]).map_err(|_| Error::Spi));
+        self.cs.set_level(dev.cs_pin, true);
+        result
+    }
+}
@@ -1,5 +1,6 @@
 #![no_std]
 
+use badged_ipc::{BootStatusSender, Caller};
 use ferros::cap::{role, CNodeRole};
 use ferros::userland::{Consumer1, Producer, RetypeForSetup};
 use ferros::vspace::{shared_status, MappedMemoryRegion};
@@ -39,11 +40,24 @@ pub struct ProcParams<Role: CNodeRole> {
     /// Memory for the socket buffers, split in half for rx and tx by the driver
     pub socket_buffer_mem: MappedMemoryRegion<RxTxSocketBufferSizeBits, shared_status::Exclusive>,
 
-    /// Hardware MAC address
+    /// IPC to the storage driver, used to read the runtime network configuration
+    pub storage_caller: Caller<
+        persistent_storage::Request,
+        Result<persistent_storage::Response, persistent_storage::Error>,
+        Role,
+    >,
+
+    /// Default hardware MAC address, overridden by the `net.mac` storage key
     pub mac_addr: EthernetAddress,
 
-    /// IPv4 address
+    /// Default IPv4 address, overridden by the `net.ip` storage key
     pub ip_addr: Ipv4Address,
+
+    /// Heartbeats to the watchdog service, sent from the GPT service interrupt
+    pub heartbeat_producer: Producer<Role, watchdog::Message>,
+
+    /// Boot status reporting to the root task
+    pub boot_status: BootStatusSender<Role>,
 }
 
 impl RetypeForSetup for ProcParams<role::Local> {
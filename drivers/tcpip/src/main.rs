@@ -4,18 +4,21 @@
 use selfe_runtime as _;
 
 use crate::ipc_phy_dev::IpcPhyDevice;
+use badged_ipc::Caller;
 use debug_logger::DebugLogger;
 use ferros::cap::role;
 use imx6_hal::{
     embedded_hal::timer::CountDown,
     timer::{Event as TimerEvent, Hertz, Timer},
 };
-use net_types::IpcUdpTransmitBuffer;
+use net_types::{IpcUdpTransmitBuffer, NetConfig};
+use persistent_storage::{Error, ErrorCode, Key, Request, Response};
 use smoltcp::iface::{EthernetInterface, EthernetInterfaceBuilder, NeighborCache, Routes};
 use smoltcp::socket::{SocketHandle, SocketSet, UdpPacketMetadata, UdpSocket, UdpSocketBuffer};
 use smoltcp::time::Instant;
 use smoltcp::wire::{IpCidr, IpEndpoint};
 use tcpip::ProcParams;
+use watchdog::HEARTBEAT_PERIOD_MS;
 
 mod ipc_phy_dev;
 
@@ -33,26 +36,38 @@ static LOGGER: DebugLogger = DebugLogger;
 #[allow(improper_ctypes_definitions)]
 #[no_mangle]
 pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
+    let restarted = unsafe { process_restart::reset_statics() }.unwrap();
     log::set_logger(&LOGGER)
         .map(|()| log::set_max_level(DebugLogger::max_log_level_from_env()))
         .unwrap();
 
-    log::debug!("[tcpip-driver] Process started");
+    log::debug!("[tcpip-driver] Process started restarted={}", restarted);
 
     let ipc_phy = IpcPhyDevice {
         consumer: params.frame_consumer,
         producer: params.frame_producer,
     };
 
+    let mut net_config = NetConfig::new(params.mac_addr, params.ip_addr);
+    load_net_config(&params.storage_caller, &mut net_config);
+
     // Build the IP stack
-    let ip_addr = IpCidr::new(smoltcp::wire::Ipv4Address(params.ip_addr.into()).into(), 24);
+    let ip_addr = IpCidr::new(
+        smoltcp::wire::Ipv4Address(net_config.ip_addr.into()).into(),
+        net_config.prefix_len,
+    );
     let mut ip_addrs = [ip_addr];
     let mut neighbor_storage = [None; MAX_ARP_ENTRIES];
     let neighbor_cache = NeighborCache::new(&mut neighbor_storage[..]);
 
-    let ethernet_addr = smoltcp::wire::EthernetAddress(params.mac_addr.into());
+    let ethernet_addr = smoltcp::wire::EthernetAddress(net_config.mac_addr.into());
     let mut routes_storage = [None; 4];
-    let routes = Routes::new(&mut routes_storage[..]);
+    let mut routes = Routes::new(&mut routes_storage[..]);
+    if let Some(gw) = net_config.gateway {
+        routes
+            .add_default_ipv4_route(smoltcp::wire::Ipv4Address(gw.into()))
+            .unwrap();
+    }
 
     let iface = EthernetInterfaceBuilder::new(ipc_phy)
         .ethernet_addr(ethernet_addr)
@@ -90,11 +105,11 @@ pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
     timer.start(TIMER_RATE);
     timer.listen(TimerEvent::TimeOut);
 
-    log::debug!(
-        "[tcpip-driver] TCP/IP stack is up IP={} MAC={}",
-        params.ip_addr,
-        params.mac_addr
-    );
+    log::debug!("[tcpip-driver] TCP/IP stack is up {}", net_config);
+
+    let heartbeat = watchdog::Client::register("tcpip-driver", params.heartbeat_producer);
+
+    params.boot_status.ready();
 
     let initial_state = Driver {
         iface,
@@ -102,6 +117,7 @@ pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
         udp_handle,
         timer,
         timer_ms: 0,
+        heartbeat,
     };
 
     params.event_consumer.consume(
@@ -130,18 +146,52 @@ pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
     );
 }
 
+/// Override the build-time defaults with any network configuration
+/// keys present in storage
+fn load_net_config(
+    storage_caller: &Caller<Request, Result<Response, Error>, role::Local>,
+    net_config: &mut NetConfig,
+) {
+    for key in NetConfig::KEYS.iter() {
+        let resp = storage_caller.blocking_call(&Request::Get(Key::from(*key)));
+        match resp {
+            Ok(Ok(Response::Value(value))) => {
+                // Get only returns UTF-8 values
+                let value = value.as_str().unwrap_or_default();
+                match net_config.set(key, value) {
+                    Ok(()) => log::debug!("[tcpip-driver] Using {}={}", key, value),
+                    Err(e) => log::warn!(
+                        "[tcpip-driver] Ignoring invalid {}='{}', {:?}",
+                        key,
+                        value,
+                        e
+                    ),
+                }
+            }
+            Ok(Ok(r)) => log::warn!("[tcpip-driver] Unexpected response for {}, {}", key, r),
+            Ok(Err(Error::Storage(ErrorCode::KeyNotFound))) => (),
+            Ok(Err(e)) => log::warn!("[tcpip-driver] Failed to read {}, {:?}", key, e),
+            Err(e) => log::warn!("[tcpip-driver] Storage call for {} failed, {:?}", key, e),
+        }
+    }
+}
+
 struct Driver<'a> {
     iface: EthernetInterface<'a, IpcPhyDevice>,
     sockets: SocketSet<'a>,
     udp_handle: SocketHandle,
     timer: Timer,
     timer_ms: i64,
+    heartbeat: watchdog::Client,
 }
 
 impl<'a> Driver<'a> {
     pub fn ack_timer_irq(&mut self) {
         self.timer.wait().ok();
         self.timer_ms = self.timer_ms.wrapping_add(TIMER_MS_PER_TICK.into());
+        if self.timer_ms % i64::from(HEARTBEAT_PERIOD_MS) == 0 {
+            self.heartbeat.heartbeat();
+        }
     }
 
     pub fn get_time(&self) -> Instant {
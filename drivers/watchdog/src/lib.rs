@@ -0,0 +1,123 @@
+#![no_std]
+
+use badged_ipc::{BootStatusSender, Broadcast};
+use core::fmt;
+use ferros::cap::{role, CNodeRole};
+use ferros::userland::{Consumer1, Producer, RetypeForSetup};
+use imx6_hal::pac::wdog::wdog1::{self, WDOG1};
+
+/// Maximum number of registered clients
+pub const MAX_CLIENTS: usize = 8;
+
+/// Client names longer than this are truncated
+pub const MAX_CLIENT_NAME_SIZE: usize = 16;
+
+/// Clients with a timer of their own are expected to send a heartbeat at
+/// least this often, the others answer each tick
+pub const HEARTBEAT_PERIOD_MS: u32 = 1_000;
+
+/// Every registered client must send a heartbeat within this window
+/// for the hardware watchdog to be serviced
+pub const CHECK_PERIOD_MS: u32 = 5_000;
+
+/// Hardware watchdog timeout, the pre-timeout interrupt fires
+/// `CHECK_PERIOD_MS` after the last service
+pub const TIMEOUT_MS: u32 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientName {
+    len: usize,
+    bytes: [u8; MAX_CLIENT_NAME_SIZE],
+}
+
+impl ClientName {
+    pub fn new(name: &str) -> Self {
+        // Truncate on a char boundary so the name stays valid UTF-8
+        let mut len = name.len().min(MAX_CLIENT_NAME_SIZE);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0; MAX_CLIENT_NAME_SIZE];
+        bytes[..len].copy_from_slice(&name.as_bytes()[..len]);
+        ClientName { len, bytes }
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("?")
+    }
+}
+
+impl fmt::Display for ClientName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    /// Start tracking the client, registering again is harmless
+    Register(ClientName),
+    Heartbeat(ClientName),
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Message::Register(n) => write!(f, "Register({})", n),
+            Message::Heartbeat(n) => write!(f, "Heartbeat({})", n),
+        }
+    }
+}
+
+/// Client side of the heartbeat queue
+pub struct Client {
+    name: ClientName,
+    producer: Producer<role::Local, Message>,
+}
+
+impl Client {
+    /// Registers with the watchdog service, heartbeats are expected
+    /// from here on
+    pub fn register(name: &str, producer: Producer<role::Local, Message>) -> Self {
+        let client = Client {
+            name: ClientName::new(name),
+            producer,
+        };
+        if client
+            .producer
+            .send(Message::Register(client.name))
+            .is_err()
+        {
+            log::warn!("[{}] Failed to register with the watchdog", client.name);
+        }
+        client
+    }
+
+    pub fn heartbeat(&self) {
+        if self.producer.send(Message::Heartbeat(self.name)).is_err() {
+            log::warn!("[{}] Failed to send a watchdog heartbeat", self.name);
+        }
+    }
+}
+
+#[repr(C)]
+pub struct ProcParams<Role: CNodeRole> {
+    /// Hardware watchdog, can't be stopped once started
+    pub wdog: WDOG1,
+
+    /// The consumer handles:
+    /// - WDOG1 pre-timeout IRQ notification events (via Waker)
+    /// - Client registrations and heartbeats
+    pub consumer: Consumer1<Role, Message, wdog1::Irq>,
+
+    /// Signalled after each check, clients without a timer of their own
+    /// answer with a heartbeat
+    pub ticks: Broadcast<Role>,
+
+    /// Boot status reporting to the root task
+    pub boot_status: BootStatusSender<Role>,
+}
+
+impl RetypeForSetup for ProcParams<role::Local> {
+    type Output = ProcParams<role::Child>;
+}
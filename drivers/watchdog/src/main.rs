@@ -0,0 +1,145 @@
+#![no_std]
+#![no_main]
+
+use selfe_runtime as _;
+
+use badged_ipc::Broadcast;
+use debug_logger::DebugLogger;
+use ferros::cap::role;
+use imx6_hal::embedded_hal::watchdog::{Watchdog as _, WatchdogEnable};
+use imx6_hal::pac::wdog::wdog1::WDOG1;
+use imx6_hal::watchdog::Watchdog;
+use watchdog::{ClientName, Message, ProcParams, CHECK_PERIOD_MS, MAX_CLIENTS, TIMEOUT_MS};
+
+static LOGGER: DebugLogger = DebugLogger;
+
+#[allow(improper_ctypes_definitions)]
+#[no_mangle]
+pub extern "C" fn _start(params: ProcParams<role::Local>) -> ! {
+    let restarted = unsafe { process_restart::reset_statics() }.unwrap();
+    log::set_logger(&LOGGER)
+        .map(|()| log::set_max_level(DebugLogger::max_log_level_from_env()))
+        .unwrap();
+
+    log::debug!("[watchdog] Process started restarted={}", restarted);
+
+    let mut wdog = Watchdog::new(params.wdog);
+    log::debug!("[watchdog] Last reset cause {:?}", wdog.reset_cause());
+
+    wdog.disable_power_down_counter();
+    wdog.listen_pre_timeout(TIMEOUT_MS - CHECK_PERIOD_MS);
+    wdog.start(TIMEOUT_MS);
+
+    log::debug!(
+        "[watchdog] Started, {} ms timeout, checking clients every {} ms",
+        TIMEOUT_MS,
+        CHECK_PERIOD_MS
+    );
+
+    params.boot_status.ready();
+
+    let initial_state = Service {
+        wdog,
+        ticks: params.ticks,
+        clients: [None; MAX_CLIENTS],
+        expired: false,
+    };
+
+    params.consumer.consume(
+        initial_state,
+        |mut state| {
+            // Non-queue wakeup event, the pre-timeout IRQ
+            state.check_clients();
+            state
+        },
+        |msg, mut state| {
+            log::trace!("[watchdog] Processing {}", msg);
+            match msg {
+                Message::Register(name) => state.register(name),
+                Message::Heartbeat(name) => state.heartbeat(name),
+            }
+            state
+        },
+    );
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Client {
+    name: ClientName,
+    /// Heartbeat received since the last check
+    alive: bool,
+}
+
+struct Service {
+    wdog: Watchdog<WDOG1>,
+    ticks: Broadcast<role::Local>,
+    clients: [Option<Client>; MAX_CLIENTS],
+    /// A client went silent, the watchdog is no longer serviced
+    expired: bool,
+}
+
+impl Service {
+    fn client_mut(&mut self, name: &ClientName) -> Option<&mut Client> {
+        self.clients.iter_mut().flatten().find(|c| &c.name == name)
+    }
+
+    fn register(&mut self, name: ClientName) {
+        if let Some(client) = self.client_mut(&name) {
+            // Re-registering after the supervisor restarted the client
+            client.alive = true;
+            return;
+        }
+        match self.clients.iter_mut().find(|c| c.is_none()) {
+            Some(slot) => {
+                log::debug!("[watchdog] Registered client {}", name);
+                *slot = Some(Client { name, alive: true });
+            }
+            None => log::warn!(
+                "[watchdog] Rejected client {}, all {} slots are taken",
+                name,
+                MAX_CLIENTS
+            ),
+        }
+    }
+
+    fn heartbeat(&mut self, name: ClientName) {
+        match self.client_mut(&name) {
+            Some(client) => client.alive = true,
+            None => log::warn!("[watchdog] Heartbeat from unregistered client {}", name),
+        }
+    }
+
+    /// Services the watchdog only if every registered client sent a
+    /// heartbeat since the last check, otherwise lets it time out
+    fn check_clients(&mut self) {
+        self.wdog.clear_interrupt();
+
+        if self.expired {
+            return;
+        }
+
+        let mut all_alive = true;
+        for client in self.clients.iter().flatten().filter(|c| !c.alive) {
+            log::error!(
+                "[watchdog] Client {} missed its heartbeat deadline",
+                client.name
+            );
+            all_alive = false;
+        }
+
+        if all_alive {
+            self.clients
+                .iter_mut()
+                .flatten()
+                .for_each(|c| c.alive = false);
+            self.wdog.feed();
+            self.ticks.signal();
+        } else {
+            log::error!(
+                "[watchdog] No longer servicing the watchdog, resetting in {} ms",
+                TIMEOUT_MS - CHECK_PERIOD_MS
+            );
+            self.expired = true;
+        }
+    }
+}
@@ -1,12 +1,10 @@
-//! ECSPI1
+//! ECSPI
 //! See [IMX6DQRM](http://cache.freescale.com/files/32bit/doc/ref_manual/IMX6DQRM.pdf) chapter 21.
 
 use core::mem;
 use core::ops::{Deref, DerefMut};
 use static_assertions::const_assert_eq;
-use typenum::{Unsigned, U63};
-
-pub type Irq = U63;
+use typenum::{Unsigned, U63, U64, U65, U66, U67};
 
 register! {
     Rx,
@@ -213,38 +211,54 @@ pub struct RegisterBlock {
     pub period: Period::Register, // 0x1C
 }
 
-pub struct ECSPI1 {
-    vaddr: u32,
-}
+macro_rules! ecspi {
+    ($ECSPIx:ident, $ecspix:ident, $PADDR:literal, $Irq:ty) => {
+        pub mod $ecspix {
+            use super::*;
 
-impl ECSPI1 {
-    pub const PADDR: u32 = 0x0200_8000;
-    pub const SIZE: usize = crate::PageBytes::USIZE;
+            pub type Irq = $Irq;
 
-    /// # Safety
-    /// out of thin air
-    pub unsafe fn from_vaddr(vaddr: u32) -> Self {
-        Self { vaddr }
-    }
+            pub struct $ECSPIx {
+                vaddr: u32,
+            }
 
-    fn as_ptr(&self) -> *const RegisterBlock {
-        self.vaddr as *const _
-    }
+            impl $ECSPIx {
+                pub const PADDR: u32 = $PADDR;
+                pub const SIZE: usize = crate::PageBytes::USIZE;
 
-    fn as_mut_ptr(&mut self) -> *mut RegisterBlock {
-        self.vaddr as *mut _
-    }
-}
+                /// # Safety
+                /// out of thin air
+                pub unsafe fn from_vaddr(vaddr: u32) -> Self {
+                    Self { vaddr }
+                }
 
-impl Deref for ECSPI1 {
-    type Target = RegisterBlock;
-    fn deref(&self) -> &RegisterBlock {
-        unsafe { &*self.as_ptr() }
-    }
-}
+                fn as_ptr(&self) -> *const RegisterBlock {
+                    self.vaddr as *const _
+                }
 
-impl DerefMut for ECSPI1 {
-    fn deref_mut(&mut self) -> &mut RegisterBlock {
-        unsafe { &mut *self.as_mut_ptr() }
-    }
+                fn as_mut_ptr(&mut self) -> *mut RegisterBlock {
+                    self.vaddr as *mut _
+                }
+            }
+
+            impl Deref for $ECSPIx {
+                type Target = RegisterBlock;
+                fn deref(&self) -> &RegisterBlock {
+                    unsafe { &*self.as_ptr() }
+                }
+            }
+
+            impl DerefMut for $ECSPIx {
+                fn deref_mut(&mut self) -> &mut RegisterBlock {
+                    unsafe { &mut *self.as_mut_ptr() }
+                }
+            }
+        }
+    };
 }
+
+ecspi!(ECSPI1, ecspi1, 0x0200_8000, U63);
+ecspi!(ECSPI2, ecspi2, 0x0200_C000, U64);
+ecspi!(ECSPI3, ecspi3, 0x0201_0000, U65);
+ecspi!(ECSPI4, ecspi4, 0x0201_4000, U66);
+ecspi!(ECSPI5, ecspi5, 0x0201_8000, U67);
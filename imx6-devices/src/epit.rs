@@ -0,0 +1,128 @@
+//! EPIT
+//! See [IMX6DQRM](http://cache.freescale.com/files/32bit/doc/ref_manual/IMX6DQRM.pdf) chapter 24.
+
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use static_assertions::const_assert_eq;
+use typenum::{Unsigned, U88, U89};
+
+register! {
+    Control,
+    u32,
+    RW,
+    Fields [
+        Enable              WIDTH(U1) OFFSET(U0),
+        EnableMode          WIDTH(U1) OFFSET(U1),
+        OutputCompareIntEn  WIDTH(U1) OFFSET(U2),
+        Reload              WIDTH(U1) OFFSET(U3),
+        Prescaler           WIDTH(U12) OFFSET(U4),
+        SwReset             WIDTH(U1) OFFSET(U16),
+        LoadOverwrite       WIDTH(U1) OFFSET(U17),
+        DebugMode           WIDTH(U1) OFFSET(U18),
+        WaitMode            WIDTH(U1) OFFSET(U19),
+        StopMode            WIDTH(U1) OFFSET(U21),
+        OutputMode          WIDTH(U2) OFFSET(U22),
+        ClockSource         WIDTH(U2) OFFSET(U24) [
+            Off = U0,
+            PeripheralClock = U1,
+            HighFreqRefClock = U2,
+            LowFreqRefClock = U3
+        ]
+    ]
+}
+
+register! {
+    Status,
+    u32,
+    RW,
+    Fields [
+        OutputCompare       WIDTH(U1) OFFSET(U0),
+    ]
+}
+
+register! {
+    Load,
+    u32,
+    RW,
+    Fields [
+        Value               WIDTH(U32) OFFSET(U0),
+    ]
+}
+
+register! {
+    Compare,
+    u32,
+    RW,
+    Fields [
+        Value               WIDTH(U32) OFFSET(U0),
+    ]
+}
+
+register! {
+    Counter,
+    u32,
+    RO,
+    Fields [
+        Count               WIDTH(U32) OFFSET(U0),
+    ]
+}
+
+const_assert_eq!(mem::size_of::<RegisterBlock>(), 0x14);
+
+#[repr(C)]
+pub struct RegisterBlock {
+    pub cr: Control::Register,   // 0x00
+    pub sr: Status::Register,    // 0x04
+    pub lr: Load::Register,      // 0x08
+    pub cmpr: Compare::Register, // 0x0C
+    pub cnr: Counter::Register,  // 0x10
+}
+
+macro_rules! epit {
+    ($EPITx:ident, $epitx:ident, $PADDR:literal, $Irq:ty) => {
+        pub mod $epitx {
+            use super::*;
+
+            pub type Irq = $Irq;
+
+            pub struct $EPITx {
+                vaddr: u32,
+            }
+
+            impl $EPITx {
+                pub const PADDR: u32 = $PADDR;
+                pub const SIZE: usize = crate::PageBytes::USIZE;
+
+                /// # Safety
+                /// out of thin air
+                pub unsafe fn from_vaddr(vaddr: u32) -> Self {
+                    Self { vaddr }
+                }
+
+                fn as_ptr(&self) -> *const RegisterBlock {
+                    self.vaddr as *const _
+                }
+
+                fn as_mut_ptr(&mut self) -> *mut RegisterBlock {
+                    self.vaddr as *mut _
+                }
+            }
+
+            impl Deref for $EPITx {
+                type Target = RegisterBlock;
+                fn deref(&self) -> &RegisterBlock {
+                    unsafe { &*self.as_ptr() }
+                }
+            }
+
+            impl DerefMut for $EPITx {
+                fn deref_mut(&mut self) -> &mut RegisterBlock {
+                    unsafe { &mut *self.as_mut_ptr() }
+                }
+            }
+        }
+    };
+}
+
+epit!(EPIT1, epit1, 0x020D_0000, U88);
+epit!(EPIT2, epit2, 0x020D_4000, U89);
@@ -4,7 +4,9 @@
 use core::mem;
 use core::ops::{Deref, DerefMut};
 use static_assertions::const_assert_eq;
-use typenum::Unsigned;
+use typenum::{
+    Unsigned, U100, U101, U102, U103, U104, U105, U106, U107, U108, U109, U110, U111, U98, U99,
+};
 
 register! {
     Data,
@@ -95,7 +97,17 @@ pub struct RegisterBlock {
 pub const NUM_BLOCKS: usize = 7;
 
 macro_rules! gpio_pins {
-    ($GPIOx:ident, $PADDR:literal) => {
+    ($GPIOx:ident, $gpiox:ident, $PADDR:literal, $IrqLow:ty, $IrqHigh:ty) => {
+        pub mod $gpiox {
+            use super::*;
+
+            /// Combined interrupt for pins 0 through 15
+            pub type IrqLow = $IrqLow;
+
+            /// Combined interrupt for pins 16 through 31
+            pub type IrqHigh = $IrqHigh;
+        }
+
         pub struct $GPIOx {
             vaddr: u32,
         }
@@ -134,10 +146,10 @@ macro_rules! gpio_pins {
     };
 }
 
-gpio_pins!(GPIO1, 0x0209_C000);
-gpio_pins!(GPIO2, 0x020A_0000);
-gpio_pins!(GPIO3, 0x020A_4000);
-gpio_pins!(GPIO4, 0x020A_8000);
-gpio_pins!(GPIO5, 0x020A_C000);
-gpio_pins!(GPIO6, 0x020B_0000);
-gpio_pins!(GPIO7, 0x020B_4000);
+gpio_pins!(GPIO1, gpio1, 0x0209_C000, U98, U99);
+gpio_pins!(GPIO2, gpio2, 0x020A_0000, U100, U101);
+gpio_pins!(GPIO3, gpio3, 0x020A_4000, U102, U103);
+gpio_pins!(GPIO4, gpio4, 0x020A_8000, U104, U105);
+gpio_pins!(GPIO5, gpio5, 0x020A_C000, U106, U107);
+gpio_pins!(GPIO6, gpio6, 0x020B_0000, U108, U109);
+gpio_pins!(GPIO7, gpio7, 0x020B_4000, U110, U111);
@@ -10,8 +10,9 @@ use typenum::{op, U1, U12};
 /// 4KB pages
 pub type PageBytes = op!(U1 << U12);
 
-pub mod ecspi1;
+pub mod ecspi;
 pub mod enet;
+pub mod epit;
 pub mod gpio;
 pub mod gpt;
 pub mod iomuxc;
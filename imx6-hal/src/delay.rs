@@ -0,0 +1,133 @@
+//! Blocking delays on an EPIT
+//!
+//! The counter is loaded with the delay and counts down to the compare
+//! value, waiting spins by default, `with_wait` blocks on the output compare
+//! interrupt instead. A timeout can also be started without waiting on it,
+//! see `start_ms` and `is_expired`.
+
+use crate::asm;
+use crate::embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use crate::pac::epit::*;
+use crate::wait::{Spin, Wait};
+use core::ops::DerefMut;
+use num::integer::Integer;
+
+/// Using the 32.768 kHz low frequency reference clock
+const CLOCK_FREQ: u32 = 32_768;
+
+pub struct Delay<EPIT, W = Spin> {
+    epit: EPIT,
+    wait: W,
+}
+
+impl<EPIT> Delay<EPIT, Spin>
+where
+    EPIT: DerefMut<Target = RegisterBlock>,
+{
+    pub fn new(epit: EPIT) -> Self {
+        let mut d = Delay { epit, wait: Spin };
+        d.reset();
+        d
+    }
+}
+
+impl<EPIT, W> Delay<EPIT, W>
+where
+    EPIT: DerefMut<Target = RegisterBlock>,
+    W: Wait,
+{
+    /// Block on the timer's interrupt through `wait` instead of spinning
+    pub fn with_wait<V: Wait>(self, wait: V) -> Delay<EPIT, V> {
+        Delay {
+            epit: self.epit,
+            wait,
+        }
+    }
+
+    pub fn free(self) -> EPIT {
+        self.epit
+    }
+
+    fn reset(&mut self) {
+        self.epit.cr.modify(Control::Enable::Clear);
+        self.epit.cr.modify(Control::SwReset::Set);
+        while self.epit.cr.is_set(Control::SwReset::Set) {
+            asm::nop();
+        }
+        self.epit.cr.modify(
+            Control::EnableMode::Set
+                + Control::Reload::Set
+                + Control::LoadOverwrite::Set
+                + Control::WaitMode::Set
+                + Control::ClockSource::LowFreqRefClock,
+        );
+        unsafe { self.epit.cmpr.write(0) };
+        self.epit.sr.modify(Status::OutputCompare::Set);
+    }
+
+    /// Start a timeout of `ms` without waiting for it, poll it with
+    /// `is_expired`
+    pub fn start_ms(&mut self, ms: u32) {
+        self.epit.cr.modify(Control::Enable::Clear);
+        self.start_ticks(ms_to_ticks(ms).max(1));
+    }
+
+    /// The timeout started by `start_ms` has elapsed
+    pub fn is_expired(&self) -> bool {
+        self.epit.sr.is_set(Status::OutputCompare::Set)
+    }
+
+    fn start_ticks(&mut self, ticks: u32) {
+        // Loading overwrites the counter, it then counts down to the compare
+        // value of 0
+        unsafe { self.epit.lr.write(ticks) };
+        self.epit.sr.modify(Status::OutputCompare::Set);
+        self.epit.cr.modify(Control::Enable::Set);
+    }
+
+    fn delay_ticks(&mut self, ticks: u32) {
+        if ticks == 0 {
+            return;
+        }
+        self.start_ticks(ticks);
+
+        while !self.epit.sr.is_set(Status::OutputCompare::Set) {
+            if W::INTERRUPTS {
+                self.epit.cr.modify(Control::OutputCompareIntEn::Set);
+                self.wait.wait();
+                self.epit.cr.modify(Control::OutputCompareIntEn::Clear);
+            } else {
+                self.wait.wait();
+            }
+        }
+
+        self.epit.cr.modify(Control::Enable::Clear);
+        self.epit.sr.modify(Status::OutputCompare::Set);
+    }
+}
+
+impl<EPIT, W> DelayUs<u32> for Delay<EPIT, W>
+where
+    EPIT: DerefMut<Target = RegisterBlock>,
+    W: Wait,
+{
+    /// Rounded up to the next tick, about 30 us
+    fn delay_us(&mut self, us: u32) {
+        let ticks = (u64::from(us) * u64::from(CLOCK_FREQ)).div_ceil(&1_000_000);
+        self.delay_ticks(ticks as u32);
+    }
+}
+
+impl<EPIT, W> DelayMs<u32> for Delay<EPIT, W>
+where
+    EPIT: DerefMut<Target = RegisterBlock>,
+    W: Wait,
+{
+    fn delay_ms(&mut self, ms: u32) {
+        self.delay_ticks(ms_to_ticks(ms));
+    }
+}
+
+fn ms_to_ticks(ms: u32) -> u32 {
+    (u64::from(ms) * u64::from(CLOCK_FREQ)).div_ceil(&1_000) as u32
+}
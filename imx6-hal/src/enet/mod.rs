@@ -29,6 +29,13 @@ pub type MinDescriptors = U2;
 pub const ENET_FREQ_HZ: u32 = 125_000_000;
 pub const MDC_FREQ_HZ: u32 = 20_000_000;
 
+/// The adjustable timer counts nanoseconds of the ENET clock
+const TIMER_INC_NS: u32 = 1_000_000_000 / ENET_FREQ_HZ;
+
+/// ATCR bits
+const TIMER_ENABLE: u32 = 1 << 0;
+const TIMER_PERIOD_RESET: u32 = 1 << 4;
+
 /// Pause duration field when sending pause frames
 type PauseDuration = U32;
 
@@ -57,6 +64,14 @@ impl From<MemRegionError> for Error {
     }
 }
 
+/// Interrupt events acknowledged by `Enet::ack_irqs`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IrqEvents {
+    pub rx_frame: bool,
+    /// Periodic timer event, see `Enet::start_periodic_timer`
+    pub timer: bool,
+}
+
 pub struct Enet {
     enet: ENET,
     mac: EthernetAddress,
@@ -313,17 +328,36 @@ impl Enet {
         }
     }
 
-    pub fn ack_irqs(&mut self) -> bool {
+    /// Start the adjustable timer with a periodic TS_TIMER interrupt,
+    /// gives the driver a wakeup independent of the traffic
+    pub fn start_periodic_timer(&mut self, period_ms: u32) {
+        unsafe {
+            self.enet.atcr.write(0);
+            self.enet.atvr.write(0);
+            self.enet.atinc.write(TIMER_INC_NS);
+            self.enet.atper.write(period_ms.saturating_mul(1_000_000));
+            self.enet.atcr.write(TIMER_ENABLE | TIMER_PERIOD_RESET);
+        }
+        self.enet.eir.modify(InterruptEvent::TsTimer::Set);
+        self.enet.eimr.modify(InterruptMask::TsTimer::Set);
+    }
+
+    pub fn ack_irqs(&mut self) -> IrqEvents {
         let irqs = self.enet.eir.extract();
-        self.enet
-            .eir
-            .modify(InterruptEvent::BusErr::Set + InterruptEvent::RxFrame::Set);
+        self.enet.eir.modify(
+            InterruptEvent::BusErr::Set
+                + InterruptEvent::RxFrame::Set
+                + InterruptEvent::TsTimer::Set,
+        );
 
         if irqs.is_set(InterruptEvent::BusErr::Set) {
             log::warn!("[enet] BUS error");
         }
 
-        irqs.is_set(InterruptEvent::RxFrame::Set)
+        IrqEvents {
+            rx_frame: irqs.is_set(InterruptEvent::RxFrame::Set),
+            timer: irqs.is_set(InterruptEvent::TsTimer::Set),
+        }
     }
 
     /// Receives the next available packet from the rx ring, if one is ready.
@@ -1,5 +1,15 @@
-use crate::embedded_hal::digital::v2::OutputPin;
-use crate::pac::gpio::*;
+//! General purpose I/O
+//!
+//! The GPIO block only controls the direction and level of a pin. Pulls,
+//! open-drain and drive strength are pad settings in the IOMUXC, which is
+//! owned by the iomux driver. A mode only takes full effect once its
+//! `PadControl` value has been applied there, along with the pad's GPIO
+//! mux, with an `iomux::Request::ConfigureGpio`.
+
+use crate::embedded_hal::digital::v2::{
+    InputPin, OutputPin, PinState, StatefulOutputPin, ToggleableOutputPin,
+};
+use crate::pac::gpio::RegisterBlock;
 use core::{convert::Infallible, marker::PhantomData};
 
 pub trait GpioExt {
@@ -8,6 +18,23 @@ pub trait GpioExt {
     fn split(self) -> Self::Parts;
 }
 
+/// Disabled mode, the reset state of a pin (input, pad not configured)
+pub struct Disabled;
+
+/// Input mode
+pub struct Input<MODE> {
+    _mode: PhantomData<MODE>,
+}
+
+/// Floating input (type state)
+pub struct Floating;
+
+/// Pulled down input (type state)
+pub struct PullDown;
+
+/// Pulled up input (type state)
+pub struct PullUp;
+
 /// Output mode
 pub struct Output<MODE> {
     _mode: PhantomData<MODE>,
@@ -16,71 +43,664 @@ pub struct Output<MODE> {
 /// Push pull output (type state)
 pub struct PushPull;
 
-/// Disabled mode
-pub struct Disabled;
+/// Open drain output (type state)
+pub struct OpenDrain;
 
-pub struct Gpio {
-    pub bank3: GpioBank3,
+/// Interrupt trigger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+    RisingFalling,
 }
 
-pub struct GpioBank3 {
-    pub p3_19: P3_19<Disabled>,
+/// IOMUXC pad control (SW_PAD_CTL_PAD_*) value expected by a pin mode
+pub trait PadControl {
+    const PAD_CONTROL: u32;
 }
 
-pub struct P3_19<MODE> {
-    bank: GPIO3,
-    _mode: PhantomData<MODE>,
+/// Hysteresis enable
+const PAD_HYS: u32 = 1 << 16;
+/// 100K pull down
+const PAD_PUS_100K_DOWN: u32 = 0 << 14;
+/// 100K pull up
+const PAD_PUS_100K_UP: u32 = 2 << 14;
+/// Pull (rather than keeper) select
+const PAD_PUE: u32 = 1 << 13;
+/// Pull/keeper enable
+const PAD_PKE: u32 = 1 << 12;
+/// Open drain enable
+const PAD_ODE: u32 = 1 << 11;
+/// Medium speed, 100 MHz
+const PAD_SPEED_MEDIUM: u32 = 2 << 6;
+/// 40 Ohm drive strength
+const PAD_DSE_40_OHM: u32 = 6 << 3;
+/// Fast slew rate
+const PAD_SRE_FAST: u32 = 1;
+
+const PAD_OUTPUT: u32 = PAD_SPEED_MEDIUM | PAD_DSE_40_OHM | PAD_SRE_FAST;
+
+impl PadControl for Input<Floating> {
+    const PAD_CONTROL: u32 = PAD_HYS;
 }
 
-impl GpioExt for GPIO3 {
-    type Parts = Gpio;
+impl PadControl for Input<PullDown> {
+    const PAD_CONTROL: u32 = PAD_HYS | PAD_PUS_100K_DOWN | PAD_PUE | PAD_PKE;
+}
 
-    fn split(self) -> Self::Parts {
-        Gpio {
-            bank3: GpioBank3 {
-                p3_19: P3_19 {
-                    bank: self,
-                    _mode: PhantomData,
-                },
-            },
-        }
+impl PadControl for Input<PullUp> {
+    const PAD_CONTROL: u32 = PAD_HYS | PAD_PUS_100K_UP | PAD_PUE | PAD_PKE;
+}
+
+impl PadControl for Output<PushPull> {
+    const PAD_CONTROL: u32 = PAD_HYS | PAD_OUTPUT;
+}
+
+impl PadControl for Output<OpenDrain> {
+    const PAD_CONTROL: u32 = PAD_HYS | PAD_ODE | PAD_OUTPUT;
+}
+
+// Pins of a bank share its registers, they're only accessed from
+// the owning process's single thread
+#[inline]
+fn block<'a>(regs: *mut RegisterBlock) -> &'a mut RegisterBlock {
+    unsafe { &mut *regs }
+}
+
+#[inline]
+fn set_data(regs: *mut RegisterBlock, i: u8, high: bool) {
+    let r = block(regs);
+    let val = r.data.read();
+    let val = if high {
+        val | (1 << i)
+    } else {
+        val & !(1 << i)
+    };
+    unsafe { r.data.write(val) };
+}
+
+#[inline]
+fn data(regs: *mut RegisterBlock, i: u8) -> bool {
+    block(regs).data.read() & (1 << i) != 0
+}
+
+/// Pad status, reflects the pad level in both input and output modes
+#[inline]
+fn status(regs: *mut RegisterBlock, i: u8) -> bool {
+    block(regs).status.read() & (1 << i) != 0
+}
+
+#[inline]
+fn set_direction(regs: *mut RegisterBlock, i: u8, output: bool) {
+    let r = block(regs);
+    let val = r.direction.read();
+    let val = if output {
+        val | (1 << i)
+    } else {
+        val & !(1 << i)
+    };
+    unsafe { r.direction.write(val) };
+}
+
+/// Configures the trigger in ICR1/ICR2, both edges are selected by EDGE_SEL
+/// which overrides the ICR setting
+#[inline]
+fn set_interrupt_edge(regs: *mut RegisterBlock, i: u8, edge: Edge) {
+    let r = block(regs);
+    let cfg = match edge {
+        Edge::Rising | Edge::RisingFalling => 0b10,
+        Edge::Falling => 0b11,
+    };
+    let shift = u32::from(i % 16) * 2;
+    if i < 16 {
+        let val = r.int_cfg1.read();
+        unsafe { r.int_cfg1.write((val & !(0b11 << shift)) | (cfg << shift)) };
+    } else {
+        let val = r.int_cfg2.read();
+        unsafe { r.int_cfg2.write((val & !(0b11 << shift)) | (cfg << shift)) };
     }
+    let val = r.edge_select.read();
+    let val = if edge == Edge::RisingFalling {
+        val | (1 << i)
+    } else {
+        val & !(1 << i)
+    };
+    unsafe { r.edge_select.write(val) };
 }
 
-// TODO - macro impl generate all the pins/banks
-// https://github.com/auxoncorp/ferros/issues/88
-impl<MODE> P3_19<MODE> {
-    const OFFSET: usize = 19;
+#[inline]
+fn set_interrupt_enabled(regs: *mut RegisterBlock, i: u8, enabled: bool) {
+    let r = block(regs);
+    let val = r.int_mask.read();
+    let val = if enabled {
+        val | (1 << i)
+    } else {
+        val & !(1 << i)
+    };
+    unsafe { r.int_mask.write(val) };
 }
 
-impl P3_19<Disabled> {
-    #[inline]
-    pub fn into_push_pull_output(mut self) -> P3_19<Output<PushPull>> {
-        let val = self.bank.data.read();
-        unsafe { self.bank.data.write(val | (1 << Self::OFFSET)) };
+#[inline]
+fn interrupt_pending(regs: *mut RegisterBlock, i: u8) -> bool {
+    block(regs).int_status.read() & (1 << i) != 0
+}
+
+/// Interrupt status is write-1-to-clear
+#[inline]
+fn clear_interrupt(regs: *mut RegisterBlock, i: u8) {
+    unsafe { block(regs).int_status.write(1 << i) };
+}
+
+macro_rules! impl_digital {
+    ($Pin:ident) => {
+        impl<MODE> OutputPin for $Pin<Output<MODE>> {
+            type Error = Infallible;
+
+            fn set_high(&mut self) -> Result<(), Self::Error> {
+                set_data(self.regs, self.offset(), true);
+                Ok(())
+            }
+
+            fn set_low(&mut self) -> Result<(), Self::Error> {
+                set_data(self.regs, self.offset(), false);
+                Ok(())
+            }
+        }
+
+        impl<MODE> StatefulOutputPin for $Pin<Output<MODE>> {
+            fn is_set_high(&self) -> Result<bool, Self::Error> {
+                Ok(data(self.regs, self.offset()))
+            }
+
+            fn is_set_low(&self) -> Result<bool, Self::Error> {
+                Ok(!data(self.regs, self.offset()))
+            }
+        }
+
+        impl<MODE> ToggleableOutputPin for $Pin<Output<MODE>> {
+            type Error = Infallible;
+
+            fn toggle(&mut self) -> Result<(), Self::Error> {
+                let high = data(self.regs, self.offset());
+                set_data(self.regs, self.offset(), !high);
+                Ok(())
+            }
+        }
+
+        impl<MODE> $Pin<Input<MODE>> {
+            /// Configures the interrupt trigger, see `enable_interrupt`
+            pub fn trigger_on_edge(&mut self, edge: Edge) {
+                set_interrupt_edge(self.regs, self.offset(), edge);
+            }
+
+            pub fn enable_interrupt(&mut self) {
+                set_interrupt_enabled(self.regs, self.offset(), true);
+            }
+
+            pub fn disable_interrupt(&mut self) {
+                set_interrupt_enabled(self.regs, self.offset(), false);
+            }
+
+            pub fn check_interrupt(&self) -> bool {
+                interrupt_pending(self.regs, self.offset())
+            }
+
+            pub fn clear_interrupt_pending_bit(&mut self) {
+                clear_interrupt(self.regs, self.offset());
+            }
+        }
+
+        impl<MODE> InputPin for $Pin<Input<MODE>> {
+            type Error = Infallible;
 
-        let val = self.bank.direction.read();
-        unsafe { self.bank.direction.write(val | (1 << Self::OFFSET)) };
+            fn is_high(&self) -> Result<bool, Self::Error> {
+                Ok(status(self.regs, self.offset()))
+            }
 
-        P3_19 {
-            bank: self.bank,
-            _mode: PhantomData,
+            fn is_low(&self) -> Result<bool, Self::Error> {
+                Ok(!status(self.regs, self.offset()))
+            }
         }
+
+        /// Reads the pad level, which another device may be pulling low
+        impl InputPin for $Pin<Output<OpenDrain>> {
+            type Error = Infallible;
+
+            fn is_high(&self) -> Result<bool, Self::Error> {
+                Ok(status(self.regs, self.offset()))
+            }
+
+            fn is_low(&self) -> Result<bool, Self::Error> {
+                Ok(!status(self.regs, self.offset()))
+            }
+        }
+    };
+}
+
+/// Fully erased pin, see `degrade`
+pub struct Pin<MODE> {
+    regs: *mut RegisterBlock,
+    bank: u8,
+    i: u8,
+    _mode: PhantomData<MODE>,
+}
+
+impl<MODE> Pin<MODE> {
+    /// GPIO bank number, 1 through 7
+    pub fn bank(&self) -> u8 {
+        self.bank
+    }
+
+    /// Pin number within the bank
+    pub fn pin(&self) -> u8 {
+        self.i
+    }
+
+    #[inline]
+    fn offset(&self) -> u8 {
+        self.i
     }
 }
 
-impl OutputPin for P3_19<Output<PushPull>> {
-    type Error = Infallible;
+impl_digital!(Pin);
+
+/// Untyped access to a whole bank, for drivers that hand out
+/// pins at runtime rather than owning them
+pub struct Bank {
+    regs: *mut RegisterBlock,
+}
 
-    fn set_high(&mut self) -> Result<(), Self::Error> {
-        let val = self.bank.data.read();
-        unsafe { self.bank.data.write(val | (1 << Self::OFFSET)) };
-        Ok(())
+impl Bank {
+    pub fn set_direction(&mut self, pin: u8, output: bool) {
+        set_direction(self.regs, pin, output);
     }
 
-    fn set_low(&mut self) -> Result<(), Self::Error> {
-        let val = self.bank.data.read();
-        unsafe { self.bank.data.write(val & !(1 << Self::OFFSET)) };
-        Ok(())
+    pub fn set_level(&mut self, pin: u8, high: bool) {
+        set_data(self.regs, pin, high);
+    }
+
+    /// Pad level, in both input and output modes
+    pub fn level(&self, pin: u8) -> bool {
+        status(self.regs, pin)
+    }
+
+    /// Enables the pin's interrupt on `edge`, or disables it on `None`
+    pub fn set_interrupt(&mut self, pin: u8, edge: Option<Edge>) {
+        match edge {
+            Some(edge) => {
+                set_interrupt_enabled(self.regs, pin, false);
+                set_interrupt_edge(self.regs, pin, edge);
+                clear_interrupt(self.regs, pin);
+                set_interrupt_enabled(self.regs, pin, true);
+            }
+            None => {
+                set_interrupt_enabled(self.regs, pin, false);
+                clear_interrupt(self.regs, pin);
+            }
+        }
+    }
+
+    /// Clears and returns the pending interrupts of the enabled pins,
+    /// one bit per pin
+    pub fn take_interrupts(&mut self) -> u32 {
+        let r = block(self.regs);
+        let pending = r.int_status.read() & r.int_mask.read();
+        unsafe { r.int_status.write(pending) };
+        pending
     }
 }
+
+macro_rules! gpio {
+    ($GPIOx:ident, $gpiox:ident, $bank:expr, [
+        $($PXi:ident: ($pxi:ident, $i:expr),)+
+    ]) => {
+        pub mod $gpiox {
+            use super::*;
+            use crate::pac::gpio::$GPIOx;
+
+            pub struct Parts {
+                $(
+                    pub $pxi: $PXi<Disabled>,
+                )+
+            }
+
+            impl From<$GPIOx> for Bank {
+                fn from(mut gpio: $GPIOx) -> Self {
+                    Bank { regs: &mut *gpio }
+                }
+            }
+
+            impl GpioExt for $GPIOx {
+                type Parts = Parts;
+
+                fn split(mut self) -> Self::Parts {
+                    let regs: *mut RegisterBlock = &mut *self;
+                    Parts {
+                        $(
+                            $pxi: $PXi {
+                                regs,
+                                _mode: PhantomData,
+                            },
+                        )+
+                    }
+                }
+            }
+
+            $(
+                pub struct $PXi<MODE> {
+                    regs: *mut RegisterBlock,
+                    _mode: PhantomData<MODE>,
+                }
+
+                impl<MODE> $PXi<MODE> {
+                    const OFFSET: u8 = $i;
+
+                    #[inline]
+                    fn offset(&self) -> u8 {
+                        Self::OFFSET
+                    }
+
+                    #[inline]
+                    fn into_mode<NEW>(self) -> $PXi<NEW> {
+                        $PXi {
+                            regs: self.regs,
+                            _mode: PhantomData,
+                        }
+                    }
+
+                    pub fn into_floating_input(self) -> $PXi<Input<Floating>> {
+                        set_direction(self.regs, Self::OFFSET, false);
+                        self.into_mode()
+                    }
+
+                    pub fn into_pull_down_input(self) -> $PXi<Input<PullDown>> {
+                        set_direction(self.regs, Self::OFFSET, false);
+                        self.into_mode()
+                    }
+
+                    pub fn into_pull_up_input(self) -> $PXi<Input<PullUp>> {
+                        set_direction(self.regs, Self::OFFSET, false);
+                        self.into_mode()
+                    }
+
+                    /// Configures the pin as a push pull output, driven low
+                    pub fn into_push_pull_output(self) -> $PXi<Output<PushPull>> {
+                        self.into_push_pull_output_in_state(PinState::Low)
+                    }
+
+                    /// The level is set before the direction, so the pin doesn't glitch
+                    pub fn into_push_pull_output_in_state(
+                        self,
+                        state: PinState,
+                    ) -> $PXi<Output<PushPull>> {
+                        set_data(self.regs, Self::OFFSET, state == PinState::High);
+                        set_direction(self.regs, Self::OFFSET, true);
+                        self.into_mode()
+                    }
+
+                    /// Configures the pin as an open drain output, released (high)
+                    pub fn into_open_drain_output(self) -> $PXi<Output<OpenDrain>> {
+                        self.into_open_drain_output_in_state(PinState::High)
+                    }
+
+                    pub fn into_open_drain_output_in_state(
+                        self,
+                        state: PinState,
+                    ) -> $PXi<Output<OpenDrain>> {
+                        set_data(self.regs, Self::OFFSET, state == PinState::High);
+                        set_direction(self.regs, Self::OFFSET, true);
+                        self.into_mode()
+                    }
+
+                    /// Pad control value to apply through the iomux driver, the
+                    /// pulls and open drain of a mode are only set there
+                    pub fn pad_control(&self) -> u32
+                    where
+                        MODE: PadControl,
+                    {
+                        MODE::PAD_CONTROL
+                    }
+
+                    /// Erases the bank and pin number from the type
+                    pub fn degrade(self) -> Pin<MODE> {
+                        Pin {
+                            regs: self.regs,
+                            bank: $bank,
+                            i: Self::OFFSET,
+                            _mode: PhantomData,
+                        }
+                    }
+                }
+
+                impl_digital!($PXi);
+            )+
+        }
+    };
+}
+
+gpio!(GPIO1, gpio1, 1, [
+    P1_0: (p1_0, 0),
+    P1_1: (p1_1, 1),
+    P1_2: (p1_2, 2),
+    P1_3: (p1_3, 3),
+    P1_4: (p1_4, 4),
+    P1_5: (p1_5, 5),
+    P1_6: (p1_6, 6),
+    P1_7: (p1_7, 7),
+    P1_8: (p1_8, 8),
+    P1_9: (p1_9, 9),
+    P1_10: (p1_10, 10),
+    P1_11: (p1_11, 11),
+    P1_12: (p1_12, 12),
+    P1_13: (p1_13, 13),
+    P1_14: (p1_14, 14),
+    P1_15: (p1_15, 15),
+    P1_16: (p1_16, 16),
+    P1_17: (p1_17, 17),
+    P1_18: (p1_18, 18),
+    P1_19: (p1_19, 19),
+    P1_20: (p1_20, 20),
+    P1_21: (p1_21, 21),
+    P1_22: (p1_22, 22),
+    P1_23: (p1_23, 23),
+    P1_24: (p1_24, 24),
+    P1_25: (p1_25, 25),
+    P1_26: (p1_26, 26),
+    P1_27: (p1_27, 27),
+    P1_28: (p1_28, 28),
+    P1_29: (p1_29, 29),
+    P1_30: (p1_30, 30),
+    P1_31: (p1_31, 31),
+]);
+
+gpio!(GPIO2, gpio2, 2, [
+    P2_0: (p2_0, 0),
+    P2_1: (p2_1, 1),
+    P2_2: (p2_2, 2),
+    P2_3: (p2_3, 3),
+    P2_4: (p2_4, 4),
+    P2_5: (p2_5, 5),
+    P2_6: (p2_6, 6),
+    P2_7: (p2_7, 7),
+    P2_8: (p2_8, 8),
+    P2_9: (p2_9, 9),
+    P2_10: (p2_10, 10),
+    P2_11: (p2_11, 11),
+    P2_12: (p2_12, 12),
+    P2_13: (p2_13, 13),
+    P2_14: (p2_14, 14),
+    P2_15: (p2_15, 15),
+    P2_16: (p2_16, 16),
+    P2_17: (p2_17, 17),
+    P2_18: (p2_18, 18),
+    P2_19: (p2_19, 19),
+    P2_20: (p2_20, 20),
+    P2_21: (p2_21, 21),
+    P2_22: (p2_22, 22),
+    P2_23: (p2_23, 23),
+    P2_24: (p2_24, 24),
+    P2_25: (p2_25, 25),
+    P2_26: (p2_26, 26),
+    P2_27: (p2_27, 27),
+    P2_28: (p2_28, 28),
+    P2_29: (p2_29, 29),
+    P2_30: (p2_30, 30),
+    P2_31: (p2_31, 31),
+]);
+
+gpio!(GPIO3, gpio3, 3, [
+    P3_0: (p3_0, 0),
+    P3_1: (p3_1, 1),
+    P3_2: (p3_2, 2),
+    P3_3: (p3_3, 3),
+    P3_4: (p3_4, 4),
+    P3_5: (p3_5, 5),
+    P3_6: (p3_6, 6),
+    P3_7: (p3_7, 7),
+    P3_8: (p3_8, 8),
+    P3_9: (p3_9, 9),
+    P3_10: (p3_10, 10),
+    P3_11: (p3_11, 11),
+    P3_12: (p3_12, 12),
+    P3_13: (p3_13, 13),
+    P3_14: (p3_14, 14),
+    P3_15: (p3_15, 15),
+    P3_16: (p3_16, 16),
+    P3_17: (p3_17, 17),
+    P3_18: (p3_18, 18),
+    P3_19: (p3_19, 19),
+    P3_20: (p3_20, 20),
+    P3_21: (p3_21, 21),
+    P3_22: (p3_22, 22),
+    P3_23: (p3_23, 23),
+    P3_24: (p3_24, 24),
+    P3_25: (p3_25, 25),
+    P3_26: (p3_26, 26),
+    P3_27: (p3_27, 27),
+    P3_28: (p3_28, 28),
+    P3_29: (p3_29, 29),
+    P3_30: (p3_30, 30),
+    P3_31: (p3_31, 31),
+]);
+
+gpio!(GPIO4, gpio4, 4, [
+    P4_0: (p4_0, 0),
+    P4_1: (p4_1, 1),
+    P4_2: (p4_2, 2),
+    P4_3: (p4_3, 3),
+    P4_4: (p4_4, 4),
+    P4_5: (p4_5, 5),
+    P4_6: (p4_6, 6),
+    P4_7: (p4_7, 7),
+    P4_8: (p4_8, 8),
+    P4_9: (p4_9, 9),
+    P4_10: (p4_10, 10),
+    P4_11: (p4_11, 11),
+    P4_12: (p4_12, 12),
+    P4_13: (p4_13, 13),
+    P4_14: (p4_14, 14),
+    P4_15: (p4_15, 15),
+    P4_16: (p4_16, 16),
+    P4_17: (p4_17, 17),
+    P4_18: (p4_18, 18),
+    P4_19: (p4_19, 19),
+    P4_20: (p4_20, 20),
+    P4_21: (p4_21, 21),
+    P4_22: (p4_22, 22),
+    P4_23: (p4_23, 23),
+    P4_24: (p4_24, 24),
+    P4_25: (p4_25, 25),
+    P4_26: (p4_26, 26),
+    P4_27: (p4_27, 27),
+    P4_28: (p4_28, 28),
+    P4_29: (p4_29, 29),
+    P4_30: (p4_30, 30),
+    P4_31: (p4_31, 31),
+]);
+
+gpio!(GPIO5, gpio5, 5, [
+    P5_0: (p5_0, 0),
+    P5_1: (p5_1, 1),
+    P5_2: (p5_2, 2),
+    P5_3: (p5_3, 3),
+    P5_4: (p5_4, 4),
+    P5_5: (p5_5, 5),
+    P5_6: (p5_6, 6),
+    P5_7: (p5_7, 7),
+    P5_8: (p5_8, 8),
+    P5_9: (p5_9, 9),
+    P5_10: (p5_10, 10),
+    P5_11: (p5_11, 11),
+    P5_12: (p5_12, 12),
+    P5_13: (p5_13, 13),
+    P5_14: (p5_14, 14),
+    P5_15: (p5_15, 15),
+    P5_16: (p5_16, 16),
+    P5_17: (p5_17, 17),
+    P5_18: (p5_18, 18),
+    P5_19: (p5_19, 19),
+    P5_20: (p5_20, 20),
+    P5_21: (p5_21, 21),
+    P5_22: (p5_22, 22),
+    P5_23: (p5_23, 23),
+    P5_24: (p5_24, 24),
+    P5_25: (p5_25, 25),
+    P5_26: (p5_26, 26),
+    P5_27: (p5_27, 27),
+    P5_28: (p5_28, 28),
+    P5_29: (p5_29, 29),
+    P5_30: (p5_30, 30),
+    P5_31: (p5_31, 31),
+]);
+
+gpio!(GPIO6, gpio6, 6, [
+    P6_0: (p6_0, 0),
+    P6_1: (p6_1, 1),
+    P6_2: (p6_2, 2),
+    P6_3: (p6_3, 3),
+    P6_4: (p6_4, 4),
+    P6_5: (p6_5, 5),
+    P6_6: (p6_6, 6),
+    P6_7: (p6_7, 7),
+    P6_8: (p6_8, 8),
+    P6_9: (p6_9, 9),
+    P6_10: (p6_10, 10),
+    P6_11: (p6_11, 11),
+    P6_12: (p6_12, 12),
+    P6_13: (p6_13, 13),
+    P6_14: (p6_14, 14),
+    P6_15: (p6_15, 15),
+    P6_16: (p6_16, 16),
+    P6_17: (p6_17, 17),
+    P6_18: (p6_18, 18),
+    P6_19: (p6_19, 19),
+    P6_20: (p6_20, 20),
+    P6_21: (p6_21, 21),
+    P6_22: (p6_22, 22),
+    P6_23: (p6_23, 23),
+    P6_24: (p6_24, 24),
+    P6_25: (p6_25, 25),
+    P6_26: (p6_26, 26),
+    P6_27: (p6_27, 27),
+    P6_28: (p6_28, 28),
+    P6_29: (p6_29, 29),
+    P6_30: (p6_30, 30),
+    P6_31: (p6_31, 31),
+]);
+
+gpio!(GPIO7, gpio7, 7, [
+    P7_0: (p7_0, 0),
+    P7_1: (p7_1, 1),
+    P7_2: (p7_2, 2),
+    P7_3: (p7_3, 3),
+    P7_4: (p7_4, 4),
+    P7_5: (p7_5, 5),
+    P7_6: (p7_6, 6),
+    P7_7: (p7_7, 7),
+    P7_8: (p7_8, 8),
+    P7_9: (p7_9, 9),
+    P7_10: (p7_10, 10),
+    P7_11: (p7_11, 11),
+    P7_12: (p7_12, 12),
+    P7_13: (p7_13, 13),
+]);
@@ -7,6 +7,7 @@ pub use imx6_devices::{bounded_registers, typenum};
 pub use nb;
 
 pub mod asm;
+pub mod delay;
 pub mod enet;
 pub mod gpio;
 pub mod otp;
@@ -14,3 +15,5 @@ pub mod serial;
 pub mod spi;
 pub mod spi_nor_flash;
 pub mod timer;
+pub mod wait;
+pub mod watchdog;
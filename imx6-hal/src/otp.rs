@@ -51,4 +51,38 @@ impl Otp {
             .val() as u8;
         EthernetAddress([b0, b1, b2, b3, b4, b5])
     }
+
+    /// Read the 64-bit chip unique ID from the CFG0 (low) and CFG1 (high) fuses
+    pub fn read_unique_id(&self) -> u64 {
+        let lo = self.ocotp.cfg0.read();
+        let hi = self.ocotp.cfg1.read();
+        (u64::from(hi) << 32) | u64::from(lo)
+    }
+
+    /// Read the fused MAC address, or forge one from the unique ID
+    /// when the MAC fuses are blank
+    pub fn read_mac_address_or_forged(&self) -> EthernetAddress {
+        let mac = self.read_mac_address();
+        if mac.0 == [0; 6] || mac.0 == [0xFF; 6] {
+            Self::forged_mac_address(self.read_unique_id())
+        } else {
+            mac
+        }
+    }
+
+    /// Locally administered, unicast MAC address derived from the unique ID.
+    ///
+    /// The ID is folded into the lower 5 octets, the first octet
+    /// is fixed at 0x02.
+    pub fn forged_mac_address(unique_id: u64) -> EthernetAddress {
+        let id = unique_id.to_le_bytes();
+        EthernetAddress([
+            0x02,
+            id[0] ^ id[5],
+            id[1] ^ id[6],
+            id[2] ^ id[7],
+            id[3],
+            id[4],
+        ])
+    }
 }
@@ -1,17 +1,37 @@
 //! SPI driver based on the u-boot ECSPI driver
+//!
+//! Transfers of any length are split into back to back bursts of at most
+//! `MAX_BURST_BYTES`, a TX FIFO's worth. Each burst is loaded into the TX
+//! FIFO whole before XCH is set, since the controller clears XCH and ends
+//! the exchange as soon as the TX FIFO runs empty, and RX is drained while
+//! it's in progress.
+//!
+//! The chip select is expected to be a GPIO held by the caller for the
+//! whole transaction, see `spi::Transactional`.
+//!
+//! Waiting for RX data and transfer completion spins by default, `with_wait`
+//! blocks on the RR and TC interrupts instead.
 
-// NOTE: the Transfer impl can likely be improved to not require
-// the 2 part operations, mostly copied from u-boot
-
-use crate::asm;
-use crate::pac::{ecspi1::*, typenum};
+use crate::pac::{ecspi::*, typenum};
+use crate::timer::Hertz;
+use crate::wait::{Spin, Wait};
 use core::cmp;
-use embedded_hal::blocking::spi;
-pub use embedded_hal::spi::{Mode, Phase, Polarity};
+use core::ops::DerefMut;
+use embedded_hal::blocking::spi::{self, Operation};
+pub use embedded_hal::spi::{Mode, Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_3};
 use num::integer::Integer;
 
+/// ECSPI root clock, PLL3 / 8 with the reset value of the CSCDR2 divider
+pub const REF_CLOCK_HZ: u32 = 60_000_000;
+
 const FIFO_SIZE_WORDS: usize = 64;
-const FIFO_SIZE_BYTES: usize = FIFO_SIZE_WORDS * 4;
+
+/// Bursts fit in the TX FIFO, the burst length field would allow 512 bytes
+const MAX_BURST_BYTES: usize = FIFO_SIZE_WORDS * 4;
+
+/// Interrupt enable bits, RX FIFO ready (RR) and transfer completed (TC)
+const INT_RX_READY: u32 = 1 << 3;
+const INT_TRANSFER_COMPLETE: u32 = 1 << 7;
 
 /// SPI error
 #[non_exhaustive]
@@ -19,21 +39,82 @@ const FIFO_SIZE_BYTES: usize = FIFO_SIZE_WORDS * 4;
 pub enum Error {
     /// Overrun occurred
     Overrun,
-    /// This driver only supports transfers of up to `FIFO_SIZE_BYTES`
-    TooMuchData,
 }
 
-pub struct Spi<SPI> {
+/// Chip select channel, the SSn signal of the controller
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ChipSelect {
+    Cs0,
+    Cs1,
+    Cs2,
+    Cs3,
+}
+
+pub struct Spi<SPI, W = Spin> {
     spi: SPI,
+    wait: W,
+    mode: Mode,
+    cs: ChipSelect,
+    pre_divider: u32,
+    post_divider: u32,
 }
 
-impl Spi<ECSPI1> {
-    pub fn new(spi: ECSPI1) -> Self {
-        log::trace!("[ECSPI1] init");
-        let mut spi = Spi { spi };
+impl<SPI> Spi<SPI, Spin>
+where
+    SPI: DerefMut<Target = RegisterBlock>,
+{
+    /// SCLK is the fastest rate at or below `freq` the dividers allow
+    pub fn new<F: Into<Hertz>>(spi: SPI, mode: Mode, freq: F, cs: ChipSelect) -> Self {
+        let (pre_divider, post_divider) = dividers(freq.into());
+        let mut spi = Spi {
+            spi,
+            wait: Spin,
+            mode,
+            cs,
+            pre_divider,
+            post_divider,
+        };
+        log::trace!("[ECSPI] init {:?}, SCLK {} Hz", spi.cs, spi.frequency().0);
         spi.reset();
         spi
     }
+}
+
+impl<SPI, W> Spi<SPI, W>
+where
+    SPI: DerefMut<Target = RegisterBlock>,
+    W: Wait,
+{
+    /// Block on the controller's interrupt through `wait` instead of spinning
+    pub fn with_wait<V: Wait>(self, wait: V) -> Spi<SPI, V> {
+        Spi {
+            spi: self.spi,
+            wait,
+            mode: self.mode,
+            cs: self.cs,
+            pre_divider: self.pre_divider,
+            post_divider: self.post_divider,
+        }
+    }
+
+    pub fn free(self) -> SPI {
+        self.spi
+    }
+
+    /// Reconfigure for another device on the bus
+    pub fn configure<F: Into<Hertz>>(&mut self, mode: Mode, freq: F, cs: ChipSelect) {
+        let (pre_divider, post_divider) = dividers(freq.into());
+        self.mode = mode;
+        self.cs = cs;
+        self.pre_divider = pre_divider;
+        self.post_divider = post_divider;
+        self.reset();
+    }
+
+    /// The actual SCLK rate
+    pub fn frequency(&self) -> Hertz {
+        Hertz(REF_CLOCK_HZ / ((self.pre_divider + 1) << self.post_divider))
+    }
 
     pub fn reset(&mut self) {
         self.spi.ctl.modify(
@@ -49,21 +130,29 @@ impl Spi<ECSPI1> {
                 + Control::HardwareTrigger::Clear
                 + Control::Exchange::Clear
                 + Control::StartModeControl::Clear
-                + Control::PostDivider::Field::checked::<typenum::U0>()
-                + Control::PreDivider::Field::checked::<typenum::U2>()
+                + Control::PostDivider::Field::new(self.post_divider).unwrap()
+                + Control::PreDivider::Field::new(self.pre_divider).unwrap()
                 + Control::DataReadyControl::Any
-                + Control::ChannelSelect::ChipSelect0
+                + Control::ChannelSelect::Field::new(self.cs as u32).unwrap()
                 + Control::BurstLength::Field::checked::<typenum::U0>(),
         );
 
-        self.spi.cfg.modify(
-            Config::Channel0Phase::Phase0
-                + Config::Channel0Polarity::ActiveHigh
-                + Config::Channel0WaveFromSelect::Clear
-                + Config::Channel0SSPolarity::ActiveLow
-                + Config::Channel0DataCtl::StayHigh
-                + Config::Channel0SclkCtl::StayLow,
-        );
+        // Each field of the config register has a bit per channel,
+        // at bit `channel` of every nibble
+        let ch = self.cs as u32;
+        let phase = match self.mode.phase {
+            Phase::CaptureOnFirstTransition => 0,
+            Phase::CaptureOnSecondTransition => 1,
+        };
+        let (polarity, sclk_idle) = match self.mode.polarity {
+            Polarity::IdleLow => (0, 0),
+            Polarity::IdleHigh => (1, 1),
+        };
+        // SS_CTL clear (single burst), SS_POL active low, DATA_CTL stay high
+        let mask = (1 << ch) * 0x11_1111;
+        let val = (phase << ch) | (polarity << (4 + ch)) | (sclk_idle << (20 + ch));
+        let cfg = self.spi.cfg.read();
+        unsafe { self.spi.cfg.write((cfg & !mask) | val) };
 
         self.spi
             .int
@@ -76,26 +165,38 @@ impl Spi<ECSPI1> {
         self.spi.period.modify(Period::ClockSource::RefClock);
 
         log::trace!(
-            "[ECSPI1] ctl=0x{:04X}, cfg=0x{:04X}, period=0x{:04X}",
+            "[ECSPI] ctl=0x{:04X}, cfg=0x{:04X}, period=0x{:04X}",
             self.spi.ctl.read(),
             self.spi.cfg.read(),
             self.spi.period.read()
         );
     }
-}
 
-impl spi::Transfer<u8> for Spi<ECSPI1> {
-    type Error = Error;
-
-    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
-        let mut n_bytes = words.len();
-        let n_bits = n_bytes * 8;
-        log::trace!("[ECSPI1] transfer len {} bit_len {}", n_bytes, n_bits);
+    /// Streams `buf` out in bursts of up to `MAX_BURST_BYTES`, replacing
+    /// its contents with the received data if it's a `Transfer`
+    fn exchange(&mut self, buf: &mut Operation<'_, u8>) -> Result<(), Error> {
+        let len = match buf {
+            Operation::Write(words) => words.len(),
+            Operation::Transfer(words) => words.len(),
+        };
+        log::trace!("[ECSPI] exchange len {}", len);
 
-        if n_bytes > FIFO_SIZE_BYTES {
-            log::error!("[ECSPI1] transfer would overflow the fifo");
-            return Err(Error::TooMuchData);
+        let mut offset = 0;
+        while offset < len {
+            let burst_len = cmp::min(len - offset, MAX_BURST_BYTES);
+            self.burst(buf, offset, burst_len)?;
+            offset += burst_len;
         }
+        Ok(())
+    }
+
+    fn burst(
+        &mut self,
+        buf: &mut Operation<'_, u8>,
+        offset: usize,
+        len: usize,
+    ) -> Result<(), Error> {
+        let n_bits = len * 8;
 
         self.spi.ctl.modify(
             Control::Enable::Set + Control::BurstLength::Field::new((n_bits - 1) as u32).unwrap(),
@@ -105,70 +206,140 @@ impl spi::Transfer<u8> for Spi<ECSPI1> {
             .status
             .modify(Status::RxFifoOverflow::Set + Status::TransferComplete::Set);
 
-        // The SPI controller works only with words,
-        // check if less than a word is sent.
+        // The SPI controller works only with words, when the burst isn't
+        // a multiple of 32 bits the first word carries the remainder.
         // Access to the FIFO is only 32 bit
-        let mut byte_index = 0;
-        let mut data: u32 = 0;
-
-        if n_bits % 32 != 0 {
-            let cnt = (n_bits % 32) / 8;
-            for _ in 0..cnt {
-                data = (data << 8) | u32::from(words[byte_index]);
-                byte_index += 1;
-            }
-            unsafe { self.spi.tx.write(data) };
-            n_bytes -= cnt;
-        }
+        let lead = len % 4;
+        let n_words = len.div_ceil(&4);
+        let word_range = |w: usize| {
+            let start = if w == 0 || lead == 0 {
+                w * 4
+            } else {
+                lead + (w - 1) * 4
+            };
+            let cnt = if w == 0 && lead != 0 { lead } else { 4 };
+            offset + start..offset + start + cnt
+        };
 
-        while n_bytes > 0 {
-            data = 0;
-            for _ in 0..4 {
-                data = (data << 8) | u32::from(words[byte_index]);
-                byte_index += 1;
-            }
+        // Load the whole burst, XCH self-clears once the TX FIFO is empty
+        // so it can't be topped up once the exchange has started
+        debug_assert!(n_words <= FIFO_SIZE_WORDS);
+        for w in 0..n_words {
+            let data = match buf {
+                Operation::Write(words) => pack(&words[word_range(w)]),
+                Operation::Transfer(words) => pack(&words[word_range(w)]),
+            };
             unsafe { self.spi.tx.write(data) };
-            n_bytes -= 4;
         }
 
-        // FIFO is written, now starts the transfer setting the XCH bit
         self.spi.ctl.modify(Control::Exchange::Set);
 
+        // The RX FIFO is as deep as TX, so it can't overflow
+        let mut rx_words = 0;
+        while rx_words < n_words {
+            if self.spi.status.is_set(Status::RxFifoReady::Set) {
+                let data = self.spi.rx.read();
+                if let Operation::Transfer(words) = buf {
+                    unpack(data, &mut words[word_range(rx_words)]);
+                }
+                rx_words += 1;
+            } else {
+                self.wait_for(INT_RX_READY);
+            }
+        }
+
         // Wait until the TC (Transfer completed) bit is set
         while !self.spi.status.is_set(Status::TransferComplete::Set) {
-            asm::nop();
+            self.wait_for(INT_TRANSFER_COMPLETE);
         }
 
+        let overrun = self.spi.status.is_set(Status::RxFifoOverflow::Set);
+
         // Transfer completed, clear any pending request
         self.spi
             .status
             .modify(Status::RxFifoOverflow::Set + Status::TransferComplete::Set);
 
-        n_bytes = n_bits.div_ceil(&8);
-        byte_index = 0;
-
-        if n_bits % 32 != 0 {
-            data = self.spi.rx.read();
-            let cnt = (n_bits % 32) / 8;
-            data = data.to_be() >> ((4 - cnt) * 8);
-            (&mut words[byte_index..byte_index + cnt]).copy_from_slice(&data.to_ne_bytes()[..cnt]);
-            byte_index += cnt;
-            n_bytes -= cnt;
+        if overrun {
+            Err(Error::Overrun)
+        } else {
+            Ok(())
         }
+    }
 
-        while n_bytes > 0 {
-            let tmp = self.spi.rx.read();
-            data = tmp.to_be();
-            let cnt = cmp::min(n_bytes, 4);
-            (&mut words[byte_index..byte_index + cnt]).copy_from_slice(&data.to_ne_bytes()[..cnt]);
-            byte_index += cnt;
-            n_bytes -= cnt;
+    /// Block until one of the `sources` interrupts is pending
+    fn wait_for(&mut self, sources: u32) {
+        if W::INTERRUPTS {
+            unsafe { self.spi.int.write(sources) };
+            self.wait.wait();
+            unsafe { self.spi.int.write(0) };
+        } else {
+            self.wait.wait();
         }
+    }
+}
 
-        if self.spi.status.is_set(Status::RxFifoOverflow::Set) {
-            Err(Error::Overrun)
-        } else {
-            Ok(words)
+/// Pre (divide by 1-16) and post (divide by 2^0-2^15) divider field values
+/// for the fastest SCLK at or below `freq`
+fn dividers(freq: Hertz) -> (u32, u32) {
+    let freq = u64::from(cmp::max(freq.0, 1));
+    for post in 0..16 {
+        let pre = u64::from(REF_CLOCK_HZ).div_ceil(&(freq << post));
+        if pre <= 16 {
+            return (cmp::max(pre, 1) as u32 - 1, post);
         }
     }
+    (15, 15)
+}
+
+/// Bytes are shifted out MSB first, from the low `bytes.len()` bytes
+/// of the FIFO word
+fn pack(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |data, b| (data << 8) | u32::from(*b))
+}
+
+fn unpack(data: u32, bytes: &mut [u8]) {
+    let cnt = bytes.len();
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = (data >> ((cnt - 1 - i) * 8)) as u8;
+    }
+}
+
+impl<SPI, W> spi::Transfer<u8> for Spi<SPI, W>
+where
+    SPI: DerefMut<Target = RegisterBlock>,
+    W: Wait,
+{
+    type Error = Error;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+        self.exchange(&mut Operation::Transfer(&mut *words))?;
+        Ok(words)
+    }
+}
+
+impl<SPI, W> spi::Write<u8> for Spi<SPI, W>
+where
+    SPI: DerefMut<Target = RegisterBlock>,
+    W: Wait,
+{
+    type Error = Error;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        self.exchange(&mut Operation::Write(words))
+    }
+}
+
+impl<SPI, W> spi::Transactional<u8> for Spi<SPI, W>
+where
+    SPI: DerefMut<Target = RegisterBlock>,
+    W: Wait,
+{
+    type Error = Error;
+
+    /// Runs the operations back to back, the caller holds the chip select
+    /// across the whole list
+    fn exec<'a>(&mut self, operations: &mut [Operation<'a, u8>]) -> Result<(), Self::Error> {
+        operations.iter_mut().try_for_each(|op| self.exchange(op))
+    }
 }
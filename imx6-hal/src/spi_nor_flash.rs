@@ -1,38 +1,98 @@
 //! SPI NOR FLASH
+//!
+//! The geometry is read from the part's SFDP basic parameter table, parts
+//! without one are looked up by JEDEC ID in `KNOWN_PARTS`.
+//!
+//! `protect_lower` makes the driver refuse to program or erase the bottom of
+//! the part, and locks it with the block protect bits on parts which can
+//! protect from the bottom.
 
 use crate::{
-    embedded_hal::{blocking::spi::Transfer, digital::v2::OutputPin},
-    gpio::{Output, PushPull, P3_19},
-    pac::ecspi1::ECSPI1,
-    spi::Spi,
+    embedded_hal::blocking::{
+        delay::DelayUs,
+        spi::{Operation, Transactional},
+    },
+    spi::{Mode, MODE_0},
 };
 use bitflags::bitflags;
+use core::cmp;
+use core::convert::TryInto;
+use nor_flash::NorFlash;
 
-/// 2 MiB
-pub const FLASH_SIZE_BYTES: usize = 2 * 1024 * 1024;
-/// 4 KiB
-pub const ERASE_SIZE_BYTES: usize = 4096;
-/// 256 byte pages
-pub const PAGE_SIZE_BYTES: usize = 256;
+pub const SPI_MODE: Mode = MODE_0;
+pub const SPI_FREQ_HZ: u32 = 20_000_000;
 
-pub type CsPin = P3_19<Output<PushPull>>;
+/// Busy polling intervals, sector and block erases take tens to hundreds
+/// of milliseconds, chip erases seconds and page programs under one
+const ERASE_POLL_INTERVAL_US: u32 = 1_000;
+const CHIP_ERASE_POLL_INTERVAL_US: u32 = 100_000;
+const PROGRAM_POLL_INTERVAL_US: u32 = 100;
+
+/// Time to enter deep power-down (tDP) and to leave it (tRES1)
+const POWER_DOWN_DELAY_US: u32 = 10;
+const RELEASE_POWER_DOWN_DELAY_US: u32 = 50;
+
+const BLOCK_32K_BYTES: usize = 32 * 1024;
+const BLOCK_64K_BYTES: usize = 64 * 1024;
+
+/// Largest part addressable with 3 byte addresses, 16 MiB
+const MAX_CAPACITY_BYTES: u64 = 1 << 24;
+
+/// Page size of parts which don't report one
+const DEFAULT_PAGE_SIZE_BYTES: usize = 256;
+
+/// "SFDP" in little endian
+const SFDP_SIGNATURE: u32 = 0x5044_4653;
+/// ID of the JEDEC basic flash parameter table
+const SFDP_BFPT_ID: u16 = 0xFF00;
+/// JESD216 defines 9 DWORDs, later revisions up to 16 of which only the
+/// first 11 are used
+const SFDP_BFPT_MIN_DWORDS: usize = 9;
+const SFDP_BFPT_MAX_DWORDS: usize = 11;
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum Error {
     /// An SPI transfer failed.
     Spi,
 
-    /// A GPIO could not be set.
-    Gpio,
-
     /// Status register contained unexpected flags.
     ///
     /// This can happen when the chip is faulty, incorrectly connected, or the
     /// driver wasn't constructed or destructed properly (eg. while there is
     /// still a write in progress).
     UnexpectedStatus,
+
+    /// The part has no usable SFDP table and its JEDEC ID isn't in
+    /// `KNOWN_PARTS`, or it needs 4 byte addresses.
+    UnsupportedPart,
+
+    /// The part doesn't support an erase of the requested size.
+    UnsupportedErase,
+
+    /// The address range extends past the end of the flash.
+    OutOfBounds,
+
+    /// An erase range doesn't start and end on sector boundaries.
+    Misaligned,
+
+    /// The data read back doesn't match the data written.
+    VerifyFailed,
+
+    /// The address range overlaps the protected area, see `protect_lower`.
+    Protected,
+
+    /// The part is in deep power-down, see `release_power_down`.
+    PoweredDown,
+
+    /// The JEDEC ID read after leaving deep power-down doesn't match the
+    /// one read by `init`.
+    UnexpectedId,
+
+    /// The part doesn't support deep power-down, see `KnownPart`.
+    PowerDownUnsupported,
 }
 
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct JedecId {
     bytes: [u8; 3],
 }
@@ -47,6 +107,184 @@ impl JedecId {
     }
 }
 
+/// An erase command and the size of the area it erases
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct EraseType {
+    pub size_bytes: usize,
+    pub opcode: u8,
+}
+
+pub const MAX_ERASE_TYPES: usize = 4;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Geometry {
+    pub capacity_bytes: usize,
+    pub page_size_bytes: usize,
+    /// Supported erase types, smallest first, there is at least one
+    pub erase_types: [Option<EraseType>; MAX_ERASE_TYPES],
+}
+
+impl Geometry {
+    /// The smallest erasable area
+    pub fn sector(&self) -> EraseType {
+        self.erase_types[0].expect("Geometry without an erase type")
+    }
+
+    /// Parse the basic flash parameter table, `None` if the part needs 4
+    /// byte addresses or the table is malformed
+    pub fn from_sfdp_bfpt(dwords: &[u32]) -> Option<Self> {
+        if dwords.len() < SFDP_BFPT_MIN_DWORDS {
+            return None;
+        }
+
+        // Address bytes, 0 is 3 byte only
+        if (dwords[0] >> 17) & 0b11 != 0 {
+            return None;
+        }
+
+        // Density in bits, N + 1 or 2^N when bit 31 is set
+        let density = dwords[1];
+        let capacity_bits = if density & (1 << 31) == 0 {
+            u64::from(density) + 1
+        } else {
+            1u64.checked_shl(density & !(1 << 31))?
+        };
+        let capacity_bytes = capacity_bits / 8;
+        if capacity_bytes == 0 || capacity_bytes > MAX_CAPACITY_BYTES {
+            return None;
+        }
+
+        // Erase types 1-4, a 2^N byte size and an opcode each,
+        // a size of 0 is unsupported
+        let mut erase_types = [None; MAX_ERASE_TYPES];
+        let mut n = 0;
+        for dword in dwords[7..9].iter() {
+            for half in [*dword as u16, (*dword >> 16) as u16] {
+                let exp = half as u8;
+                if exp != 0 && exp < 32 {
+                    erase_types[n] = Some(EraseType {
+                        size_bytes: 1 << exp,
+                        opcode: (half >> 8) as u8,
+                    });
+                    n += 1;
+                }
+            }
+        }
+        // JESD216 parts without erase types still report the 4K erase
+        if n == 0 && dwords[0] & 0b11 == 0b01 {
+            erase_types[0] = Some(EraseType {
+                size_bytes: 4096,
+                opcode: (dwords[0] >> 8) as u8,
+            });
+            n = 1;
+        }
+        if n == 0 {
+            return None;
+        }
+        erase_types[..n].sort_unstable_by_key(|e| e.map(|e| e.size_bytes));
+
+        // Page size 2^N, JESD216A and later
+        let page_size_bytes = match dwords.get(10) {
+            Some(dword) => 1 << ((dword >> 4) & 0xF),
+            None => DEFAULT_PAGE_SIZE_BYTES,
+        };
+
+        let geometry = Geometry {
+            capacity_bytes: capacity_bytes as usize,
+            page_size_bytes,
+            erase_types,
+        };
+        geometry.is_valid().then(|| geometry)
+    }
+
+    /// Erase areas and pages must tile the part
+    fn is_valid(&self) -> bool {
+        self.capacity_bytes.is_power_of_two()
+            && self.page_size_bytes <= self.capacity_bytes
+            && self.erase_types[0].is_some()
+            && self
+                .erase_types
+                .iter()
+                .flatten()
+                .all(|e| e.size_bytes <= self.capacity_bytes)
+    }
+}
+
+/// Block protect bits for the largest area at the bottom, with `Status::TB`
+/// set, that fits in `size_bytes`. 1-5 protect the lower 1/32 to 1/2 of the
+/// part, 6 all of it
+fn block_protect_bits(capacity_bytes: usize, size_bytes: usize) -> u8 {
+    if size_bytes >= capacity_bytes {
+        return 6;
+    }
+    (1..=5)
+        .rev()
+        .find(|bits| capacity_bytes >> (6 - bits) <= size_bytes)
+        .unwrap_or(0)
+}
+
+/// A part identified by JEDEC ID, the geometry is used if it has no SFDP
+/// table
+pub struct KnownPart {
+    pub name: &'static str,
+    pub mfr_code: u8,
+    pub device_id: u16,
+    pub geometry: Geometry,
+    /// `Status::TB` selects whether the block protect bits count from the
+    /// top or the bottom, otherwise they only protect the top
+    pub bottom_protect: bool,
+    /// Supports the deep power-down (0xB9) and release (0xAB) commands
+    pub deep_power_down: bool,
+}
+
+const fn known_part(
+    name: &'static str,
+    mfr_code: u8,
+    device_id: u16,
+    capacity_bytes: usize,
+    bottom_protect: bool,
+    deep_power_down: bool,
+) -> KnownPart {
+    KnownPart {
+        name,
+        mfr_code,
+        device_id,
+        bottom_protect,
+        deep_power_down,
+        geometry: Geometry {
+            capacity_bytes,
+            page_size_bytes: DEFAULT_PAGE_SIZE_BYTES,
+            erase_types: [
+                Some(EraseType {
+                    size_bytes: 4096,
+                    opcode: 0x20,
+                }),
+                Some(EraseType {
+                    size_bytes: 32 * 1024,
+                    opcode: 0x52,
+                }),
+                Some(EraseType {
+                    size_bytes: 64 * 1024,
+                    opcode: 0xD8,
+                }),
+                None,
+            ],
+        },
+    }
+}
+
+/// Fallback geometry by JEDEC ID, all with 4K, 32K and 64K erases. Deep
+/// power-down is only used on the parts listed here as supporting it
+pub const KNOWN_PARTS: [KnownPart; 6] = [
+    // The sabrelite's flash, also emulated by QEMU, 0xAB is its read ID
+    known_part("SST25VF016B", 0xBF, 0x2541, 2 * 1024 * 1024, false, false),
+    known_part("W25Q16", 0xEF, 0x4015, 2 * 1024 * 1024, true, true),
+    known_part("W25Q32", 0xEF, 0x4016, 4 * 1024 * 1024, true, true),
+    known_part("W25Q64", 0xEF, 0x4017, 8 * 1024 * 1024, true, true),
+    known_part("MX25L1606E", 0xC2, 0x2015, 2 * 1024 * 1024, false, true),
+    known_part("MX25L3206E", 0xC2, 0x2016, 4 * 1024 * 1024, false, true),
+];
+
 bitflags! {
     /// Status register bits.
     pub struct Status: u8 {
@@ -56,6 +294,9 @@ bitflags! {
         const WEL = 1 << 1;
         /// The 3 protection region bits.
         const PROT = 0b00011100;
+        /// **T**op/**B**ottom protect on parts with `bottom_protect`, a
+        /// fourth protection bit on most others.
+        const TB = 1 << 5;
         /// **S**tatus **R**egister **W**rite **D**isable bit.
         const SRWD = 1 << 7;
     }
@@ -63,22 +304,52 @@ bitflags! {
 
 enum Opcode {
     ReadStatus = 0x05,
+    WriteStatus = 0x01,
     ReadJedecId = 0x9F,
     ReadFast = 0x0B,
-    SectorErase = 0x20,
+    ReadSfdp = 0x5A,
     WriteEnable = 0x06,
     WriteDisable = 0x04,
     PageProg = 0x02,
+    ChipErase = 0xC7,
+    DeepPowerDown = 0xB9,
+    ReleasePowerDown = 0xAB,
 }
 
-pub struct SpiNorFlash {
-    spi: Spi<ECSPI1>,
-    cs: CsPin,
+/// Flash on an SPI bus whose `Transactional` impl holds the chip select
+/// across each transaction, `delay` paces the busy polling
+pub struct SpiNorFlash<SPI, D> {
+    spi: SPI,
+    delay: D,
+    geometry: Geometry,
+    bottom_protect: bool,
+    deep_power_down: bool,
+    /// Size of the area at the bottom the driver refuses to modify
+    protected_bytes: usize,
+    id: JedecId,
+    powered_down: bool,
 }
 
-impl SpiNorFlash {
-    pub fn init(spi: Spi<ECSPI1>, cs: CsPin) -> Result<Self, Error> {
-        let mut f = Self { spi, cs };
+impl<SPI, D> SpiNorFlash<SPI, D>
+where
+    SPI: Transactional<u8>,
+    D: DelayUs<u32>,
+{
+    pub fn init(spi: SPI, delay: D) -> Result<Self, Error> {
+        let mut f = Self {
+            spi,
+            delay,
+            // Replaced once the part is identified
+            geometry: KNOWN_PARTS[0].geometry,
+            bottom_protect: false,
+            deep_power_down: false,
+            protected_bytes: 0,
+            id: JedecId { bytes: [0; 3] },
+            powered_down: false,
+        };
+        // A restarted driver may have left the part in deep power-down, where
+        // it ignores everything else. Harmless on parts without it
+        f.release()?;
         let status = f.read_status()?;
         let id = f.read_jedec_id()?;
         log::trace!(
@@ -90,27 +361,140 @@ impl SpiNorFlash {
         if !(status & (Status::BUSY | Status::WEL)).is_empty() {
             return Err(Error::UnexpectedStatus);
         }
+        let part = KNOWN_PARTS
+            .iter()
+            .find(|p| p.mfr_code == id.mfr_code() && p.device_id == id.device_id());
+        f.id = id;
+        f.geometry = f.detect_geometry(&id, part)?;
+        f.bottom_protect = part.map_or(false, |p| p.bottom_protect);
+        f.deep_power_down = part.map_or(false, |p| p.deep_power_down);
+        log::debug!(
+            "[flash] {} bytes, {} byte pages, {} byte sectors",
+            f.geometry.capacity_bytes,
+            f.geometry.page_size_bytes,
+            f.geometry.sector().size_bytes
+        );
         Ok(f)
     }
 
+    pub fn geometry(&self) -> &Geometry {
+        &self.geometry
+    }
+
     pub fn read_status(&mut self) -> Result<Status, Error> {
-        let mut cmd = [Opcode::ReadStatus as u8];
+        let cmd = [Opcode::ReadStatus as u8];
         let mut data = [0];
-        self.command(&mut cmd, &mut data)?;
+        self.command(&cmd, &mut data)?;
         Ok(Status::from_bits_truncate(data[0]))
     }
 
+    /// Write the non-volatile bits, the busy and write enable latch bits
+    /// are ignored
+    pub fn write_status(&mut self, status: Status) -> Result<(), Error> {
+        log::trace!("[flash] WriteStatus {:?}", status);
+        self.write_enable()?;
+        let cmd = [Opcode::WriteStatus as u8, status.bits()];
+        self.command(&cmd, &mut [])?;
+        self.wait_done(PROGRAM_POLL_INTERVAL_US)?;
+        Ok(())
+    }
+
+    /// Refuse to program or erase the lower `kib` KiB. The block protect bits
+    /// cover as much of it as they can on parts with `bottom_protect`, others
+    /// keep theirs as they are and only the driver enforces it
+    pub fn protect_lower(&mut self, kib: usize) -> Result<(), Error> {
+        let size_bytes = kib * 1024;
+        if size_bytes > self.geometry.capacity_bytes {
+            return Err(Error::OutOfBounds);
+        }
+
+        if self.bottom_protect {
+            let prot = Status::PROT | Status::TB;
+            let bits = block_protect_bits(self.geometry.capacity_bytes, size_bytes);
+            let wanted = Status::from_bits_truncate(bits << 2) | Status::TB;
+            let status = self.read_status()?;
+            // TB is usually non-volatile, only write when something changes
+            if status & prot != wanted {
+                self.write_status((status & !(prot | Status::SRWD)) | wanted)?;
+                if self.read_status()? & prot != wanted {
+                    return Err(Error::UnexpectedStatus);
+                }
+            }
+        } else {
+            log::warn!("[flash] The block protect bits can't cover the bottom of the part");
+        }
+
+        self.protected_bytes = size_bytes;
+        log::debug!("[flash] Protected the lower {} KiB", kib);
+        Ok(())
+    }
+
+    /// Size of the area at the bottom that can't be programmed or erased
+    pub fn protected_bytes(&self) -> usize {
+        self.protected_bytes
+    }
+
+    /// Enter deep power-down, every command but `release_power_down` fails
+    /// with `Error::PoweredDown` until it's released
+    pub fn power_down(&mut self) -> Result<(), Error> {
+        if !self.deep_power_down {
+            return Err(Error::PowerDownUnsupported);
+        }
+        if self.powered_down {
+            return Ok(());
+        }
+        log::trace!("[flash] DeepPowerDown");
+        let cmd = [Opcode::DeepPowerDown as u8];
+        self.command(&cmd, &mut [])?;
+        self.delay.delay_us(POWER_DOWN_DELAY_US);
+        self.powered_down = true;
+        Ok(())
+    }
+
+    /// Leave deep power-down, and check it's still the part `init` found
+    pub fn release_power_down(&mut self) -> Result<(), Error> {
+        self.release()?;
+        self.powered_down = false;
+        if self.read_jedec_id()? != self.id {
+            self.powered_down = true;
+            return Err(Error::UnexpectedId);
+        }
+        Ok(())
+    }
+
+    pub fn is_powered_down(&self) -> bool {
+        self.powered_down
+    }
+
+    /// Whether the part is known to support deep power-down
+    pub fn supports_power_down(&self) -> bool {
+        self.deep_power_down
+    }
+
     pub fn read_jedec_id(&mut self) -> Result<JedecId, Error> {
-        let mut cmd = [Opcode::ReadJedecId as u8];
+        let cmd = [Opcode::ReadJedecId as u8];
         let mut data: [u8; 6] = [0; 6];
-        self.command(&mut cmd, &mut data)?;
+        self.command(&cmd, &mut data)?;
         Ok(JedecId {
             bytes: [data[0], data[1], data[2]],
         })
     }
 
+    /// Read from the SFDP address space, parts without SFDP return garbage
+    pub fn read_sfdp(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), Error> {
+        let cmd = [
+            Opcode::ReadSfdp as u8,
+            (addr >> 16) as u8,
+            (addr >> 8) as u8,
+            addr as u8,
+            0xFF,
+        ];
+        self.transaction(&mut [Operation::Write(&cmd), Operation::Transfer(buf)])
+    }
+
     pub fn read(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), Error> {
-        let mut cmd = [
+        self.check_bounds(addr, buf.len())?;
+        let cmd = [
             Opcode::ReadFast as u8,
             (addr >> 16) as u8,
             (addr >> 8) as u8,
@@ -118,80 +502,313 @@ impl SpiNorFlash {
             0xFF,
         ];
         log::trace!("[flash] ReadFast {} bytes from 0x{:X}", buf.len(), addr);
-        self.spi.reset();
-        self.cs.set_low().map_err(|_| Error::Gpio)?;
-        let mut spi_result = self.spi.transfer(&mut cmd);
-        if spi_result.is_ok() {
-            spi_result = self.spi.transfer(buf);
-        }
-        self.cs.set_high().map_err(|_| Error::Gpio)?;
-        spi_result.map(|_| ()).map_err(|_| Error::Spi)
+        self.transaction(&mut [Operation::Write(&cmd), Operation::Transfer(buf)])
     }
 
+    /// Erase the smallest erasable area containing `addr`
     pub fn erase_sector(&mut self, addr: u32) -> Result<(), Error> {
-        log::trace!("[flash] SectorErase 0x{:X}", addr);
+        self.erase_with(self.geometry.sector(), addr)
+    }
+
+    /// Erase the 32K block containing `addr`
+    pub fn erase_block_32k(&mut self, addr: u32) -> Result<(), Error> {
+        let erase = self.erase_type(BLOCK_32K_BYTES)?;
+        self.erase_with(erase, addr)
+    }
+
+    /// Erase the 64K block containing `addr`
+    pub fn erase_block_64k(&mut self, addr: u32) -> Result<(), Error> {
+        let erase = self.erase_type(BLOCK_64K_BYTES)?;
+        self.erase_with(erase, addr)
+    }
+
+    /// Erase `len` bytes from `addr` with the largest erases that fit, the
+    /// range must start and end on sector boundaries
+    pub fn erase(&mut self, addr: u32, len: usize) -> Result<(), Error> {
+        self.check_writable(addr, len)?;
+        let sector = self.geometry.sector();
+        let mut pos = addr as usize;
+        let end = pos + len;
+        if pos % sector.size_bytes != 0 || len % sector.size_bytes != 0 {
+            return Err(Error::Misaligned);
+        }
+        while pos < end {
+            let erase = self
+                .geometry
+                .erase_types
+                .iter()
+                .flatten()
+                .rev()
+                .find(|e| pos % e.size_bytes == 0 && pos + e.size_bytes <= end)
+                .copied()
+                .unwrap_or(sector);
+            self.erase_with(erase, pos as u32)?;
+            pos += erase.size_bytes;
+        }
+        Ok(())
+    }
+
+    pub fn erase_chip(&mut self) -> Result<(), Error> {
+        self.check_writable(0, self.geometry.capacity_bytes)?;
+        log::trace!("[flash] ChipErase");
+        self.write_enable()?;
+        let cmd = [Opcode::ChipErase as u8];
+        self.command(&cmd, &mut [])?;
+        self.wait_done(CHIP_ERASE_POLL_INTERVAL_US)?;
+        self.write_disable()?;
+        Ok(())
+    }
+
+    /// Program up to a page, `data` must not cross a page boundary
+    pub fn write_page(&mut self, addr: u32, data: &[u8]) -> Result<(), Error> {
+        self.check_writable(addr, data.len())?;
+        log::trace!("[flash] PageProg 0x{:X} size {}", addr, data.len());
         self.write_enable()?;
-        let mut cmd = [
-            Opcode::SectorErase as u8,
+        let cmd = [
+            Opcode::PageProg as u8,
             (addr >> 16) as u8,
             (addr >> 8) as u8,
             addr as u8,
         ];
-        self.command(&mut cmd, &mut [])?;
-        self.wait_done()?;
+        self.transaction(&mut [Operation::Write(&cmd), Operation::Write(data)])?;
+        self.wait_done(PROGRAM_POLL_INTERVAL_US)?;
         self.write_disable()?;
         Ok(())
     }
 
-    pub fn write_page(&mut self, addr: u32, data: &mut [u8]) -> Result<(), Error> {
-        log::trace!("[flash] PageProg 0x{:X} size {}", addr, data.len());
+    /// Program `data` a page at a time, `addr` doesn't need to be page
+    /// aligned. The area must have been erased
+    pub fn write(&mut self, addr: u32, data: &[u8]) -> Result<(), Error> {
+        self.check_writable(addr, data.len())?;
+        let page_size = self.geometry.page_size_bytes;
+        let mut addr = addr as usize;
+        let mut data = data;
+        while !data.is_empty() {
+            // Up to the end of the page
+            let len = cmp::min(page_size - addr % page_size, data.len());
+            let (page, rest) = data.split_at(len);
+            self.write_page(addr as u32, page)?;
+            addr += len;
+            data = rest;
+        }
+        Ok(())
+    }
+
+    /// Read back `data` from `addr` a `buf` at a time and compare, larger
+    /// buffers take fewer transactions
+    ///
+    /// # Panics
+    /// If `buf` is empty
+    pub fn verify(&mut self, addr: u32, data: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+        self.check_bounds(addr, data.len())?;
+        let chunk_size = buf.len();
+        for (c, expected) in data.chunks(chunk_size).enumerate() {
+            let actual = &mut buf[..expected.len()];
+            self.read(addr + (c * chunk_size) as u32, actual)?;
+            if actual != expected {
+                log::warn!(
+                    "[flash] Verify failed at 0x{:X}",
+                    addr as usize + c * chunk_size
+                );
+                return Err(Error::VerifyFailed);
+            }
+        }
+        Ok(())
+    }
+
+    /// SFDP geometry if the part has a basic parameter table, `KNOWN_PARTS`
+    /// otherwise
+    fn detect_geometry(
+        &mut self,
+        id: &JedecId,
+        part: Option<&KnownPart>,
+    ) -> Result<Geometry, Error> {
+        if let Some(geometry) = self.read_sfdp_geometry()? {
+            log::trace!("[flash] Geometry from SFDP");
+            return Ok(geometry);
+        }
+        match part {
+            Some(part) => {
+                log::trace!("[flash] Geometry of known part {}", part.name);
+                Ok(part.geometry)
+            }
+            None => {
+                log::warn!(
+                    "[flash] Unsupported part MFR=0x{:X} ID=0x{:X}",
+                    id.mfr_code(),
+                    id.device_id()
+                );
+                Err(Error::UnsupportedPart)
+            }
+        }
+    }
+
+    fn read_sfdp_geometry(&mut self) -> Result<Option<Geometry>, Error> {
+        // SFDP header followed by the first parameter header, which is
+        // always the basic flash parameter table's
+        let mut header = [0; 16];
+        self.read_sfdp(0, &mut header)?;
+        let signature = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let id = u16::from_le_bytes([header[8], header[15]]);
+        let major_rev = header[10];
+        if signature != SFDP_SIGNATURE || id != SFDP_BFPT_ID || major_rev != 1 {
+            return Ok(None);
+        }
+        let len_dwords = usize::from(header[11]).min(SFDP_BFPT_MAX_DWORDS);
+        let table_addr = u32::from_le_bytes([header[12], header[13], header[14], 0]);
+
+        let mut table = [0; SFDP_BFPT_MAX_DWORDS * 4];
+        let table = &mut table[..len_dwords * 4];
+        self.read_sfdp(table_addr, table)?;
+        let mut dwords = [0; SFDP_BFPT_MAX_DWORDS];
+        for (dword, bytes) in dwords.iter_mut().zip(table.chunks_exact(4)) {
+            *dword = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+        Ok(Geometry::from_sfdp_bfpt(&dwords[..len_dwords]))
+    }
+
+    /// The erase type of `size_bytes`
+    fn erase_type(&self, size_bytes: usize) -> Result<EraseType, Error> {
+        self.geometry
+            .erase_types
+            .iter()
+            .flatten()
+            .find(|e| e.size_bytes == size_bytes)
+            .copied()
+            .ok_or(Error::UnsupportedErase)
+    }
+
+    fn erase_with(&mut self, erase: EraseType, addr: u32) -> Result<(), Error> {
+        // The whole area containing `addr`
+        let start = addr as usize & !(erase.size_bytes - 1);
+        self.check_writable(start as u32, erase.size_bytes)?;
+        log::trace!(
+            "[flash] Erase 0x{:X} size {} opcode 0x{:X}",
+            addr,
+            erase.size_bytes,
+            erase.opcode
+        );
         self.write_enable()?;
-        let mut cmd = [
-            Opcode::PageProg as u8,
+        let cmd = [
+            erase.opcode,
             (addr >> 16) as u8,
             (addr >> 8) as u8,
             addr as u8,
         ];
-        self.spi.reset();
-        self.cs.set_low().map_err(|_| Error::Gpio)?;
-        let mut spi_result = self.spi.transfer(&mut cmd);
-        if spi_result.is_ok() {
-            spi_result = self.spi.transfer(data);
-        }
-        self.cs.set_high().map_err(|_| Error::Gpio)?;
-        spi_result.map(|_| ()).map_err(|_| Error::Spi)?;
-        self.wait_done()?;
+        self.command(&cmd, &mut [])?;
+        self.wait_done(ERASE_POLL_INTERVAL_US)?;
         self.write_disable()?;
         Ok(())
     }
 
-    fn command(&mut self, cmd: &mut [u8], data: &mut [u8]) -> Result<(), Error> {
-        self.spi.reset();
-        self.cs.set_low().map_err(|_| Error::Gpio)?;
-        self.spi.transfer(cmd).map_err(|_| Error::Spi)?;
-        if !data.is_empty() {
-            self.spi.transfer(data).map_err(|_| Error::Spi)?;
+    fn check_bounds(&self, addr: u32, len: usize) -> Result<(), Error> {
+        match (addr as usize).checked_add(len) {
+            Some(end) if end <= self.geometry.capacity_bytes => Ok(()),
+            _ => Err(Error::OutOfBounds),
         }
-        self.cs.set_high().map_err(|_| Error::Gpio)?;
+    }
+
+    fn check_writable(&self, addr: u32, len: usize) -> Result<(), Error> {
+        self.check_bounds(addr, len)?;
+        if len != 0 && (addr as usize) < self.protected_bytes {
+            Err(Error::Protected)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn command(&mut self, cmd: &[u8], data: &mut [u8]) -> Result<(), Error> {
+        self.transaction(&mut [Operation::Write(cmd), Operation::Transfer(data)])
+    }
+
+    /// Send the release from deep power-down command, whatever the state
+    fn release(&mut self) -> Result<(), Error> {
+        log::trace!("[flash] ReleasePowerDown");
+        let cmd = [Opcode::ReleasePowerDown as u8];
+        self.spi
+            .exec(&mut [Operation::Write(&cmd)])
+            .map_err(|_| Error::Spi)?;
+        self.delay.delay_us(RELEASE_POWER_DOWN_DELAY_US);
         Ok(())
     }
 
+    fn transaction(&mut self, ops: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        if self.powered_down {
+            return Err(Error::PoweredDown);
+        }
+        self.spi.exec(ops).map_err(|_| Error::Spi)
+    }
+
     fn write_enable(&mut self) -> Result<(), Error> {
-        let mut cmd = [Opcode::WriteEnable as u8];
-        self.command(&mut cmd, &mut [])?;
+        let cmd = [Opcode::WriteEnable as u8];
+        self.command(&cmd, &mut [])?;
         log::trace!("[flash] write enabled");
         Ok(())
     }
 
     fn write_disable(&mut self) -> Result<(), Error> {
-        let mut cmd = [Opcode::WriteDisable as u8];
-        self.command(&mut cmd, &mut [])?;
+        let cmd = [Opcode::WriteDisable as u8];
+        self.command(&cmd, &mut [])?;
         log::trace!("[flash] write disabled");
         Ok(())
     }
 
-    fn wait_done(&mut self) -> Result<(), Error> {
-        while self.read_status()?.contains(Status::BUSY) {}
+    fn wait_done(&mut self, poll_interval_us: u32) -> Result<(), Error> {
+        while self.read_status()?.contains(Status::BUSY) {
+            self.delay.delay_us(poll_interval_us);
+        }
         Ok(())
     }
 }
+
+impl<SPI, D> NorFlash for SpiNorFlash<SPI, D>
+where
+    SPI: Transactional<u8>,
+    D: DelayUs<u32>,
+{
+    type Error = Error;
+
+    fn capacity_bytes(&self) -> usize {
+        self.geometry.capacity_bytes
+    }
+
+    fn page_size_bytes(&self) -> usize {
+        self.geometry.page_size_bytes
+    }
+
+    fn sector_size_bytes(&self) -> usize {
+        self.geometry.sector().size_bytes
+    }
+
+    fn read(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), Error> {
+        SpiNorFlash::read(self, addr, buf)
+    }
+
+    fn write(&mut self, addr: u32, data: &[u8]) -> Result<(), Error> {
+        SpiNorFlash::write(self, addr, data)
+    }
+
+    fn erase(&mut self, addr: u32, len: usize) -> Result<(), Error> {
+        SpiNorFlash::erase(self, addr, len)
+    }
+
+    fn verify(&mut self, addr: u32, data: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+        SpiNorFlash::verify(self, addr, data, buf)
+    }
+
+    fn protect_lower(&mut self, kib: usize) -> Result<(), Error> {
+        SpiNorFlash::protect_lower(self, kib)
+    }
+
+    fn power_down(&mut self) -> Result<(), Error> {
+        SpiNorFlash::power_down(self)
+    }
+
+    fn release_power_down(&mut self) -> Result<(), Error> {
+        SpiNorFlash::release_power_down(self)
+    }
+
+    fn is_powered_down(&self) -> bool {
+        SpiNorFlash::is_powered_down(self)
+    }
+}
@@ -0,0 +1,27 @@
+//! Waiting on a peripheral's interrupt
+//!
+//! Drivers which can block take a `Wait`, they enable the interrupt sources
+//! they're waiting on around each call to `Wait::wait`. `Spin` leaves the
+//! interrupts masked and busy-waits instead.
+
+use crate::asm;
+
+pub trait Wait {
+    /// Whether the driver enables its interrupt sources while waiting
+    const INTERRUPTS: bool;
+
+    /// Block until one of the enabled sources is pending, they're masked
+    /// again after it returns
+    fn wait(&mut self);
+}
+
+/// Busy-wait with the interrupts masked
+pub struct Spin;
+
+impl Wait for Spin {
+    const INTERRUPTS: bool = false;
+
+    fn wait(&mut self) {
+        asm::nop();
+    }
+}
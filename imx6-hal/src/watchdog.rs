@@ -1,16 +1,149 @@
-// TODO - finish this impl
+//! Watchdog timer
+//!
+//! The timeout and pre-timeout interrupt are configured in 0.5 s steps.
+//! Once started the watchdog can't be disabled, see the WDE bit in WCR.
 
-/*
+use crate::embedded_hal::watchdog;
 use crate::pac::wdog::*;
+use core::ops::DerefMut;
 
-pub struct Watchdog<WDOG: AsRef<RegisterBlock> + AsMut<RegisterBlock>> {
-    wdt: WDOG,
+/// Longest configurable timeout, 128 seconds
+pub const MAX_TIMEOUT_MS: u32 = 128_000;
+
+/// Longest configurable pre-timeout interrupt lead time, 127.5 seconds
+pub const MAX_PRE_TIMEOUT_MS: u32 = 127_500;
+
+const STEP_MS: u32 = 500;
+
+/// WICR interrupt enable (WIE), write-once
+const WICR_INTERRUPT_ENABLE: u16 = 1 << 15;
+/// WICR interrupt status (WTIS), write-1-to-clear
+const WICR_INTERRUPT_STATUS: u16 = 1 << 14;
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+pub struct Milliseconds(pub u32);
+
+impl From<u32> for Milliseconds {
+    fn from(ms: u32) -> Self {
+        Milliseconds(ms)
+    }
+}
+
+/// Cause of the last reset, from WRSR
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ResetCause {
+    SoftwareReset,
+    Timeout,
+    PowerOnReset,
+    Unknown,
+}
+
+pub struct Watchdog<WDOG> {
+    wdog: WDOG,
+}
+
+impl<WDOG> Watchdog<WDOG>
+where
+    WDOG: DerefMut<Target = RegisterBlock>,
+{
+    /// NOTE: does not start/enable
+    pub fn new(wdog: WDOG) -> Self {
+        Watchdog { wdog }
+    }
+
+    pub fn free(self) -> WDOG {
+        self.wdog
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.wdog.wcr.is_set(Control::Enable::Set)
+    }
+
+    pub fn reset_cause(&self) -> ResetCause {
+        let wrsr = &self.wdog.wrsr;
+        if wrsr
+            .get_field(ResetStatus::PowerOnReset::Read)
+            .unwrap()
+            .val()
+            != 0
+        {
+            ResetCause::PowerOnReset
+        } else if wrsr.get_field(ResetStatus::Timeout::Read).unwrap().val() != 0 {
+            ResetCause::Timeout
+        } else if wrsr.get_field(ResetStatus::SwReset::Read).unwrap().val() != 0 {
+            ResetCause::SoftwareReset
+        } else {
+            ResetCause::Unknown
+        }
+    }
+
+    /// The power-down counter is enabled out of reset and asserts
+    /// WDOG_B after 16 seconds unless disabled, write-once
+    pub fn disable_power_down_counter(&mut self) {
+        self.wdog.wmcr.modify(MiscControl::PowerDownEnable::Clear);
+    }
+
+    /// Enable the pre-timeout interrupt, which fires `lead_time` before
+    /// the watchdog times out, rounded down to 0.5 s steps.
+    ///
+    /// The lead time (WICT) and the enable (WIE) are each write-once, so
+    /// they're written together. Only the first call has any effect.
+    pub fn listen_pre_timeout<T>(&mut self, lead_time: T)
+    where
+        T: Into<Milliseconds>,
+    {
+        let lead_time = lead_time.into().0.min(MAX_PRE_TIMEOUT_MS);
+        let wict = (lead_time / STEP_MS) as u16;
+        unsafe { self.wdog.wicr.write(WICR_INTERRUPT_ENABLE | wict) };
+    }
+
+    pub fn is_interrupt_pending(&self) -> bool {
+        self.wdog
+            .wicr
+            .is_set(InterruptControl::InterruptStatus::Set)
+    }
+
+    /// Interrupt status is write-1-to-clear. Only it is written, a
+    /// read-modify-write could write the locked fields
+    pub fn clear_interrupt(&mut self) {
+        unsafe { self.wdog.wicr.write(WICR_INTERRUPT_STATUS) };
+    }
+
+    /// Assert the software reset signal, resets the chip
+    pub fn system_reset(&mut self) {
+        self.wdog.wcr.modify(Control::SwResetSignal::AssertReset);
+    }
+}
+
+impl<WDOG> watchdog::WatchdogEnable for Watchdog<WDOG>
+where
+    WDOG: DerefMut<Target = RegisterBlock>,
+{
+    type Time = Milliseconds;
+
+    /// Start the watchdog, the period is rounded up to 0.5 s steps
+    fn start<T>(&mut self, period: T)
+    where
+        T: Into<Milliseconds>,
+    {
+        let period = period.into().0.max(STEP_MS).min(MAX_TIMEOUT_MS);
+        let wt = ((period + STEP_MS - 1) / STEP_MS) - 1;
+        self.wdog
+            .wcr
+            .modify(Control::Timeout::Field::new(wt as _).unwrap());
+        watchdog::Watchdog::feed(self);
+        self.wdog.wcr.modify(Control::Enable::Set);
+    }
 }
 
-impl<WDOG: AsRef<RegisterBlock> + AsMut<RegisterBlock>> Watchdog<WDOG> {
-    // NOTE: does not start/enable
-    pub fn new(wdt: WDOG) -> Self {
-        Watchdog { wdt }
+impl<WDOG> watchdog::Watchdog for Watchdog<WDOG>
+where
+    WDOG: DerefMut<Target = RegisterBlock>,
+{
+    fn feed(&mut self) {
+        unsafe {
+            self.wdog.wsr.write(SEQUENCE_A);
+            self.wdog.wsr.write(SEQUENCE_B);
+        }
     }
 }
-*/
@@ -0,0 +1,142 @@
+use imx6_hal::spi_nor_flash::{EraseType, Geometry};
+
+const MIB: usize = 1024 * 1024;
+
+/// 4K erase with 0x20, 3 byte addresses only
+const DWORD_0: u32 = 0xFFF1_20E5;
+/// 16 Mbit, N + 1 encoded
+const DENSITY_16M: u32 = 16 * 1024 * 1024 - 1;
+/// 4K 0x20 and 32K 0x52, then 64K 0xD8
+const ERASE_TYPES_1_2: u32 = 0x520F_200C;
+const ERASE_TYPES_3_4: u32 = 0x0000_D810;
+
+fn bfpt(density: u32, erase_1_2: u32, erase_3_4: u32) -> [u32; 9] {
+    [DWORD_0, density, 0, 0, 0, 0, 0, erase_1_2, erase_3_4]
+}
+
+fn erase(size_bytes: usize, opcode: u8) -> Option<EraseType> {
+    Some(EraseType { size_bytes, opcode })
+}
+
+#[test]
+fn jesd216_table() {
+    let g = Geometry::from_sfdp_bfpt(&bfpt(DENSITY_16M, ERASE_TYPES_1_2, ERASE_TYPES_3_4)).unwrap();
+    assert_eq!(g.capacity_bytes, 2 * MIB);
+    assert_eq!(g.page_size_bytes, 256);
+    assert_eq!(
+        g.erase_types,
+        [
+            erase(4096, 0x20),
+            erase(32 * 1024, 0x52),
+            erase(64 * 1024, 0xD8),
+            None
+        ]
+    );
+    assert_eq!(
+        g.sector(),
+        EraseType {
+            size_bytes: 4096,
+            opcode: 0x20
+        }
+    );
+}
+
+#[test]
+fn power_of_two_density() {
+    // 2^26 bits
+    let g = Geometry::from_sfdp_bfpt(&bfpt(0x8000_001A, ERASE_TYPES_1_2, 0)).unwrap();
+    assert_eq!(g.capacity_bytes, 8 * MIB);
+}
+
+#[test]
+fn rejects_parts_over_16_mib() {
+    // 256 Mbit, both encodings
+    assert_eq!(
+        Geometry::from_sfdp_bfpt(&bfpt(0x8000_001C, ERASE_TYPES_1_2, 0)),
+        None
+    );
+    assert_eq!(
+        Geometry::from_sfdp_bfpt(&bfpt(0x0FFF_FFFF, ERASE_TYPES_1_2, 0)),
+        None
+    );
+}
+
+#[test]
+fn rejects_bad_densities() {
+    // Not a power of two
+    assert_eq!(
+        Geometry::from_sfdp_bfpt(&bfpt(3 * 1024 * 1024 - 1, ERASE_TYPES_1_2, 0)),
+        None
+    );
+    // Under a byte
+    assert_eq!(Geometry::from_sfdp_bfpt(&bfpt(0, ERASE_TYPES_1_2, 0)), None);
+    // Shift overflow
+    assert_eq!(
+        Geometry::from_sfdp_bfpt(&bfpt(0x8000_0040, ERASE_TYPES_1_2, 0)),
+        None
+    );
+}
+
+#[test]
+fn rejects_4_byte_addressing() {
+    for addr_bytes in [0b01, 0b10] {
+        let mut dwords = bfpt(DENSITY_16M, ERASE_TYPES_1_2, 0);
+        dwords[0] |= addr_bytes << 17;
+        assert_eq!(Geometry::from_sfdp_bfpt(&dwords), None);
+    }
+}
+
+#[test]
+fn erase_types_are_sorted_and_unsupported_ones_skipped() {
+    // 64K, none, 32K, 4K with another opcode
+    let g = Geometry::from_sfdp_bfpt(&bfpt(DENSITY_16M, 0x0000_D810, 0x21_0C_52_0F)).unwrap();
+    assert_eq!(
+        g.erase_types,
+        [
+            erase(4096, 0x21),
+            erase(32 * 1024, 0x52),
+            erase(64 * 1024, 0xD8),
+            None
+        ]
+    );
+}
+
+#[test]
+fn legacy_4k_erase_without_erase_types() {
+    let g = Geometry::from_sfdp_bfpt(&bfpt(DENSITY_16M, 0, 0)).unwrap();
+    assert_eq!(g.erase_types, [erase(4096, 0x20), None, None, None]);
+}
+
+#[test]
+fn rejects_no_erase_types() {
+    let mut dwords = bfpt(DENSITY_16M, 0, 0);
+    // 4K erase unavailable
+    dwords[0] |= 0b11;
+    assert_eq!(Geometry::from_sfdp_bfpt(&dwords), None);
+}
+
+#[test]
+fn rejects_erase_types_larger_than_the_part() {
+    // 1 Mbit part with a 256K erase
+    let g = Geometry::from_sfdp_bfpt(&bfpt(1024 * 1024 - 1, 0x0000_D812, 0));
+    assert_eq!(g, None);
+}
+
+#[test]
+fn page_size_from_jesd216a_dword_11() {
+    let mut dwords = [0; 11];
+    dwords[..9].copy_from_slice(&bfpt(DENSITY_16M, ERASE_TYPES_1_2, 0));
+    dwords[10] = 6 << 4;
+    let g = Geometry::from_sfdp_bfpt(&dwords).unwrap();
+    assert_eq!(g.page_size_bytes, 64);
+
+    dwords[10] = 8 << 4;
+    let g = Geometry::from_sfdp_bfpt(&dwords).unwrap();
+    assert_eq!(g.page_size_bytes, 256);
+}
+
+#[test]
+fn rejects_short_tables() {
+    let dwords = bfpt(DENSITY_16M, ERASE_TYPES_1_2, 0);
+    assert_eq!(Geometry::from_sfdp_bfpt(&dwords[..8]), None);
+}
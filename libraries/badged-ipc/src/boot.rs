@@ -0,0 +1,81 @@
+//! Boot readiness reporting
+//!
+//! Processes report to the root task once they've finished initializing,
+//! or that they failed to, so it can start their dependents.
+//!
+//! Reports are signals on a notification shared by all processes, each
+//! process sets its own ready or failed bit. Signalling never blocks, so a
+//! process restarted by the supervisor after boot can report again with
+//! nobody waiting on it.
+
+use crate::{Notification, Signaller};
+use ferros::cap::{role, Badge, CNodeRole, CNodeSlots, LocalCNode, LocalCap};
+use ferros::error::SeL4Error;
+use typenum::U2;
+
+/// Number of process badges that fit in a notification word, badge zero
+/// is reserved
+pub const MAX_BOOT_BADGES: usize = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootStatus {
+    Ready,
+    /// Failed to initialize, the process logs why
+    Failed,
+}
+
+impl BootStatus {
+    /// Status of the process with `badge` in the accumulated signal `bits`
+    pub fn from_bits(badge: usize, bits: usize) -> Option<Self> {
+        if bits & failed_bit(badge) != 0 {
+            Some(BootStatus::Failed)
+        } else if bits & ready_bit(badge) != 0 {
+            Some(BootStatus::Ready)
+        } else {
+            None
+        }
+    }
+}
+
+fn ready_bit(badge: usize) -> usize {
+    1 << badge
+}
+
+fn failed_bit(badge: usize) -> usize {
+    1 << (badge + MAX_BOOT_BADGES)
+}
+
+impl Notification {
+    /// Mint a process's boot status reporter into `slots`, its signals set
+    /// the bits of `badge`, which must be non-zero and below
+    /// `MAX_BOOT_BADGES`
+    pub fn create_boot_status<Role: CNodeRole>(
+        &self,
+        cnode: &LocalCap<LocalCNode>,
+        slots: CNodeSlots<U2, Role>,
+        badge: usize,
+    ) -> Result<BootStatusSender<Role>, SeL4Error> {
+        debug_assert!(badge != 0 && badge < MAX_BOOT_BADGES);
+        let (ready_slots, failed_slots) = slots.alloc();
+        Ok(BootStatusSender {
+            ready: self.create_signaller(cnode, ready_slots, Badge::from(ready_bit(badge)))?,
+            failed: self.create_signaller(cnode, failed_slots, Badge::from(failed_bit(badge)))?,
+        })
+    }
+}
+
+#[repr(C)]
+pub struct BootStatusSender<Role: CNodeRole> {
+    ready: Signaller<Role>,
+    failed: Signaller<Role>,
+}
+
+impl BootStatusSender<role::Local> {
+    pub fn ready(&self) {
+        self.ready.signal();
+    }
+
+    pub fn failed(&self) {
+        self.failed.signal();
+    }
+}
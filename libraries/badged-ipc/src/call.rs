@@ -0,0 +1,181 @@
+//! Typed request/response calls from badged callers
+//!
+//! Like ferros' `call_channel`, but each caller is minted with a badge the
+//! responder is handed along with every request, so it can tell its clients
+//! apart. Requests and responses are copied through the IPC buffer as they
+//! are, so they must be plain data, no references or pointers, and no
+//! larger than `MAX_MESSAGE_BYTES`.
+
+use crate::{Error, NOTIFICATION_BADGE};
+use core::marker::PhantomData;
+use core::mem::size_of;
+use core::ptr;
+use ferros::cap::{
+    role, Badge, CNodeRole, CNodeSlots, Cap, Endpoint, LocalCNode, LocalCap, Untyped,
+};
+use ferros::error::SeL4Error;
+use ferros::userland::CapRights;
+use selfe_sys::{
+    seL4_Call, seL4_GetIPCBuffer, seL4_MessageInfo_get_length, seL4_MessageInfo_new,
+    seL4_MessageInfo_t, seL4_Recv, seL4_ReplyRecv, seL4_Word,
+};
+use typenum::{U1, U4};
+
+/// Size of the IPC buffer's message registers, seL4_MsgMaxLength words
+pub const MAX_MESSAGE_BYTES: usize = 120 * size_of::<seL4_Word>();
+
+pub struct CallChannelSetup<Req, Rsp> {
+    ep: LocalCap<Endpoint>,
+    _msg: PhantomData<(Req, Rsp)>,
+}
+
+impl<Req, Rsp> CallChannelSetup<Req, Rsp> {
+    pub fn new(
+        ut: LocalCap<Untyped<U4>>,
+        slots: CNodeSlots<U1, role::Local>,
+    ) -> Result<Self, Error> {
+        for size in [size_of::<Req>(), size_of::<Rsp>()] {
+            if size > MAX_MESSAGE_BYTES {
+                return Err(Error::MessageTooLarge(size));
+            }
+        }
+        Ok(CallChannelSetup {
+            ep: ut.retype(slots)?,
+            _msg: PhantomData,
+        })
+    }
+
+    pub fn create_responder<Role: CNodeRole>(
+        &self,
+        cnode: &LocalCap<LocalCNode>,
+        slots: CNodeSlots<U1, Role>,
+    ) -> Result<Responder<Req, Rsp, Role>, SeL4Error> {
+        Ok(Responder {
+            ep: self.ep.copy(cnode, slots, CapRights::RW)?,
+            _msg: PhantomData,
+        })
+    }
+
+    /// Mint a caller into `slots`, the responder is handed `badge` with
+    /// each of its requests.
+    ///
+    /// Badge zero is reserved, it can't tell the caller apart from others.
+    pub fn create_caller<Role: CNodeRole>(
+        &self,
+        cnode: &LocalCap<LocalCNode>,
+        slots: CNodeSlots<U1, Role>,
+        badge: Badge,
+    ) -> Result<Caller<Req, Rsp, Role>, SeL4Error> {
+        Ok(Caller {
+            // Grant is required for seL4_Call to create a reply cap
+            ep: self.ep.mint(cnode, slots, CapRights::WG, badge)?,
+            _msg: PhantomData,
+        })
+    }
+}
+
+#[repr(C)]
+pub struct Responder<Req, Rsp, Role: CNodeRole> {
+    ep: Cap<Endpoint, Role>,
+    _msg: PhantomData<(Req, Rsp)>,
+}
+
+impl<Req, Rsp> Responder<Req, Rsp, role::Local> {
+    /// Reply to each call with `f` of the caller's badge and its request,
+    /// forever
+    pub fn reply_recv<F>(self, f: F) -> !
+    where
+        F: FnMut(usize, Req) -> Rsp,
+    {
+        self.reply_recv_with_signals(f, |_| ())
+    }
+
+    /// Like `reply_recv`, signals on the notification bound to the thread
+    /// are handed to `s` with `NOTIFICATION_BADGE` cleared from the badge
+    pub fn reply_recv_with_signals<F, S>(self, mut f: F, mut s: S) -> !
+    where
+        F: FnMut(usize, Req) -> Rsp,
+        S: FnMut(usize),
+    {
+        let mut badge: seL4_Word = 0;
+        let mut info = unsafe { seL4_Recv(self.ep.cptr, &mut badge) };
+        loop {
+            if badge as usize & NOTIFICATION_BADGE != 0 {
+                // Nobody to reply to
+                s(badge as usize & !NOTIFICATION_BADGE);
+                info = unsafe { seL4_Recv(self.ep.cptr, &mut badge) };
+                continue;
+            }
+            // A message of the wrong length isn't read as a request, the
+            // empty reply fails the call
+            let reply = match unsafe { read_value::<Req>(info) } {
+                Some(req) => unsafe { write_value(f(badge as _, req)) },
+                None => unsafe { seL4_MessageInfo_new(0, 0, 0, 0) },
+            };
+            info = unsafe { seL4_ReplyRecv(self.ep.cptr, reply, &mut badge) };
+        }
+    }
+}
+
+#[repr(C)]
+pub struct Caller<Req, Rsp, Role: CNodeRole> {
+    ep: Cap<Endpoint, Role>,
+    _msg: PhantomData<(Req, Rsp)>,
+}
+
+impl<Req: Clone, Rsp> Caller<Req, Rsp, role::Local> {
+    /// Call the responder and block until it replies
+    ///
+    /// Fails with `Error::MalformedReply` if the responder dropped the
+    /// request
+    pub fn blocking_call(&self, req: &Req) -> Result<Rsp, Error> {
+        let info = unsafe { seL4_Call(self.ep.cptr, write_value(req.clone())) };
+        unsafe { read_value(info) }.ok_or(Error::MalformedReply)
+    }
+}
+
+/// Whether `names` are `expected`, in order. The root task checks a
+/// responder's table of its callers against the badges it mints them at
+/// build time, see `caller_names` in system.toml
+pub const fn names_match(names: &[&str], expected: &[&str]) -> bool {
+    if names.len() != expected.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < names.len() {
+        let (a, b) = (names[i].as_bytes(), expected[i].as_bytes());
+        if a.len() != b.len() {
+            return false;
+        }
+        let mut j = 0;
+        while j < a.len() {
+            if a[j] != b[j] {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+fn message_words<T>() -> usize {
+    (size_of::<T>() + size_of::<seL4_Word>() - 1) / size_of::<seL4_Word>()
+}
+
+/// Move `value` into the IPC buffer, it's dropped by whoever reads it back
+unsafe fn write_value<T>(value: T) -> seL4_MessageInfo_t {
+    let msg = (*seL4_GetIPCBuffer()).msg.as_mut_ptr();
+    ptr::write_unaligned(msg as *mut T, value);
+    seL4_MessageInfo_new(0, 0, 0, message_words::<T>() as _)
+}
+
+/// Take the value out of the IPC buffer, `None` if the message is the
+/// wrong length for a `T`
+unsafe fn read_value<T>(info: seL4_MessageInfo_t) -> Option<T> {
+    if seL4_MessageInfo_get_length(info) as usize != message_words::<T>() {
+        return None;
+    }
+    let msg = (*seL4_GetIPCBuffer()).msg.as_ptr();
+    Some(ptr::read_unaligned(msg as *const T))
+}
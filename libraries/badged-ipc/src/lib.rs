@@ -0,0 +1,162 @@
+//! Badged IPC between a single receiver and many senders
+//!
+//! Each sender is minted from the receiver's endpoint with a unique badge, so
+//! the receiver can tell who a message came from. Messages are a label plus
+//! a few data words, sent with a plain `seL4_Send`, or with `seL4_Call` for
+//! services which reply, see `service`. Typed calls carrying a whole request
+//! and response are in `call`.
+
+#![no_std]
+
+use ferros::cap::{
+    role, Badge, CNodeRole, CNodeSlots, Cap, Endpoint, IRQError, LocalCNode, LocalCap, Untyped,
+};
+use ferros::error::SeL4Error;
+use ferros::userland::CapRights;
+use selfe_sys::{
+    seL4_GetMR, seL4_MessageInfo_get_label, seL4_MessageInfo_get_length, seL4_MessageInfo_new,
+    seL4_MessageInfo_t, seL4_NBRecv, seL4_Recv, seL4_Send, seL4_SetMR, seL4_Word,
+};
+use typenum::{U1, U4};
+
+pub use crate::boot::*;
+pub use crate::call::*;
+pub use crate::notification::*;
+pub use crate::service::*;
+
+mod boot;
+mod call;
+mod notification;
+mod service;
+
+/// Maximum number of data words in a message
+pub const MAX_DATA_WORDS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Message {
+    /// Badge of the sender
+    pub badge: usize,
+    pub label: usize,
+    len: usize,
+    data: [usize; MAX_DATA_WORDS],
+}
+
+impl Message {
+    pub fn data(&self) -> &[usize] {
+        &self.data[..self.len]
+    }
+
+    /// Signal on the receiving thread's bound notification rather than a
+    /// message, the badge holds the accumulated signal bits
+    pub fn is_notification(&self) -> bool {
+        self.badge & NOTIFICATION_BADGE != 0
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    SeL4Error(SeL4Error),
+    IRQError(IRQError),
+    BindNotification(usize),
+    /// A call channel's request or response of this many bytes doesn't fit
+    /// in the IPC buffer
+    MessageTooLarge(usize),
+    /// A call's reply wasn't a response, the responder dropped the request
+    MalformedReply,
+}
+
+impl From<SeL4Error> for Error {
+    fn from(e: SeL4Error) -> Self {
+        Error::SeL4Error(e)
+    }
+}
+
+impl From<IRQError> for Error {
+    fn from(e: IRQError) -> Self {
+        Error::IRQError(e)
+    }
+}
+
+pub struct Receiver {
+    ep: LocalCap<Endpoint>,
+}
+
+impl Receiver {
+    pub fn new(
+        ut: LocalCap<Untyped<U4>>,
+        slots: CNodeSlots<U1, role::Local>,
+    ) -> Result<Self, SeL4Error> {
+        Ok(Receiver {
+            ep: ut.retype(slots)?,
+        })
+    }
+
+    /// Mint a sender into `slots`, messages it sends are tagged with `badge`.
+    ///
+    /// Badge zero is reserved to indicate no message in `try_recv`.
+    pub fn create_sender<Role: CNodeRole>(
+        &self,
+        cnode: &LocalCap<LocalCNode>,
+        slots: CNodeSlots<U1, Role>,
+        badge: Badge,
+    ) -> Result<Sender<Role>, SeL4Error> {
+        Ok(Sender {
+            ep: self.ep.mint(cnode, slots, CapRights::W, badge)?,
+        })
+    }
+
+    /// Block until a message arrives
+    pub fn recv(&self) -> Message {
+        let mut badge: seL4_Word = 0;
+        let info = unsafe { seL4_Recv(self.ep.cptr, &mut badge) };
+        read_message(info, badge)
+    }
+
+    /// Receive a message if one is pending
+    pub fn try_recv(&self) -> Option<Message> {
+        let mut badge: seL4_Word = 0;
+        let info = unsafe { seL4_NBRecv(self.ep.cptr, &mut badge) };
+        if badge == 0 {
+            None
+        } else {
+            Some(read_message(info, badge))
+        }
+    }
+}
+
+#[repr(C)]
+pub struct Sender<Role: CNodeRole> {
+    ep: Cap<Endpoint, Role>,
+}
+
+impl Sender<role::Local> {
+    /// Send a message, blocks until the receiver picks it up.
+    ///
+    /// At most `MAX_DATA_WORDS` words of `data` are sent.
+    pub fn send(&self, label: usize, data: &[usize]) {
+        unsafe { seL4_Send(self.ep.cptr, set_message(label, data)) };
+    }
+}
+
+/// Load `data` into the message registers, at most `MAX_DATA_WORDS` words
+fn set_message(label: usize, data: &[usize]) -> seL4_MessageInfo_t {
+    let len = data.len().min(MAX_DATA_WORDS);
+    for (i, word) in data[..len].iter().enumerate() {
+        unsafe { seL4_SetMR(i as _, *word as _) };
+    }
+    unsafe { seL4_MessageInfo_new(label as _, 0, 0, len as _) }
+}
+
+fn read_message(info: seL4_MessageInfo_t, badge: seL4_Word) -> Message {
+    let mut data = [0; MAX_DATA_WORDS];
+    let len = (unsafe { seL4_MessageInfo_get_length(info) } as usize).min(MAX_DATA_WORDS);
+    for (i, word) in data[..len].iter_mut().enumerate() {
+        *word = unsafe { seL4_GetMR(i as _) } as _;
+    }
+    Message {
+        badge: badge as _,
+        label: unsafe { seL4_MessageInfo_get_label(info) } as _,
+        len,
+        data,
+    }
+}
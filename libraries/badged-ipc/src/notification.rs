@@ -0,0 +1,203 @@
+//! Badged notifications and interrupt handlers
+//!
+//! A notification bound to a thread's TCB is delivered by `seL4_Recv` on any
+//! endpoint the thread waits on, which lets a server handle interrupts and
+//! requests from the same loop. Bound signals are told apart from messages
+//! by `NOTIFICATION_BADGE`.
+
+use crate::Error;
+use core::marker::PhantomData;
+use ferros::arch::MaxIRQCount;
+use ferros::cap::{
+    role, Badge, CNodeRole, CNodeSlots, Cap, IRQControl, LocalCNode, LocalCap,
+    Notification as NotificationObject, Untyped,
+};
+use ferros::error::SeL4Error;
+use ferros::userland::CapRights;
+use selfe_sys::{
+    seL4_IRQHandler_Ack, seL4_NoError, seL4_Poll, seL4_Signal, seL4_TCB_BindNotification,
+    seL4_Wait, seL4_Word,
+};
+use typenum::{IsLess, True, Unsigned, U1, U2, U4};
+
+/// Set in the badge of every bound notification signal, endpoint badges
+/// must stay below it
+pub const NOTIFICATION_BADGE: usize = 1 << 27;
+
+/// Set in the badge of a `Broadcast` signal, bound interrupt badges must
+/// stay below it
+pub const BROADCAST_BADGE: usize = 1 << 26;
+
+/// Maximum number of notifications a `Broadcast` signals
+pub const MAX_BROADCAST_SIGNALLERS: usize = 8;
+
+pub struct Notification {
+    ntfn: LocalCap<NotificationObject>,
+}
+
+impl Notification {
+    pub fn new(
+        ut: LocalCap<Untyped<U4>>,
+        slots: CNodeSlots<U1, role::Local>,
+    ) -> Result<Self, SeL4Error> {
+        Ok(Notification {
+            ntfn: ut.retype(slots)?,
+        })
+    }
+
+    /// Mint a signaller into `slots`, its signals set the bits of `badge`
+    pub fn create_signaller<Role: CNodeRole>(
+        &self,
+        cnode: &LocalCap<LocalCNode>,
+        slots: CNodeSlots<U1, Role>,
+        badge: Badge,
+    ) -> Result<Signaller<Role>, SeL4Error> {
+        Ok(Signaller {
+            ntfn: self.ntfn.mint(cnode, slots, CapRights::W, badge)?,
+        })
+    }
+
+    pub fn create_waiter<Role: CNodeRole>(
+        &self,
+        cnode: &LocalCap<LocalCNode>,
+        slots: CNodeSlots<U1, Role>,
+    ) -> Result<Waiter<Role>, SeL4Error> {
+        Ok(Waiter {
+            ntfn: self.ntfn.copy(cnode, slots, CapRights::R)?,
+        })
+    }
+
+    /// Create a handler for `IRQ` which signals this notification with
+    /// `NOTIFICATION_BADGE | badge`, and move it into `dest_slots` along
+    /// with a waiter
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_irq_handler<IRQ, Role>(
+        &self,
+        irq_control: &mut LocalCap<IRQControl>,
+        cnode: &LocalCap<LocalCNode>,
+        handler_slots: CNodeSlots<U1, role::Local>,
+        badge_slots: CNodeSlots<U1, role::Local>,
+        dest_slots: CNodeSlots<U2, Role>,
+        badge: usize,
+    ) -> Result<IrqHandler<Role>, Error>
+    where
+        IRQ: Unsigned + IsLess<MaxIRQCount, Output = True>,
+        Role: CNodeRole,
+    {
+        let badged = self.ntfn.mint(
+            cnode,
+            badge_slots,
+            CapRights::W,
+            Badge::from(NOTIFICATION_BADGE | badge),
+        )?;
+        let handler = irq_control.create_handler::<IRQ>(handler_slots)?;
+        let handler = handler.set_notification(&badged)?;
+        let (handler_dest, waiter_dest) = dest_slots.alloc();
+        let handler: Cap<_, Role> = handler.move_to_slot(cnode, handler_dest)?;
+        Ok(IrqHandler {
+            cptr: handler.cptr,
+            _role: PhantomData,
+            waiter: self.create_waiter(cnode, waiter_dest)?,
+        })
+    }
+
+    /// Bind to a thread, its `seL4_Recv` calls then also return signals
+    pub fn bind(&self, tcb: usize) -> Result<(), Error> {
+        let err = unsafe { seL4_TCB_BindNotification(tcb, self.ntfn.cptr) };
+        if err == seL4_NoError as _ {
+            Ok(())
+        } else {
+            Err(Error::BindNotification(err as _))
+        }
+    }
+}
+
+#[repr(C)]
+pub struct Signaller<Role: CNodeRole> {
+    ntfn: Cap<NotificationObject, Role>,
+}
+
+impl Signaller<role::Local> {
+    pub fn signal(&self) {
+        unsafe { seL4_Signal(self.ntfn.cptr) };
+    }
+}
+
+/// Signals several notifications at once, each bound to another process's
+/// thread, which receive `NOTIFICATION_BADGE | BROADCAST_BADGE`
+#[repr(C)]
+pub struct Broadcast<Role: CNodeRole> {
+    signallers: [Option<Signaller<Role>>; MAX_BROADCAST_SIGNALLERS],
+}
+
+impl<Role: CNodeRole> Default for Broadcast<Role> {
+    fn default() -> Self {
+        Broadcast {
+            signallers: Default::default(),
+        }
+    }
+}
+
+impl<Role: CNodeRole> Broadcast<Role> {
+    /// Add a signaller minted with `NOTIFICATION_BADGE | BROADCAST_BADGE`,
+    /// see `Notification::create_signaller`
+    pub fn add(&mut self, signaller: Signaller<Role>) {
+        if let Some(slot) = self.signallers.iter_mut().find(|s| s.is_none()) {
+            *slot = Some(signaller);
+        }
+    }
+}
+
+impl Broadcast<role::Local> {
+    pub fn signal(&self) {
+        self.signallers.iter().flatten().for_each(|s| s.signal());
+    }
+}
+
+#[repr(C)]
+pub struct Waiter<Role: CNodeRole> {
+    ntfn: Cap<NotificationObject, Role>,
+}
+
+impl Waiter<role::Local> {
+    /// Block until signalled, returns the accumulated badge bits
+    pub fn wait(&self) -> usize {
+        let mut badge: seL4_Word = 0;
+        unsafe { seL4_Wait(self.ntfn.cptr, &mut badge) };
+        badge as _
+    }
+
+    /// Returns the accumulated badge bits if signalled
+    pub fn poll(&self) -> Option<usize> {
+        let mut badge: seL4_Word = 0;
+        unsafe { seL4_Poll(self.ntfn.cptr, &mut badge) };
+        if badge == 0 {
+            None
+        } else {
+            Some(badge as _)
+        }
+    }
+}
+
+/// Interrupt handler cap in the CSpace of `Role`, the interrupt number
+/// is only tracked while it's created, see `Notification::create_irq_handler`
+#[repr(C)]
+pub struct IrqHandler<Role: CNodeRole> {
+    cptr: usize,
+    _role: PhantomData<Role>,
+    waiter: Waiter<Role>,
+}
+
+impl IrqHandler<role::Local> {
+    /// Re-enable the interrupt once it's been handled
+    pub fn ack(&self) {
+        unsafe { seL4_IRQHandler_Ack(self.cptr) };
+    }
+
+    /// Block until the notification is signalled, returns the accumulated
+    /// badge bits. A bound notification's thread also receives them from
+    /// `seL4_Recv`, and the bits of every interrupt sharing it are returned
+    pub fn wait(&self) -> usize {
+        self.waiter.wait()
+    }
+}
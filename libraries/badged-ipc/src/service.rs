@@ -0,0 +1,132 @@
+//! Request/response services with per-client event notifications
+//!
+//! Clients call the server over an endpoint minted with their badge, and
+//! the server signals a client's notification when it has events for it.
+//! The server replies to one call at a time from a single loop, see
+//! `Server::recv` and `Server::reply_recv`.
+
+use crate::notification::{Notification, Signaller, Waiter};
+use crate::{read_message, set_message, Message};
+use ferros::cap::{
+    role, Badge, CNodeRole, CNodeSlots, Cap, Endpoint, LocalCNode, LocalCap, Untyped,
+};
+use ferros::error::SeL4Error;
+use ferros::userland::CapRights;
+use selfe_sys::{seL4_Call, seL4_Recv, seL4_ReplyRecv, seL4_Word};
+use typenum::{U1, U2, U4};
+
+/// Client badges are 1 through `MAX_CLIENTS`
+pub const MAX_CLIENTS: usize = 8;
+
+/// Badge of the client event signals
+const EVENT_BADGE: usize = 1;
+
+pub struct ServiceSetup {
+    ep: LocalCap<Endpoint>,
+}
+
+impl ServiceSetup {
+    pub fn new(
+        ut: LocalCap<Untyped<U4>>,
+        slots: CNodeSlots<U1, role::Local>,
+    ) -> Result<Self, SeL4Error> {
+        Ok(ServiceSetup {
+            ep: ut.retype(slots)?,
+        })
+    }
+
+    pub fn create_server<Role: CNodeRole>(
+        &self,
+        cnode: &LocalCap<LocalCNode>,
+        slots: CNodeSlots<U1, Role>,
+    ) -> Result<Server<Role>, SeL4Error> {
+        Ok(Server {
+            ep: self.ep.copy(cnode, slots, CapRights::RW)?,
+            clients: Default::default(),
+        })
+    }
+
+    /// Create a client whose calls carry `badge`, along with the signaller
+    /// the server uses to notify it of events, see `Server::add_client`
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_client<Role: CNodeRole, ServerRole: CNodeRole>(
+        &self,
+        ut: LocalCap<Untyped<U4>>,
+        slots: CNodeSlots<U1, role::Local>,
+        cnode: &LocalCap<LocalCNode>,
+        client_slots: CNodeSlots<U2, Role>,
+        server_slots: CNodeSlots<U1, ServerRole>,
+        badge: Badge,
+    ) -> Result<(Client<Role>, Signaller<ServerRole>), SeL4Error> {
+        let events = Notification::new(ut, slots)?;
+        let (caller_slots, waiter_slots) = client_slots.alloc();
+        let client = Client {
+            // Grant is required for seL4_Call to create a reply cap
+            ep: self.ep.mint(cnode, caller_slots, CapRights::WG, badge)?,
+            events: events.create_waiter(cnode, waiter_slots)?,
+        };
+        let signaller = events.create_signaller(cnode, server_slots, Badge::from(EVENT_BADGE))?;
+        Ok((client, signaller))
+    }
+}
+
+#[repr(C)]
+pub struct Server<Role: CNodeRole> {
+    ep: Cap<Endpoint, Role>,
+    /// Event signallers, indexed by client badge - 1
+    clients: [Option<Signaller<Role>>; MAX_CLIENTS],
+}
+
+impl<Role: CNodeRole> Server<Role> {
+    pub fn add_client(&mut self, badge: usize, events: Signaller<Role>) {
+        assert!((1..=MAX_CLIENTS).contains(&badge));
+        self.clients[badge - 1] = Some(events);
+    }
+}
+
+impl Server<role::Local> {
+    /// Block until a call arrives, or a signal on the thread's bound notification
+    pub fn recv(&self) -> Message {
+        let mut badge: seL4_Word = 0;
+        let info = unsafe { seL4_Recv(self.ep.cptr, &mut badge) };
+        read_message(info, badge)
+    }
+
+    /// Reply to the last call and block until the next one
+    pub fn reply_recv(&self, label: usize, data: &[usize]) -> Message {
+        let mut badge: seL4_Word = 0;
+        let info = unsafe { seL4_ReplyRecv(self.ep.cptr, set_message(label, data), &mut badge) };
+        read_message(info, badge)
+    }
+
+    /// Notify the client with `badge` that it has pending events
+    pub fn signal(&self, badge: usize) {
+        if let Some(Some(events)) = badge.checked_sub(1).and_then(|i| self.clients.get(i)) {
+            events.signal();
+        }
+    }
+}
+
+#[repr(C)]
+pub struct Client<Role: CNodeRole> {
+    ep: Cap<Endpoint, Role>,
+    events: Waiter<Role>,
+}
+
+impl Client<role::Local> {
+    /// Call the server and block until it replies
+    pub fn call(&self, label: usize, data: &[usize]) -> Message {
+        let info = unsafe { seL4_Call(self.ep.cptr, set_message(label, data)) };
+        read_message(info, 0)
+    }
+
+    /// Block until the server signals pending events
+    pub fn wait_events(&self) {
+        self.events.wait();
+    }
+
+    /// Returns true if the server signalled pending events
+    pub fn poll_events(&self) -> bool {
+        self.events.poll().is_some()
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{NorFlash, MAX_REGIONS};
+use core::cell::{Ref, RefCell, RefMut};
+use tickv::{ErrorCode, FlashController};
+
+// TODO
+// put some checks in the linker script or somewhere to check the binary
+// doesn't run into the reserved region
+
+/// Storage in a partition of `regions` regions of `S` bytes at the end of
+/// the flash, the rest of the flash is write protected
+pub struct SpiNorFlashController<'a, F, const S: usize> {
+    flash: RefCell<F>,
+    /// Read back buffer for verifying writes
+    scratchpad: RefCell<&'a mut [u8]>,
+    base_addr: usize,
+    regions: usize,
+    /// Erases of each region since the controller was created, at boot
+    erases_since_boot: RefCell<[u32; MAX_REGIONS]>,
+    /// End of the last write in each region, `None` until it's read from
+    /// the flash
+    write_pointers: RefCell<[Option<usize>; MAX_REGIONS]>,
+}
+
+impl<'a, F, const S: usize> SpiNorFlashController<'a, F, S>
+where
+    F: NorFlash,
+{
+    pub fn new(mut flash: F, scratchpad: &'a mut [u8], regions: usize) -> Result<Self, ErrorCode> {
+        let page_size = flash.page_size_bytes();
+        let sector_size = flash.sector_size_bytes();
+        let capacity = flash.capacity_bytes();
+        if scratchpad.len() < page_size {
+            return Err(ErrorCode::BufferTooSmall(page_size));
+        }
+        // A region is erased in whole sectors
+        if S % sector_size != 0 {
+            return Err(ErrorCode::BufferTooSmall(sector_size));
+        }
+        let partition_size = match regions.checked_mul(S) {
+            Some(size) if regions != 0 && regions <= MAX_REGIONS && size <= capacity => size,
+            _ => return Err(ErrorCode::FlashFull),
+        };
+        let base_addr = capacity - partition_size;
+        // Lock everything below the partition, the bootloader included
+        flash
+            .protect_lower(base_addr / 1024)
+            .map_err(|_| ErrorCode::WriteFail)?;
+        log::trace!(
+            "[tickv] SpiNorFlashController base address=0x{:X} regions={}",
+            base_addr,
+            regions,
+        );
+        Ok(SpiNorFlashController {
+            flash: RefCell::new(flash),
+            scratchpad: RefCell::new(scratchpad),
+            base_addr,
+            regions,
+            erases_since_boot: RefCell::new([0; MAX_REGIONS]),
+            write_pointers: RefCell::new([None; MAX_REGIONS]),
+        })
+    }
+
+    pub fn base_addr(&self) -> usize {
+        self.base_addr
+    }
+
+    pub fn regions(&self) -> usize {
+        self.regions
+    }
+
+    /// Size of the whole partition, the flash size TicKV is given
+    pub fn partition_size_bytes(&self) -> usize {
+        self.regions * S
+    }
+
+    /// Erases of each region since the controller was created, they
+    /// aren't kept across boots
+    pub fn erases_since_boot(&self) -> Ref<'_, [u32]> {
+        Ref::map(self.erases_since_boot.borrow(), |c| &c[..self.regions])
+    }
+
+    /// Bytes of region `region_number` up to the last one that isn't
+    /// erased, TicKV fills a region from the start. Read back through the
+    /// scratchpad the first time, then tracked through writes and erases
+    pub fn used_bytes(&self, region_number: usize) -> Result<usize, ErrorCode> {
+        let addr = self
+            .region_addr(region_number, 0)
+            .ok_or(ErrorCode::ReadFail)?;
+        if let Some(used) = self.write_pointers.borrow()[region_number] {
+            return Ok(used);
+        }
+        let mut flash = self.flash().map_err(|_| ErrorCode::ReadFail)?;
+        let mut scratchpad = self.scratchpad.borrow_mut();
+        let mut used = 0;
+        let mut offset = 0;
+        while offset < S {
+            let len = (S - offset).min(scratchpad.len());
+            let chunk = &mut scratchpad[..len];
+            flash
+                .read(addr + offset as u32, chunk)
+                .map_err(|_| ErrorCode::ReadFail)?;
+            if let Some(i) = chunk.iter().rposition(|&b| b != 0xFF) {
+                used = offset + i + 1;
+            }
+            offset += chunk.len();
+        }
+        self.write_pointers.borrow_mut()[region_number] = Some(used);
+        Ok(used)
+    }
+
+    /// Put the flash in deep power-down, the next access wakes it
+    pub fn power_down(&self) -> Result<(), F::Error> {
+        self.flash.borrow_mut().power_down()
+    }
+
+    /// The flash, without waking it
+    pub fn inner(&self) -> Ref<'_, F> {
+        self.flash.borrow()
+    }
+
+    /// Borrow the flash, waking it from deep power-down
+    fn flash(&self) -> Result<RefMut<'_, F>, F::Error> {
+        let mut flash = self.flash.borrow_mut();
+        if flash.is_powered_down() {
+            flash.release_power_down()?;
+        }
+        Ok(flash)
+    }
+
+    /// Move the write pointers of the regions `len` bytes from `address`
+    /// span past the write, or drop them to be read again if it failed
+    fn track_write(&self, address: usize, len: usize, written: bool) {
+        if len == 0 {
+            return;
+        }
+        let end = address + len;
+        let mut pointers = self.write_pointers.borrow_mut();
+        for region in address / S..=(end - 1) / S {
+            let pointer = &mut pointers[region];
+            *pointer = match *pointer {
+                Some(used) if written => Some(used.max(end.min((region + 1) * S) - region * S)),
+                _ => None,
+            };
+        }
+    }
+
+    /// Flash address of `offset` bytes into region `region_number`
+    fn region_addr(&self, region_number: usize, offset: usize) -> Option<u32> {
+        if region_number < self.regions && offset < S {
+            Some((self.base_addr + region_number * S + offset) as u32)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, F, const S: usize> FlashController<S> for SpiNorFlashController<'a, F, S>
+where
+    F: NorFlash,
+{
+    fn read_region(
+        &self,
+        region_number: usize,
+        offset: usize,
+        buf: &mut [u8; S],
+    ) -> Result<(), ErrorCode> {
+        log::trace!(
+            "[tickv] read region number={} offset=0x{:X}",
+            region_number,
+            offset
+        );
+        let addr = self
+            .region_addr(region_number, offset)
+            .ok_or(ErrorCode::ReadFail)?;
+        let mut flash = self.flash().map_err(|_| ErrorCode::ReadFail)?;
+        flash.read(addr, buf).map_err(|_| ErrorCode::ReadFail)
+    }
+
+    fn write(&self, address: usize, buf: &[u8]) -> Result<(), ErrorCode> {
+        log::trace!("[tickv] write address=0x{:X} len={}", address, buf.len());
+        if address + buf.len() > self.partition_size_bytes() {
+            return Err(ErrorCode::WriteFail);
+        }
+        let mut flash = self.flash().map_err(|_| ErrorCode::WriteFail)?;
+        let mut scratchpad = self.scratchpad.borrow_mut();
+        let addr = (self.base_addr + address) as u32;
+        let result = flash
+            .write(addr, buf)
+            .and_then(|()| flash.verify(addr, buf, &mut scratchpad[..]))
+            .map_err(|_| ErrorCode::WriteFail);
+        self.track_write(address, buf.len(), result.is_ok());
+        result
+    }
+
+    fn erase_region(&self, region_number: usize) -> Result<(), ErrorCode> {
+        log::trace!("[tickv] erase region number={}", region_number);
+        let addr = self
+            .region_addr(region_number, 0)
+            .ok_or(ErrorCode::EraseFail)?;
+        let mut flash = self.flash().map_err(|_| ErrorCode::EraseFail)?;
+        let result = flash.erase(addr, S).map_err(|_| ErrorCode::EraseFail);
+        // A failed erase may have been cut short
+        self.write_pointers.borrow_mut()[region_number] = result.ok().map(|()| 0);
+        result?;
+        self.erases_since_boot.borrow_mut()[region_number] += 1;
+        Ok(())
+    }
+}
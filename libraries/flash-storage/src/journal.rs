@@ -0,0 +1,427 @@
+//! Transactions
+//!
+//! One caller at a time, identified by its badge, has a transaction open
+//! and its changes are staged in the journal buffer. `commit`
+//! appends the whole journal as one TicKV object, which is the point the
+//! transaction takes effect, then applies the changes and invalidates it.
+//! Until that's done the journal is pending, a journal found at boot or
+//! left by a commit which failed part way is applied again by `recover`.
+//! Every change sets the key's final state so applying one twice is
+//! harmless.
+
+use crate::key_index::KeyIndex;
+use crate::values::{self, ValueBuffer};
+use crate::{ErrorCode, Key, Value, MAX_KEYS, MAX_KEY_SIZE, MAX_VALUE_SIZE};
+use core::hash::{Hash, Hasher};
+use core::str;
+use siphasher::sip::SipHasher;
+use tickv::{FlashController, TicKV};
+
+/// The journal object is hashed with this tag, apart from the `[u8]` keys
+/// clients use
+const JOURNAL_KEY_TAG: &str = "journal";
+
+/// The journal starts with its length, a little endian u16, followed by
+/// the changes, each a kind, the key's length and bytes and for sets the
+/// value's little endian u16 length and bytes
+const HEADER_SIZE: usize = 2;
+const KIND_SET: u8 = 1;
+const KIND_INVALIDATE: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// TicKV failed
+    Storage(ErrorCode),
+    /// `begin` while a transaction is open
+    TransactionOpen,
+    /// `commit` or `abort` without an open transaction
+    NoTransaction,
+    /// The staged changes don't fit in the journal
+    TransactionFull,
+}
+
+impl From<ErrorCode> for Error {
+    fn from(e: ErrorCode) -> Self {
+        Error::Storage(e)
+    }
+}
+
+enum Change<'b> {
+    Set(&'b [u8], &'b [u8]),
+    Invalidate(&'b [u8]),
+}
+
+impl<'b> Change<'b> {
+    fn key(&self) -> &'b [u8] {
+        match self {
+            Change::Set(k, _) | Change::Invalidate(k) => k,
+        }
+    }
+}
+
+pub struct Journal<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    changes: usize,
+    /// Badge of the caller with the transaction open
+    owner: Option<usize>,
+    /// Whether storage may hold a journal which hasn't been applied in
+    /// full, there's none open while there is
+    pending: bool,
+}
+
+impl<'a> Journal<'a> {
+    /// A journal of up to `buf.len()` bytes, which must fit in a TicKV
+    /// object
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        assert!(buf.len() > HEADER_SIZE && buf.len() <= usize::from(u16::MAX));
+        Journal {
+            buf,
+            len: HEADER_SIZE,
+            changes: 0,
+            owner: None,
+            // Until storage has been checked for one
+            pending: true,
+        }
+    }
+
+    /// Whether the caller with `badge` has the transaction open
+    pub fn is_open_by(&self, badge: usize) -> bool {
+        self.owner == Some(badge)
+    }
+
+    /// Badge of the caller with the transaction open
+    pub fn owner(&self) -> Option<usize> {
+        self.owner
+    }
+
+    /// Whether a committed journal has yet to be applied, see `recover`
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Open a transaction for the caller with `badge`, first finishing any
+    /// pending journal
+    pub fn begin<C: FlashController<S>, const S: usize>(
+        &mut self,
+        badge: usize,
+        tickv: &TicKV<C, S>,
+        key_index: &mut KeyIndex,
+        value_buffer: &mut ValueBuffer,
+    ) -> Result<(), Error> {
+        if self.owner.is_some() {
+            return Err(Error::TransactionOpen);
+        }
+        self.recover(tickv, key_index, value_buffer)?;
+        self.owner = Some(badge);
+        Ok(())
+    }
+
+    pub fn stage_set(&mut self, key: &Key, value: &Value) -> Result<(), Error> {
+        let len = 1 + 1 + key.len() + 2 + value.len();
+        let at = self.reserve(len)?;
+        let buf = &mut self.buf[at..at + len];
+        buf[0] = KIND_SET;
+        buf[1] = key.len() as u8;
+        let (k, v) = buf[2..].split_at_mut(key.len());
+        k.copy_from_slice(key.as_bytes());
+        v[..2].copy_from_slice(&(value.len() as u16).to_le_bytes());
+        v[2..].copy_from_slice(value.as_bytes());
+        Ok(())
+    }
+
+    pub fn stage_invalidate(&mut self, key: &Key) -> Result<(), Error> {
+        let len = 1 + 1 + key.len();
+        let at = self.reserve(len)?;
+        let buf = &mut self.buf[at..at + len];
+        buf[0] = KIND_INVALIDATE;
+        buf[1] = key.len() as u8;
+        buf[2..].copy_from_slice(key.as_bytes());
+        Ok(())
+    }
+
+    /// Whether `key` exists once the staged changes are applied, `None` if
+    /// none of them touch it
+    pub fn staged_exists(&self, key: &Key) -> Option<bool> {
+        changes(&self.buf[HEADER_SIZE..self.len])
+            .filter_map(Result::ok)
+            .filter(|c| c.key() == key.as_bytes())
+            .last()
+            .map(|c| matches!(c, Change::Set(..)))
+    }
+
+    /// Each key the staged changes touch, once, with its value once they're
+    /// applied, `None` if it's invalidated
+    pub fn staged(&self) -> impl Iterator<Item = (Key, Option<&[u8]>)> {
+        let buf = &self.buf[HEADER_SIZE..self.len];
+        changes(buf)
+            .filter_map(Result::ok)
+            .enumerate()
+            // Only the last change of a key counts
+            .filter(move |(i, c)| {
+                changes(buf)
+                    .filter_map(Result::ok)
+                    .skip(i + 1)
+                    .all(|later| later.key() != c.key())
+            })
+            .filter_map(|(_, c)| {
+                let value = match c {
+                    Change::Set(_, value) => Some(value),
+                    Change::Invalidate(_) => None,
+                };
+                key_of(c.key()).ok().map(|key| (key, value))
+            })
+    }
+
+    /// Fails with `ErrorCode::FlashFull` if applying the staged changes and
+    /// then a set of `key` would, at any point, index more than `MAX_KEYS`
+    /// keys
+    pub fn check_room(&self, key_index: &KeyIndex, key: &Key) -> Result<(), ErrorCode> {
+        let staged = changes(&self.buf[HEADER_SIZE..self.len])
+            .filter_map(Result::ok)
+            .map(|c| (c.key(), matches!(c, Change::Set(..))));
+        let mut indexed = key_index.len();
+        for (n, (k, set)) in staged.chain(Some((key.as_bytes(), true))).enumerate() {
+            match (self.staged_exists_after(key_index, n, k), set) {
+                (false, true) => indexed += 1,
+                (true, false) => indexed -= 1,
+                _ => (),
+            }
+            if indexed > MAX_KEYS {
+                return Err(ErrorCode::FlashFull);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `key` is indexed once the first `n` staged changes are
+    /// applied
+    fn staged_exists_after(&self, key_index: &KeyIndex, n: usize, key: &[u8]) -> bool {
+        let last = changes(&self.buf[HEADER_SIZE..self.len])
+            .take(n)
+            .filter_map(Result::ok)
+            .filter(|c| c.key() == key)
+            .last();
+        match last {
+            Some(c) => matches!(c, Change::Set(..)),
+            None => matches!(key_of(key), Ok(k) if key_index.contains(&k)),
+        }
+    }
+
+    /// Drop the staged changes, returns how many there were
+    pub fn abort(&mut self, badge: usize) -> Result<usize, Error> {
+        if !self.is_open_by(badge) {
+            return Err(Error::NoTransaction);
+        }
+        let changes = self.changes;
+        self.clear();
+        Ok(changes)
+    }
+
+    /// Apply the staged changes and close the transaction, returns how many
+    /// changes there were. If the journal can't be appended the transaction
+    /// stays open, to commit again or abort. Once it has been the
+    /// transaction is closed, if applying it fails part way the journal is
+    /// left pending for `recover` to finish
+    pub fn commit<C: FlashController<S>, const S: usize>(
+        &mut self,
+        badge: usize,
+        tickv: &TicKV<C, S>,
+        key_index: &mut KeyIndex,
+        value_buffer: &mut ValueBuffer,
+    ) -> Result<usize, Error> {
+        if !self.is_open_by(badge) {
+            return Err(Error::NoTransaction);
+        }
+        if self.changes == 0 {
+            self.clear();
+            return Ok(0);
+        }
+        let len = self.len;
+        self.buf[..HEADER_SIZE].copy_from_slice(&(len as u16).to_le_bytes());
+        tickv.append_key(journal_key(), &self.buf[..len])?;
+        self.owner = None;
+        self.pending = true;
+        self.recover(tickv, key_index, value_buffer)
+    }
+
+    /// Apply a pending journal, from a commit which failed part way or one
+    /// found at boot, returns the number of changes applied. The journal
+    /// stays pending until this succeeds, one which can't be read back is
+    /// invalidated and dropped
+    pub fn recover<C: FlashController<S>, const S: usize>(
+        &mut self,
+        tickv: &TicKV<C, S>,
+        key_index: &mut KeyIndex,
+        value_buffer: &mut ValueBuffer,
+    ) -> Result<usize, Error> {
+        if !self.pending {
+            return Ok(0);
+        }
+        let changes = match self.read(tickv)? {
+            Some(len) => {
+                let changes = apply(tickv, key_index, value_buffer, &self.buf[HEADER_SIZE..len])?;
+                tickv.invalidate_key(journal_key())?;
+                changes
+            }
+            None => 0,
+        };
+        self.pending = false;
+        self.clear();
+        Ok(changes)
+    }
+
+    /// Size of the journal in storage, zero if there's none. A pending
+    /// journal is read back into the buffer, as by `recover`, and dropped
+    /// if it can't be
+    pub fn stored_size<C: FlashController<S>, const S: usize>(
+        &mut self,
+        tickv: &TicKV<C, S>,
+    ) -> Result<usize, ErrorCode> {
+        if !self.pending {
+            return Ok(0);
+        }
+        Ok(self.read(tickv)?.unwrap_or(0))
+    }
+
+    /// Read the journal in storage into the buffer, returns its length or
+    /// `None` if there's none to apply
+    fn read<C: FlashController<S>, const S: usize>(
+        &mut self,
+        tickv: &TicKV<C, S>,
+    ) -> Result<Option<usize>, ErrorCode> {
+        self.buf.fill(0);
+        let read = match tickv.get_key(journal_key(), self.buf) {
+            Ok(_) => Ok(()),
+            Err(ErrorCode::KeyNotFound) => return Ok(None),
+            Err(e @ (ErrorCode::CorruptData | ErrorCode::BufferTooSmall(_))) => Err(e),
+            Err(e) => return Err(e),
+        };
+        let len = usize::from(u16::from_le_bytes([self.buf[0], self.buf[1]]));
+        let valid = (HEADER_SIZE..=self.buf.len()).contains(&len)
+            && changes(&self.buf[HEADER_SIZE..len]).all(|c| c.is_ok());
+        if read.is_ok() && valid {
+            return Ok(Some(len));
+        }
+        log::error!("[flash-storage] Dropping an unreadable journal {:?}", read);
+        if let Err(e) = tickv.invalidate_key(journal_key()) {
+            log::warn!("[flash-storage] Failed to invalidate the journal {:?}", e);
+        }
+        Ok(None)
+    }
+
+    fn clear(&mut self) {
+        self.len = HEADER_SIZE;
+        self.changes = 0;
+        self.owner = None;
+    }
+
+    /// Room for a change of `len` bytes, returns where it goes
+    fn reserve(&mut self, len: usize) -> Result<usize, Error> {
+        if self.owner.is_none() {
+            return Err(Error::NoTransaction);
+        }
+        if self.len + len > self.buf.len() {
+            return Err(Error::TransactionFull);
+        }
+        let at = self.len;
+        self.len += len;
+        self.changes += 1;
+        Ok(at)
+    }
+}
+
+fn journal_key() -> u64 {
+    let mut hasher = SipHasher::new();
+    JOURNAL_KEY_TAG.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Apply the encoded changes in order, returns how many there were
+fn apply<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key_index: &mut KeyIndex,
+    value_buffer: &mut ValueBuffer,
+    buf: &[u8],
+) -> Result<usize, Error> {
+    let mut applied = 0;
+    for change in changes(buf) {
+        let change = change?;
+        let key = key_of(change.key())?;
+        match change {
+            Change::Set(_, value) => {
+                let value = Value::from_bytes(value).ok_or(ErrorCode::CorruptData)?;
+                let value_slot = key_index.value_slot(&key);
+                let (_, slot) = values::set(tickv, &key, value_slot, &value, value_buffer)?;
+                key_index.record(tickv, &key, slot);
+            }
+            Change::Invalidate(_) => {
+                match values::invalidate(tickv, &key, key_index.value_slot(&key)) {
+                    Ok(_) | Err(ErrorCode::KeyNotFound) => (),
+                    Err(e) => return Err(e.into()),
+                }
+                key_index.forget(tickv, &key);
+            }
+        }
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+fn key_of(bytes: &[u8]) -> Result<Key, ErrorCode> {
+    let mut key = Key::new();
+    str::from_utf8(bytes)
+        .ok()
+        .and_then(|k| key.push_str(k).ok())
+        .ok_or(ErrorCode::CorruptData)?;
+    Ok(key)
+}
+
+/// Decode the changes in `buf`
+fn changes(mut buf: &[u8]) -> impl Iterator<Item = Result<Change<'_>, ErrorCode>> {
+    core::iter::from_fn(move || {
+        if buf.is_empty() {
+            return None;
+        }
+        let change = decode(buf);
+        match change {
+            Ok((change, rest)) => {
+                buf = rest;
+                Some(Ok(change))
+            }
+            Err(e) => {
+                buf = &[];
+                Some(Err(e))
+            }
+        }
+    })
+}
+
+fn decode(buf: &[u8]) -> Result<(Change<'_>, &[u8]), ErrorCode> {
+    let (&kind, rest) = buf.split_first().ok_or(ErrorCode::CorruptData)?;
+    let (&key_len, rest) = rest.split_first().ok_or(ErrorCode::CorruptData)?;
+    let key_len = usize::from(key_len);
+    if rest.len() < key_len {
+        return Err(ErrorCode::CorruptData);
+    }
+    let (key, rest) = rest.split_at(key_len);
+    if key_len > MAX_KEY_SIZE || str::from_utf8(key).is_err() {
+        return Err(ErrorCode::CorruptData);
+    }
+    match kind {
+        KIND_SET => {
+            if rest.len() < 2 {
+                return Err(ErrorCode::CorruptData);
+            }
+            let (value_len, rest) = rest.split_at(2);
+            let value_len = usize::from(u16::from_le_bytes([value_len[0], value_len[1]]));
+            if rest.len() < value_len || value_len > MAX_VALUE_SIZE {
+                return Err(ErrorCode::CorruptData);
+            }
+            let (value, rest) = rest.split_at(value_len);
+            Ok((Change::Set(key, value), rest))
+        }
+        KIND_INVALIDATE => Ok((Change::Invalidate(key), rest)),
+        _ => Err(ErrorCode::CorruptData),
+    }
+}
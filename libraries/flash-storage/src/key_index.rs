@@ -0,0 +1,217 @@
+//! Names of the stored keys
+//!
+//! TicKV only keeps the hashes of keys, so each key's name is also stored
+//! in an index slot object of its own, appended and invalidated along with
+//! the key. The slots are read back into memory at boot, along with which
+//! value slot holds each key's value.
+
+use crate::values::{self, Slot, ValueBuffer};
+use crate::{ErrorCode, Key, MAX_KEYS, MAX_KEY_SIZE};
+use core::hash::{Hash, Hasher};
+use siphasher::sip::SipHasher;
+use tickv::{FlashController, TicKV};
+
+/// Slot keys are hashed with this tag, apart from the `[u8]` keys clients
+/// use
+const SLOT_KEY_TAG: &str = "key-index";
+
+/// A slot holds the key's length, a byte, followed by its bytes
+pub const SLOT_HEADER_SIZE: usize = 1;
+type SlotBuffer = [u8; SLOT_HEADER_SIZE + MAX_KEY_SIZE];
+
+struct Entry {
+    key: Key,
+    /// The value slot holding the key's value, `None` if it's unknown
+    value_slot: Option<Slot>,
+}
+
+pub struct KeyIndex {
+    slots: [Option<Entry>; MAX_KEYS],
+}
+
+impl KeyIndex {
+    /// Read the slots from storage, a slot which can't be read is left
+    /// empty. The value of each key is resolved, finishing any replacement
+    /// cut short by a power cut
+    pub fn load<C: FlashController<S>, const S: usize>(
+        tickv: &TicKV<C, S>,
+        value_buffer: &mut ValueBuffer,
+    ) -> Self {
+        const EMPTY: Option<Entry> = None;
+        let mut index = KeyIndex {
+            slots: [EMPTY; MAX_KEYS],
+        };
+        let mut buf: SlotBuffer = [0; SLOT_HEADER_SIZE + MAX_KEY_SIZE];
+        for (slot, entry) in index.slots.iter_mut().enumerate() {
+            buf.fill(0);
+            match tickv.get_key(slot_key(slot), &mut buf) {
+                Ok(_) => {
+                    *entry = decode(&buf).map(|key| Entry {
+                        value_slot: resolve(tickv, &key, value_buffer),
+                        key,
+                    });
+                    if entry.is_none() {
+                        log::warn!("[flash-storage] Corrupt key index slot {}", slot);
+                    }
+                }
+                Err(ErrorCode::KeyNotFound) => (),
+                Err(e) => {
+                    log::warn!(
+                        "[flash-storage] Failed to read key index slot {} {:?}",
+                        slot,
+                        e
+                    );
+                }
+            }
+        }
+        log::debug!("[flash-storage] Indexed {} keys", index.len());
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, key: &Key) -> bool {
+        self.position(key).is_some()
+    }
+
+    /// The value slot holding `key`'s value, `None` if it isn't indexed or
+    /// its value couldn't be resolved
+    pub fn value_slot(&self, key: &Key) -> Option<Slot> {
+        self.position(key)
+            .and_then(|slot| self.slots[slot].as_ref())
+            .and_then(|e| e.value_slot)
+    }
+
+    /// Fails with `ErrorCode::FlashFull` if `key` is new and there's no
+    /// free slot for it
+    pub fn check_room(&self, key: &Key) -> Result<(), ErrorCode> {
+        if self.contains(key) || self.slots.iter().any(Option::is_none) {
+            Ok(())
+        } else {
+            Err(ErrorCode::FlashFull)
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Key> {
+        self.slots.iter().flatten().map(|e| &e.key)
+    }
+
+    /// Record `key`, whose value has just been written to `value_slot`
+    pub fn insert<C: FlashController<S>, const S: usize>(
+        &mut self,
+        tickv: &TicKV<C, S>,
+        key: &Key,
+        value_slot: Slot,
+    ) -> Result<(), ErrorCode> {
+        if let Some(slot) = self.position(key) {
+            if let Some(entry) = &mut self.slots[slot] {
+                entry.value_slot = Some(value_slot);
+            }
+            return Ok(());
+        }
+        let slot = self
+            .slots
+            .iter()
+            .position(Option::is_none)
+            .ok_or(ErrorCode::FlashFull)?;
+        let mut buf: SlotBuffer = [0; SLOT_HEADER_SIZE + MAX_KEY_SIZE];
+        let len = SLOT_HEADER_SIZE + key.len();
+        buf[0] = key.len() as u8;
+        buf[SLOT_HEADER_SIZE..len].copy_from_slice(key.as_bytes());
+        tickv.append_key(slot_key(slot), &buf[..len])?;
+        self.slots[slot] = Some(Entry {
+            key: key.clone(),
+            value_slot: Some(value_slot),
+        });
+        Ok(())
+    }
+
+    /// Forget `key`, which has just been invalidated
+    pub fn remove<C: FlashController<S>, const S: usize>(
+        &mut self,
+        tickv: &TicKV<C, S>,
+        key: &Key,
+    ) -> Result<(), ErrorCode> {
+        if let Some(slot) = self.position(key) {
+            tickv.invalidate_key(slot_key(slot))?;
+            self.slots[slot] = None;
+        }
+        Ok(())
+    }
+
+    /// Record a key which has just been stored, it's still readable if this
+    /// fails but won't be listed
+    pub fn record<C: FlashController<S>, const S: usize>(
+        &mut self,
+        tickv: &TicKV<C, S>,
+        key: &Key,
+        value_slot: Slot,
+    ) {
+        if let Err(e) = self.insert(tickv, key, value_slot) {
+            log::warn!("[flash-storage] Failed to index key {} {:?}", key, e);
+        }
+    }
+
+    /// Forget a key which has just been invalidated, it stays listed if
+    /// this fails
+    pub fn forget<C: FlashController<S>, const S: usize>(
+        &mut self,
+        tickv: &TicKV<C, S>,
+        key: &Key,
+    ) {
+        if let Err(e) = self.remove(tickv, key) {
+            log::warn!("[flash-storage] Failed to unindex key {} {:?}", key, e);
+        }
+    }
+
+    /// The keys from slot `cursor` on, with their slots
+    pub fn iter_from(&self, cursor: usize) -> impl Iterator<Item = (usize, &Key)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .skip(cursor)
+            .filter_map(|(slot, entry)| entry.as_ref().map(|e| (slot, &e.key)))
+    }
+
+    fn position(&self, key: &Key) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|e| e.as_ref().map(|e| &e.key) == Some(key))
+    }
+}
+
+fn slot_key(slot: usize) -> u64 {
+    let mut hasher = SipHasher::new();
+    (SLOT_KEY_TAG, slot as u32).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn decode(buf: &SlotBuffer) -> Option<Key> {
+    let len = usize::from(buf[0]);
+    let name = core::str::from_utf8(buf.get(SLOT_HEADER_SIZE..SLOT_HEADER_SIZE + len)?).ok()?;
+    let mut key = Key::new();
+    key.push_str(name).ok()?;
+    Some(key)
+}
+
+/// The value slot of an indexed key, finishing a replacement of its value
+/// cut short by a power cut
+fn resolve<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key: &Key,
+    buf: &mut ValueBuffer,
+) -> Option<Slot> {
+    match values::recover(tickv, key, buf) {
+        Ok(slot) => slot,
+        Err(e) => {
+            log::warn!("[flash-storage] Failed to recover key {} {:?}", key, e);
+            None
+        }
+    }
+}
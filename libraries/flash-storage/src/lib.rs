@@ -0,0 +1,108 @@
+//! TicKV storage on NOR flash
+//!
+//! `SpiNorFlashController` runs TicKV on any `NorFlash`, the SPI NOR driver
+//! on target or the file-backed `sim::FileFlash` on the host. The key-value
+//! store on top of TicKV is split into `values`, the two slot values that
+//! survive a power cut, `key_index`, the names of the stored keys, and
+//! `journal`, transactions. `usage` accounts the space they take.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use crate::controller::SpiNorFlashController;
+pub use crate::journal::Journal;
+pub use crate::key_index::KeyIndex;
+pub use nor_flash::NorFlash;
+pub use tickv::{success_codes::SuccessCode, ErrorCode};
+
+use core::{fmt, str};
+use heapless::{String, Vec};
+
+mod controller;
+pub mod journal;
+pub mod key_index;
+#[cfg(feature = "std")]
+pub mod sim;
+pub mod usage;
+pub mod values;
+
+/// Most regions a `SpiNorFlashController` partition can have
+pub const MAX_REGIONS: usize = 32;
+
+pub const MAX_KEY_SIZE: usize = 32;
+pub type Key = String<MAX_KEY_SIZE>;
+
+pub const MAX_VALUE_SIZE: usize = 256;
+
+/// Most keys the store indexes, keys appended once it's full fail with
+/// `ErrorCode::FlashFull`
+pub const MAX_KEYS: usize = 64;
+
+/// Up to `MAX_VALUE_SIZE` bytes, of any value, read back with the length
+/// they were stored with
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Value(Vec<u8, MAX_VALUE_SIZE>);
+
+impl Value {
+    pub fn new() -> Self {
+        Value(Vec::new())
+    }
+
+    /// `None` if `bytes` is longer than `MAX_VALUE_SIZE`
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Vec::from_slice(bytes).ok().map(Value)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The value as a string, if it's UTF-8
+    pub fn as_str(&self) -> Result<&str, str::Utf8Error> {
+        str::from_utf8(&self.0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<&str> for Value {
+    /// # Panics
+    /// If `s` is longer than `MAX_VALUE_SIZE`, as for `Key`
+    fn from(s: &str) -> Self {
+        Value::from_bytes(s.as_bytes()).expect("Value too large")
+    }
+}
+
+/// UTF-8 values as a string, others as hex bytes
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.as_str() {
+            Ok(s) => f.write_str(s),
+            Err(_) => fmt::UpperHex::fmt(self, f),
+        }
+    }
+}
+
+/// The bytes as hex
+impl fmt::UpperHex for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.as_bytes() {
+            write!(f, "{:02X}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of a `values::set`
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SetCode {
+    /// The key didn't exist
+    Inserted,
+    /// The key's value was replaced
+    Updated,
+}
@@ -0,0 +1,256 @@
+//! File-backed NOR flash for host-side tests
+//!
+//! The contents are a raw image of the whole part, the same `flash.bin`
+//! format QEMU uses for the SPI NOR, see `scripts/mkflash.sh`.
+
+use crate::NorFlash;
+use std::cell::Cell;
+use std::{fs, io, path::Path};
+
+/// Geometry of the sabrelite's SST25VF016B
+pub const SST25VF016B_CAPACITY_BYTES: usize = 2 * 1024 * 1024;
+pub const SST25VF016B_SECTOR_SIZE_BYTES: usize = 4096;
+pub const SST25VF016B_PAGE_SIZE_BYTES: usize = 256;
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Error {
+    /// The address range extends past the end of the flash.
+    OutOfBounds,
+
+    /// An erase range doesn't start and end on sector boundaries.
+    Misaligned,
+
+    /// The data read back doesn't match the data written.
+    VerifyFailed,
+
+    /// The address range overlaps the protected area, see `protect_lower`.
+    Protected,
+
+    /// The part is in deep power-down, see `release_power_down`.
+    PoweredDown,
+
+    /// Power was cut, see `cut_power_after`.
+    PowerCut,
+}
+
+/// Programming ANDs the data into the contents, only an erase sets bits
+/// again, erases are counted per sector
+#[derive(Clone)]
+pub struct FileFlash {
+    data: Vec<u8>,
+    page_size_bytes: usize,
+    sector_size_bytes: usize,
+    erase_counts: Vec<u32>,
+    protected_bytes: usize,
+    powered_down: bool,
+    /// Writes and erases left before the power is cut, the supply isn't
+    /// the part's so it's switched through a shared reference
+    ops_left: Cell<Option<usize>>,
+    /// Whether a write or erase failed for lack of power
+    power_cut: Cell<bool>,
+}
+
+impl FileFlash {
+    /// An erased part
+    ///
+    /// # Panics
+    /// If `capacity_bytes` isn't a multiple of `sector_size_bytes`, or
+    /// `sector_size_bytes` of `page_size_bytes`
+    pub fn new(capacity_bytes: usize, sector_size_bytes: usize, page_size_bytes: usize) -> Self {
+        Self::from_bytes(
+            vec![0xFF; capacity_bytes],
+            sector_size_bytes,
+            page_size_bytes,
+        )
+    }
+
+    /// An erased SST25VF016B
+    pub fn sst25vf016b() -> Self {
+        Self::new(
+            SST25VF016B_CAPACITY_BYTES,
+            SST25VF016B_SECTOR_SIZE_BYTES,
+            SST25VF016B_PAGE_SIZE_BYTES,
+        )
+    }
+
+    /// A part with the contents of a flash image, the capacity is the file
+    /// size
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        sector_size_bytes: usize,
+        page_size_bytes: usize,
+    ) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        if data.is_empty() || data.len() % sector_size_bytes != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "flash image size isn't a multiple of the sector size",
+            ));
+        }
+        Ok(Self::from_bytes(data, sector_size_bytes, page_size_bytes))
+    }
+
+    /// Write the contents out as a flash image, erase counts aren't saved
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, &self.data)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Number of times the sector containing `addr` was erased
+    pub fn erase_count(&self, addr: u32) -> u32 {
+        self.erase_counts[addr as usize / self.sector_size_bytes]
+    }
+
+    /// Per sector erase counts, from the bottom of the part
+    pub fn erase_counts(&self) -> &[u32] {
+        &self.erase_counts
+    }
+
+    pub fn protected_bytes(&self) -> usize {
+        self.protected_bytes
+    }
+
+    /// Let `ops` more writes and erases through, every one after them
+    /// fails with `Error::PowerCut` and leaves the contents alone, until
+    /// `restore_power`
+    pub fn cut_power_after(&self, ops: usize) {
+        self.ops_left.set(Some(ops));
+    }
+
+    pub fn restore_power(&self) {
+        self.ops_left.set(None);
+        self.power_cut.set(false);
+    }
+
+    /// Whether a write or erase has failed since power was cut
+    pub fn power_was_cut(&self) -> bool {
+        self.power_cut.get()
+    }
+
+    fn from_bytes(data: Vec<u8>, sector_size_bytes: usize, page_size_bytes: usize) -> Self {
+        assert!(page_size_bytes != 0 && sector_size_bytes % page_size_bytes == 0);
+        assert!(data.len() % sector_size_bytes == 0);
+        let sectors = data.len() / sector_size_bytes;
+        FileFlash {
+            data,
+            page_size_bytes,
+            sector_size_bytes,
+            erase_counts: vec![0; sectors],
+            protected_bytes: 0,
+            powered_down: false,
+            ops_left: Cell::new(None),
+            power_cut: Cell::new(false),
+        }
+    }
+
+    fn check_bounds(&self, addr: u32, len: usize) -> Result<(), Error> {
+        if self.powered_down {
+            return Err(Error::PoweredDown);
+        }
+        match (addr as usize).checked_add(len) {
+            Some(end) if end <= self.data.len() => Ok(()),
+            _ => Err(Error::OutOfBounds),
+        }
+    }
+
+    fn check_writable(&self, addr: u32, len: usize) -> Result<(), Error> {
+        self.check_bounds(addr, len)?;
+        if len != 0 && (addr as usize) < self.protected_bytes {
+            return Err(Error::Protected);
+        }
+        match self.ops_left.get() {
+            Some(0) => {
+                self.power_cut.set(true);
+                Err(Error::PowerCut)
+            }
+            Some(ops) => {
+                self.ops_left.set(Some(ops - 1));
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl NorFlash for FileFlash {
+    type Error = Error;
+
+    fn capacity_bytes(&self) -> usize {
+        self.data.len()
+    }
+
+    fn page_size_bytes(&self) -> usize {
+        self.page_size_bytes
+    }
+
+    fn sector_size_bytes(&self) -> usize {
+        self.sector_size_bytes
+    }
+
+    fn read(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), Error> {
+        self.check_bounds(addr, buf.len())?;
+        let start = addr as usize;
+        buf.copy_from_slice(&self.data[start..start + buf.len()]);
+        Ok(())
+    }
+
+    fn write(&mut self, addr: u32, data: &[u8]) -> Result<(), Error> {
+        self.check_writable(addr, data.len())?;
+        let start = addr as usize;
+        for (cell, byte) in self.data[start..start + data.len()].iter_mut().zip(data) {
+            *cell &= byte;
+        }
+        Ok(())
+    }
+
+    fn erase(&mut self, addr: u32, len: usize) -> Result<(), Error> {
+        self.check_writable(addr, len)?;
+        let start = addr as usize;
+        if start % self.sector_size_bytes != 0 || len % self.sector_size_bytes != 0 {
+            return Err(Error::Misaligned);
+        }
+        self.data[start..start + len].fill(0xFF);
+        let first = start / self.sector_size_bytes;
+        let sectors = len / self.sector_size_bytes;
+        for count in &mut self.erase_counts[first..first + sectors] {
+            *count += 1;
+        }
+        Ok(())
+    }
+
+    fn verify(&mut self, addr: u32, data: &[u8], _buf: &mut [u8]) -> Result<(), Error> {
+        self.check_bounds(addr, data.len())?;
+        let start = addr as usize;
+        if &self.data[start..start + data.len()] == data {
+            Ok(())
+        } else {
+            Err(Error::VerifyFailed)
+        }
+    }
+
+    fn protect_lower(&mut self, kib: usize) -> Result<(), Error> {
+        let size_bytes = kib * 1024;
+        if size_bytes > self.data.len() {
+            return Err(Error::OutOfBounds);
+        }
+        self.protected_bytes = size_bytes;
+        Ok(())
+    }
+
+    fn power_down(&mut self) -> Result<(), Error> {
+        self.powered_down = true;
+        Ok(())
+    }
+
+    fn release_power_down(&mut self) -> Result<(), Error> {
+        self.powered_down = false;
+        Ok(())
+    }
+
+    fn is_powered_down(&self) -> bool {
+        self.powered_down
+    }
+}
@@ -0,0 +1,57 @@
+//! Space taken by the store
+//!
+//! TicKV doesn't report what it holds, so the valid objects are accounted
+//! from what the store keeps in it: the main key, the key index slots,
+//! every valid value slot and a journal which hasn't been applied. Each
+//! takes `OBJECT_OVERHEAD_BYTES` besides its value.
+
+use crate::journal::Journal;
+use crate::key_index::{self, KeyIndex};
+use crate::values::{self, ValueBuffer};
+use crate::ErrorCode;
+use tickv::{FlashController, TicKV};
+
+/// Size of a TicKV object besides its value, the header with the version,
+/// length and hashed key, and the checksum. The layout of the pinned TicKV,
+/// the `usage` host test fails if it changes
+pub const OBJECT_OVERHEAD_BYTES: usize = 1 + 2 + 8 + 4;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// Taken by valid objects
+    pub live_bytes: usize,
+    /// Number of valid objects
+    pub objects: usize,
+}
+
+impl Usage {
+    fn add(&mut self, value_size: usize) {
+        self.live_bytes += OBJECT_OVERHEAD_BYTES + value_size;
+        self.objects += 1;
+    }
+}
+
+/// The valid objects in `tickv`. Keys whose values can't be read only
+/// count their index slot
+pub fn live<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key_index: &KeyIndex,
+    journal: &mut Journal,
+    buf: &mut ValueBuffer,
+) -> Result<Usage, ErrorCode> {
+    let mut usage = Usage::default();
+    // The main key, appended without a value
+    usage.add(0);
+    for key in key_index.keys() {
+        usage.add(key_index::SLOT_HEADER_SIZE + key.len());
+        match values::stored_sizes(tickv, key, buf) {
+            Ok(sizes) => sizes.iter().flatten().for_each(|&size| usage.add(size)),
+            Err(e) => log::warn!("[flash-storage] Failed to read key {} {:?}", key, e),
+        }
+    }
+    match journal.stored_size(tickv)? {
+        0 => (),
+        size => usage.add(size),
+    }
+    Ok(usage)
+}
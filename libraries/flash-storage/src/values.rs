@@ -0,0 +1,280 @@
+//! Values as stored in TicKV
+//!
+//! Each key has two slots, its primary hash and an alternate one. `set`
+//! writes the new value to the free slot before invalidating the old one,
+//! so a value is never lost to a power cut. If both slots are found valid,
+//! the generation in the header says which value is newer.
+//!
+//! Once a key's slots are resolved only one of them is valid, the key
+//! index records which, see `KeyIndex::value_slot`. Given that slot as
+//! `value_slot` only it is looked up, with `None` both are.
+
+use crate::{ErrorCode, Key, SetCode, SuccessCode, Value, MAX_VALUE_SIZE};
+use core::hash::{Hash, Hasher};
+use siphasher::sip::SipHasher;
+use tickv::{FlashController, TicKV};
+
+/// Version of the stored layout, the value header, slots and the key
+/// index and journal objects. Bump it on any change, the main key is hashed
+/// with it so a partition of another version is erased at boot
+pub const FORMAT_VERSION: u8 = 2;
+
+/// Values are stored behind a little endian u16 header, TicKV doesn't
+/// report the length of what it reads. The low bits are the length, the
+/// high bits the generation
+pub const VALUE_HEADER_SIZE: usize = 2;
+const LEN_BITS: u16 = 9;
+const LEN_MASK: u16 = (1 << LEN_BITS) - 1;
+const GENERATION_MASK: u8 = 0x7F;
+
+/// Alternate slot keys are hashed with this tag, apart from the `[u8]`
+/// primary ones
+const ALTERNATE_SLOT_TAG: &str = "alternate";
+
+pub type ValueBuffer = [u8; VALUE_HEADER_SIZE + MAX_VALUE_SIZE];
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Slot {
+    Primary,
+    Alternate,
+}
+
+impl Slot {
+    fn hash(self, key: &Key) -> u64 {
+        match self {
+            Slot::Primary => get_hashed_key(key.as_bytes()),
+            Slot::Alternate => {
+                let mut hasher = SipHasher::new();
+                (key.as_bytes(), ALTERNATE_SLOT_TAG).hash(&mut hasher);
+                hasher.finish()
+            }
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            Slot::Primary => Slot::Alternate,
+            Slot::Alternate => Slot::Primary,
+        }
+    }
+}
+
+/// A valid copy of a key's value
+struct Stored {
+    slot: Slot,
+    generation: u8,
+    value: Value,
+}
+
+/// Fails with `ErrorCode::KeyAlreadyExists` if either slot is in use, the
+/// value goes in `Slot::Primary`
+pub fn append<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key: &Key,
+    value_slot: Option<Slot>,
+    value: &Value,
+    buf: &mut ValueBuffer,
+) -> Result<SuccessCode, ErrorCode> {
+    if value_slot.is_some() || read_slot(tickv, key, Slot::Alternate, buf)?.is_some() {
+        return Err(ErrorCode::KeyAlreadyExists);
+    }
+    write_slot(tickv, key, Slot::Primary, 0, value, buf)
+}
+
+/// Insert `key` or replace its value, the old value stays valid until the
+/// new one is written. Returns the slot the value went in
+pub fn set<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key: &Key,
+    value_slot: Option<Slot>,
+    value: &Value,
+    buf: &mut ValueBuffer,
+) -> Result<(SetCode, Slot), ErrorCode> {
+    let old = match value_slot {
+        Some(slot) => match read_slot(tickv, key, slot, buf)? {
+            Some(stored) => Some(stored),
+            None => resolve(tickv, key, buf)?,
+        },
+        None => resolve(tickv, key, buf)?,
+    };
+    match old {
+        None => {
+            write_slot(tickv, key, Slot::Primary, 0, value, buf)?;
+            Ok((SetCode::Inserted, Slot::Primary))
+        }
+        Some(old) => {
+            let generation = old.generation.wrapping_add(1) & GENERATION_MASK;
+            let slot = old.slot.other();
+            write_slot(tickv, key, slot, generation, value, buf)?;
+            tickv.invalidate_key(old.slot.hash(key))?;
+            Ok((SetCode::Updated, slot))
+        }
+    }
+}
+
+/// The newest value of `key`
+pub fn get<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key: &Key,
+    value_slot: Option<Slot>,
+    buf: &mut ValueBuffer,
+) -> Result<Value, ErrorCode> {
+    if let Some(slot) = value_slot {
+        if let Some(stored) = read_slot(tickv, key, slot, buf)? {
+            return Ok(stored.value);
+        }
+    }
+    match read_slots(tickv, key, buf)? {
+        (Some(p), Some(a)) => Ok(newer(p, a).0.value),
+        (Some(s), None) | (None, Some(s)) => Ok(s.value),
+        (None, None) => Err(ErrorCode::KeyNotFound),
+    }
+}
+
+/// Invalidate the value, fails with `ErrorCode::KeyNotFound` if neither
+/// slot is in use
+pub fn invalidate<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key: &Key,
+    value_slot: Option<Slot>,
+) -> Result<SuccessCode, ErrorCode> {
+    if let Some(slot) = value_slot {
+        if let Some(sc) = invalidate_slot(tickv, key, slot)? {
+            return Ok(sc);
+        }
+    }
+    let primary = invalidate_slot(tickv, key, Slot::Primary)?;
+    let alternate = invalidate_slot(tickv, key, Slot::Alternate)?;
+    primary.or(alternate).ok_or(ErrorCode::KeyNotFound)
+}
+
+/// Finish a `set` cut short between writing the new value and
+/// invalidating the old one, returns the slot left holding the value
+pub fn recover<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key: &Key,
+    buf: &mut ValueBuffer,
+) -> Result<Option<Slot>, ErrorCode> {
+    resolve(tickv, key, buf).map(|stored| stored.map(|s| s.slot))
+}
+
+/// Stored size of each valid copy of `key`'s value, its header included.
+/// Both are only valid while a `set` hasn't finished
+pub fn stored_sizes<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key: &Key,
+    buf: &mut ValueBuffer,
+) -> Result<[Option<usize>; 2], ErrorCode> {
+    let (primary, alternate) = read_slots(tickv, key, buf)?;
+    let size = |stored: Option<Stored>| stored.map(|s| VALUE_HEADER_SIZE + s.value.len());
+    Ok([size(primary), size(alternate)])
+}
+
+fn get_hashed_key(unhashed_key: &[u8]) -> u64 {
+    let mut hash_function = SipHasher::new();
+    unhashed_key.hash(&mut hash_function);
+    hash_function.finish()
+}
+
+/// The current value of `key`, invalidating the older one if both slots
+/// are valid
+fn resolve<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key: &Key,
+    buf: &mut ValueBuffer,
+) -> Result<Option<Stored>, ErrorCode> {
+    match read_slots(tickv, key, buf)? {
+        (Some(p), Some(a)) => drop_stale(tickv, key, p, a).map(Some),
+        (stored, None) | (None, stored) => Ok(stored),
+    }
+}
+
+/// Invalidate the older of two valid copies, returns the newer
+fn drop_stale<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key: &Key,
+    a: Stored,
+    b: Stored,
+) -> Result<Stored, ErrorCode> {
+    let (new, old) = newer(a, b);
+    log::warn!(
+        "[flash-storage] Dropping the stale {:?} value of key {}",
+        old.slot,
+        key
+    );
+    tickv.invalidate_key(old.slot.hash(key))?;
+    Ok(new)
+}
+
+/// The newer of two copies and the older one, generations wrap so newer
+/// means less than half the range ahead
+fn newer(a: Stored, b: Stored) -> (Stored, Stored) {
+    let ahead = a.generation.wrapping_sub(b.generation) & GENERATION_MASK;
+    if ahead != 0 && ahead <= GENERATION_MASK / 2 {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+fn read_slots<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key: &Key,
+    buf: &mut ValueBuffer,
+) -> Result<(Option<Stored>, Option<Stored>), ErrorCode> {
+    let primary = read_slot(tickv, key, Slot::Primary, buf)?;
+    let alternate = read_slot(tickv, key, Slot::Alternate, buf)?;
+    Ok((primary, alternate))
+}
+
+fn read_slot<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key: &Key,
+    slot: Slot,
+    buf: &mut ValueBuffer,
+) -> Result<Option<Stored>, ErrorCode> {
+    buf.fill(0);
+    match tickv.get_key(slot.hash(key), buf) {
+        Ok(_) => (),
+        Err(ErrorCode::KeyNotFound) => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let header = u16::from_le_bytes([buf[0], buf[1]]);
+    let len = usize::from(header & LEN_MASK);
+    let value = buf
+        .get(VALUE_HEADER_SIZE..VALUE_HEADER_SIZE + len)
+        .and_then(Value::from_bytes)
+        .ok_or(ErrorCode::CorruptData)?;
+    Ok(Some(Stored {
+        slot,
+        generation: (header >> LEN_BITS) as u8,
+        value,
+    }))
+}
+
+fn write_slot<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key: &Key,
+    slot: Slot,
+    generation: u8,
+    value: &Value,
+    buf: &mut ValueBuffer,
+) -> Result<SuccessCode, ErrorCode> {
+    let len = value.len();
+    let header = len as u16 | (u16::from(generation) << LEN_BITS);
+    buf[..VALUE_HEADER_SIZE].copy_from_slice(&header.to_le_bytes());
+    buf[VALUE_HEADER_SIZE..VALUE_HEADER_SIZE + len].copy_from_slice(value.as_bytes());
+    tickv.append_key(slot.hash(key), &buf[..VALUE_HEADER_SIZE + len])
+}
+
+fn invalidate_slot<C: FlashController<S>, const S: usize>(
+    tickv: &TicKV<C, S>,
+    key: &Key,
+    slot: Slot,
+) -> Result<Option<SuccessCode>, ErrorCode> {
+    match tickv.invalidate_key(slot.hash(key)) {
+        Ok(sc) => Ok(Some(sc)),
+        Err(ErrorCode::KeyNotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
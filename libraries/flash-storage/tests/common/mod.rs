@@ -0,0 +1,105 @@
+//! Fixtures shared by the host tests, each of which uses only some of them
+
+#![allow(dead_code)]
+
+use flash_storage::sim::FileFlash;
+use flash_storage::values::{self, ValueBuffer, VALUE_HEADER_SIZE};
+use flash_storage::{
+    Journal, Key, KeyIndex, SetCode, SpiNorFlashController, Value, MAX_VALUE_SIZE,
+};
+use tickv::{ErrorCode, TicKV};
+
+pub const REGION_SIZE_BYTES: usize = 4096;
+pub const REGIONS: usize = 4;
+pub const PARTITION_SIZE_BYTES: usize = REGIONS * REGION_SIZE_BYTES;
+pub const JOURNAL_SIZE_BYTES: usize = REGION_SIZE_BYTES / 2;
+
+pub const MAIN_KEY: u64 = 0x7C0F_FEE0_0000_0001;
+pub const BADGE: usize = 1;
+
+pub type Controller<'a> = SpiNorFlashController<'a, FileFlash, REGION_SIZE_BYTES>;
+
+/// A controller for the last `regions` regions of `flash`
+pub fn controller(flash: FileFlash, scratchpad: &mut [u8], regions: usize) -> Controller<'_> {
+    SpiNorFlashController::new(flash, scratchpad, regions).unwrap()
+}
+
+/// TicKV on the last `regions` regions of `flash`, initialised
+pub fn mount<'a>(
+    flash: FileFlash,
+    scratchpad: &'a mut [u8],
+    read_buf: &'a mut [u8; REGION_SIZE_BYTES],
+    regions: usize,
+) -> TicKV<'a, Controller<'a>, REGION_SIZE_BYTES> {
+    let tickv = TicKV::<Controller, REGION_SIZE_BYTES>::new(
+        controller(flash, scratchpad, regions),
+        read_buf,
+        regions * REGION_SIZE_BYTES,
+    );
+    tickv.initalise(MAIN_KEY).unwrap();
+    tickv
+}
+
+/// Boot the store on `flash` as the driver does, loading the key index and
+/// finishing any transaction, then run `f` on it. Returns the flash as `f`
+/// left it
+pub fn boot<R>(
+    flash: FileFlash,
+    f: impl FnOnce(
+        &TicKV<Controller, REGION_SIZE_BYTES>,
+        &mut KeyIndex,
+        &mut Journal,
+        &mut ValueBuffer,
+    ) -> R,
+) -> (FileFlash, R) {
+    boot_with_journal(flash, JOURNAL_SIZE_BYTES, f)
+}
+
+pub fn boot_with_journal<R>(
+    flash: FileFlash,
+    journal_size_bytes: usize,
+    f: impl FnOnce(
+        &TicKV<Controller, REGION_SIZE_BYTES>,
+        &mut KeyIndex,
+        &mut Journal,
+        &mut ValueBuffer,
+    ) -> R,
+) -> (FileFlash, R) {
+    let mut scratchpad = [0; 256];
+    let mut read_buf = [0; REGION_SIZE_BYTES];
+    let tickv = mount(flash, &mut scratchpad, &mut read_buf, REGIONS);
+    let mut value_buffer = [0; VALUE_HEADER_SIZE + MAX_VALUE_SIZE];
+    let mut key_index = KeyIndex::load(&tickv, &mut value_buffer);
+    let mut journal_buffer = [0; JOURNAL_SIZE_BYTES];
+    let mut journal = Journal::new(&mut journal_buffer[..journal_size_bytes]);
+    journal
+        .recover(&tickv, &mut key_index, &mut value_buffer)
+        .unwrap();
+    let r = f(&tickv, &mut key_index, &mut journal, &mut value_buffer);
+    let flash = tickv.controller.inner().clone();
+    (flash, r)
+}
+
+pub fn set(
+    tickv: &TicKV<Controller, REGION_SIZE_BYTES>,
+    key_index: &mut KeyIndex,
+    buf: &mut ValueBuffer,
+    key: &str,
+    value: &str,
+) -> Result<SetCode, ErrorCode> {
+    let key = Key::from(key);
+    let value_slot = key_index.value_slot(&key);
+    let (sc, slot) = values::set(tickv, &key, value_slot, &Value::from(value), buf)?;
+    key_index.insert(tickv, &key, slot)?;
+    Ok(sc)
+}
+
+pub fn get(
+    tickv: &TicKV<Controller, REGION_SIZE_BYTES>,
+    key_index: &KeyIndex,
+    buf: &mut ValueBuffer,
+    key: &str,
+) -> Result<Value, ErrorCode> {
+    let key = Key::from(key);
+    values::get(tickv, &key, key_index.value_slot(&key), buf)
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use common::{controller, Controller, REGIONS, REGION_SIZE_BYTES};
+use flash_storage::sim::FileFlash;
+use flash_storage::MAX_REGIONS;
+use tickv::{ErrorCode, FlashController};
+
+const CAPACITY: usize = 64 * 1024;
+const BASE: usize = CAPACITY - REGIONS * REGION_SIZE_BYTES;
+
+fn flash() -> FileFlash {
+    FileFlash::new(CAPACITY, 4096, 256)
+}
+
+#[test]
+fn partition_at_the_end() {
+    let mut scratchpad = [0; 256];
+    let c = controller(flash(), &mut scratchpad, REGIONS);
+    assert_eq!(c.base_addr(), BASE);
+    assert_eq!(c.regions(), REGIONS);
+    assert_eq!(c.inner().protected_bytes(), BASE);
+}
+
+#[test]
+fn rejects_bad_partitions() {
+    let mut scratchpad = [0; 256];
+    assert!(Controller::new(flash(), &mut scratchpad, 0).is_err());
+    assert!(Controller::new(flash(), &mut scratchpad, CAPACITY / REGION_SIZE_BYTES + 1).is_err());
+    assert!(Controller::new(flash(), &mut scratchpad[..16], REGIONS).is_err());
+    let big_flash = FileFlash::new(1024 * 1024, 4096, 256);
+    assert!(Controller::new(big_flash, &mut scratchpad, MAX_REGIONS + 1).is_err());
+    // Regions must be whole sectors
+    let big_sectors = FileFlash::new(CAPACITY, 8192, 256);
+    assert!(Controller::new(big_sectors, &mut scratchpad, REGIONS).is_err());
+}
+
+#[test]
+fn every_region_is_addressed() {
+    let mut scratchpad = [0; 256];
+    let c = controller(flash(), &mut scratchpad, REGIONS);
+    for region in 0..REGIONS {
+        let tag = [region as u8 + 1; 16];
+        c.write(region * REGION_SIZE_BYTES + 32, &tag).unwrap();
+    }
+
+    let mut buf = [0; REGION_SIZE_BYTES];
+    for region in 0..REGIONS {
+        c.read_region(region, 0, &mut buf).unwrap();
+        assert_eq!(buf[32..48], [region as u8 + 1; 16]);
+        assert!(buf[..32].iter().chain(&buf[48..]).all(|&b| b == 0xFF));
+
+        let start = BASE + region * REGION_SIZE_BYTES;
+        assert_eq!(
+            c.inner().as_bytes()[start + 32..start + 48],
+            [region as u8 + 1; 16]
+        );
+    }
+}
+
+#[test]
+fn erases_only_its_region() {
+    let mut scratchpad = [0; 256];
+    let c = controller(flash(), &mut scratchpad, REGIONS);
+    for region in 0..REGIONS {
+        c.write(region * REGION_SIZE_BYTES, &[0; 8]).unwrap();
+    }
+
+    c.erase_region(2).unwrap();
+    let mut buf = [0; REGION_SIZE_BYTES];
+    for region in 0..REGIONS {
+        c.read_region(region, 0, &mut buf).unwrap();
+        let erased = region == 2;
+        assert_eq!(buf[..8] == [0xFF; 8], erased);
+        let addr = (BASE + region * REGION_SIZE_BYTES) as u32;
+        assert_eq!(c.inner().erase_count(addr), erased as u32);
+        assert_eq!(c.erases_since_boot()[region], erased as u32);
+    }
+}
+
+#[test]
+fn used_bytes_ends_at_the_last_write() {
+    let mut scratchpad = [0; 256];
+    let c = controller(flash(), &mut scratchpad, REGIONS);
+    assert_eq!(c.used_bytes(0), Ok(0));
+
+    c.write(0, &[0x12; 40]).unwrap();
+    assert_eq!(c.used_bytes(0), Ok(40));
+    // Spans several scratchpad reads
+    c.write(REGION_SIZE_BYTES + 1000, &[0; 8]).unwrap();
+    assert_eq!(c.used_bytes(1), Ok(1008));
+    c.write(3 * REGION_SIZE_BYTES - 1, &[0]).unwrap();
+    assert_eq!(c.used_bytes(2), Ok(REGION_SIZE_BYTES));
+    assert_eq!(c.used_bytes(3), Ok(0));
+    assert_eq!(c.used_bytes(REGIONS), Err(ErrorCode::ReadFail));
+
+    c.erase_region(1).unwrap();
+    assert_eq!(c.used_bytes(1), Ok(0));
+}
+
+#[test]
+fn used_bytes_is_read_again_after_a_failed_write() {
+    let mut scratchpad = [0; 256];
+    let c = controller(flash(), &mut scratchpad, REGIONS);
+    c.write(0, &[0x12; 40]).unwrap();
+    assert_eq!(c.used_bytes(0), Ok(40));
+    // Invalidating an object doesn't move it back
+    c.write(4, &[0; 2]).unwrap();
+    assert_eq!(c.used_bytes(0), Ok(40));
+
+    c.inner().cut_power_after(0);
+    assert_eq!(c.write(100, &[0; 8]), Err(ErrorCode::WriteFail));
+    c.inner().restore_power();
+    assert_eq!(c.used_bytes(0), Ok(40));
+
+    // Across two regions
+    assert_eq!(c.used_bytes(2), Ok(0));
+    c.write(2 * REGION_SIZE_BYTES - 4, &[0; 8]).unwrap();
+    assert_eq!(c.used_bytes(1), Ok(REGION_SIZE_BYTES));
+    assert_eq!(c.used_bytes(2), Ok(4));
+}
+
+#[test]
+fn rejects_out_of_partition_access() {
+    let mut scratchpad = [0; 256];
+    let c = controller(flash(), &mut scratchpad, REGIONS);
+    let mut buf = [0; REGION_SIZE_BYTES];
+    assert_eq!(
+        c.read_region(REGIONS, 0, &mut buf),
+        Err(ErrorCode::ReadFail)
+    );
+    assert_eq!(c.erase_region(REGIONS), Err(ErrorCode::EraseFail));
+    assert_eq!(
+        c.write(REGIONS * REGION_SIZE_BYTES - 4, &[0; 8]),
+        Err(ErrorCode::WriteFail)
+    );
+    assert!(c.inner().erase_counts().iter().all(|&n| n == 0));
+    assert!(c.erases_since_boot().iter().all(|&n| n == 0));
+}
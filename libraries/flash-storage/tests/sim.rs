@@ -0,0 +1,94 @@
+use flash_storage::sim::{Error, FileFlash};
+use flash_storage::NorFlash;
+use std::{env, fs, process};
+
+const CAPACITY: usize = 64 * 1024;
+const SECTOR: usize = 4096;
+const PAGE: usize = 256;
+
+fn flash() -> FileFlash {
+    FileFlash::new(CAPACITY, SECTOR, PAGE)
+}
+
+#[test]
+fn starts_erased() {
+    let mut f = flash();
+    let mut buf = [0; 16];
+    f.read(0, &mut buf).unwrap();
+    assert_eq!(buf, [0xFF; 16]);
+    assert!(f.erase_counts().iter().all(|&c| c == 0));
+}
+
+#[test]
+fn programming_only_clears_bits() {
+    let mut f = flash();
+    f.write(0x10, &[0xF0, 0x0F]).unwrap();
+    f.write(0x10, &[0x3C, 0xFF]).unwrap();
+    let mut buf = [0; 2];
+    f.read(0x10, &mut buf).unwrap();
+    assert_eq!(buf, [0x30, 0x0F]);
+    assert_eq!(
+        f.verify(0x10, &[0x3C, 0xFF], &mut [0; 2]),
+        Err(Error::VerifyFailed)
+    );
+}
+
+#[test]
+fn erase_sets_sector_and_counts() {
+    let mut f = flash();
+    f.write(SECTOR as u32 - 1, &[0, 0]).unwrap();
+    f.erase(SECTOR as u32, SECTOR).unwrap();
+    let mut buf = [0; 2];
+    f.read(SECTOR as u32 - 1, &mut buf).unwrap();
+    assert_eq!(buf, [0x00, 0xFF]);
+    assert_eq!(f.erase_count(0), 0);
+    assert_eq!(f.erase_count(SECTOR as u32), 1);
+
+    f.erase(0, 2 * SECTOR).unwrap();
+    assert_eq!(&f.erase_counts()[..3], &[1, 2, 0]);
+}
+
+#[test]
+fn rejects_bad_ranges() {
+    let mut f = flash();
+    assert_eq!(f.erase(1, SECTOR), Err(Error::Misaligned));
+    assert_eq!(f.erase(0, SECTOR + 1), Err(Error::Misaligned));
+    assert_eq!(
+        f.read(CAPACITY as u32 - 1, &mut [0; 2]),
+        Err(Error::OutOfBounds)
+    );
+    assert_eq!(f.write(CAPACITY as u32, &[0]), Err(Error::OutOfBounds));
+}
+
+#[test]
+fn protect_lower() {
+    let mut f = flash();
+    f.protect_lower(8).unwrap();
+    assert_eq!(f.write(0x1FFF, &[0]), Err(Error::Protected));
+    assert_eq!(f.erase(SECTOR as u32, SECTOR), Err(Error::Protected));
+    f.write(0x2000, &[0]).unwrap();
+    f.erase(0x2000, SECTOR).unwrap();
+}
+
+#[test]
+fn power_down() {
+    let mut f = flash();
+    f.power_down().unwrap();
+    assert_eq!(f.read(0, &mut [0]), Err(Error::PoweredDown));
+    f.release_power_down().unwrap();
+    f.read(0, &mut [0]).unwrap();
+}
+
+#[test]
+fn save_load_round_trip() {
+    let path = env::temp_dir().join(format!("flash-storage-sim-{}.bin", process::id()));
+    let mut f = flash();
+    f.write(0x1234, b"persistent").unwrap();
+    f.save(&path).unwrap();
+
+    let mut loaded = FileFlash::load(&path, SECTOR, PAGE).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.capacity_bytes(), CAPACITY);
+    assert_eq!(loaded.as_bytes(), f.as_bytes());
+    loaded.verify(0x1234, b"persistent", &mut [0; 4]).unwrap();
+}
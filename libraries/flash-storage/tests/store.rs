@@ -0,0 +1,278 @@
+mod common;
+
+use common::{boot, boot_with_journal, get, set, Controller, BADGE, REGION_SIZE_BYTES};
+use flash_storage::sim::FileFlash;
+use flash_storage::values::{self, Slot, ValueBuffer};
+use flash_storage::{Journal, Key, KeyIndex, SetCode, Value, MAX_KEYS};
+use tickv::{ErrorCode, TicKV};
+
+/// Run `f` on a store on `flash` with power cut after every number of
+/// writes and erases in turn, until `f` gets through. Each time the store
+/// is booted again on what was left and `check`ed, told whether `f` was
+/// cut short
+fn cut_power_during<F, C>(flash: &FileFlash, f: F, check: C)
+where
+    F: Fn(&TicKV<Controller, REGION_SIZE_BYTES>, &mut KeyIndex, &mut Journal, &mut ValueBuffer),
+    C: Fn(&TicKV<Controller, REGION_SIZE_BYTES>, &mut KeyIndex, &mut ValueBuffer, bool),
+{
+    for ops in 0.. {
+        let flash = flash.clone();
+        flash.cut_power_after(ops);
+        let (flash, ()) = boot(flash, &f);
+        let cut = flash.power_was_cut();
+        flash.restore_power();
+        boot(flash, |tickv, key_index, _, buf| {
+            check(tickv, key_index, buf, cut)
+        });
+        if !cut {
+            break;
+        }
+    }
+}
+
+#[test]
+fn set_alternates_slots_and_the_index_records_the_live_one() {
+    let key = Key::from("net.ip");
+    let (flash, ()) = boot(FileFlash::sst25vf016b(), |tickv, key_index, _, buf| {
+        assert_eq!(
+            set(tickv, key_index, buf, "net.ip", "one"),
+            Ok(SetCode::Inserted)
+        );
+        assert_eq!(key_index.value_slot(&key), Some(Slot::Primary));
+        assert_eq!(
+            set(tickv, key_index, buf, "net.ip", "two"),
+            Ok(SetCode::Updated)
+        );
+        assert_eq!(key_index.value_slot(&key), Some(Slot::Alternate));
+    });
+    boot(flash, |tickv, key_index, _, buf| {
+        assert_eq!(key_index.value_slot(&key), Some(Slot::Alternate));
+        assert_eq!(get(tickv, key_index, buf, "net.ip"), Ok(Value::from("two")));
+        // Either slot in use counts
+        let value = Value::from("three");
+        assert_eq!(
+            values::append(tickv, &key, None, &value, buf),
+            Err(ErrorCode::KeyAlreadyExists)
+        );
+        assert_eq!(
+            values::append(tickv, &key, key_index.value_slot(&key), &value, buf),
+            Err(ErrorCode::KeyAlreadyExists)
+        );
+    });
+}
+
+#[test]
+fn stale_hints_fall_back_to_both_slots() {
+    let key = Key::from("net.ip");
+    boot(FileFlash::sst25vf016b(), |tickv, key_index, _, buf| {
+        set(tickv, key_index, buf, "net.ip", "one").unwrap();
+        set(tickv, key_index, buf, "net.ip", "two").unwrap();
+        let value = values::get(tickv, &key, Some(Slot::Primary), buf);
+        assert_eq!(value, Ok(Value::from("two")));
+        let (sc, slot) =
+            values::set(tickv, &key, Some(Slot::Primary), &Value::from("three"), buf).unwrap();
+        assert_eq!((sc, slot), (SetCode::Updated, Slot::Primary));
+        values::invalidate(tickv, &key, Some(Slot::Alternate)).unwrap();
+        assert_eq!(
+            values::get(tickv, &key, None, buf),
+            Err(ErrorCode::KeyNotFound)
+        );
+    });
+}
+
+#[test]
+fn set_keeps_the_old_or_the_new_value_across_a_power_cut() {
+    let (flash, ()) = boot(FileFlash::sst25vf016b(), |tickv, key_index, _, buf| {
+        set(tickv, key_index, buf, "net.ip", "old").unwrap();
+    });
+    cut_power_during(
+        &flash,
+        |tickv, key_index, _, buf| {
+            let _ = set(tickv, key_index, buf, "net.ip", "new");
+        },
+        |tickv, key_index, buf, cut| {
+            let value = get(tickv, key_index, buf, "net.ip").unwrap();
+            if cut {
+                assert!(value == Value::from("old") || value == Value::from("new"));
+            } else {
+                assert_eq!(value, Value::from("new"));
+            }
+            // Only one slot is left valid, a further set replaces it
+            set(tickv, key_index, buf, "net.ip", "newer").unwrap();
+            assert_eq!(
+                get(tickv, key_index, buf, "net.ip"),
+                Ok(Value::from("newer"))
+            );
+        },
+    );
+}
+
+#[test]
+fn commit_applies_all_or_none_of_a_transaction_across_a_power_cut() {
+    let (flash, ()) = boot(FileFlash::sst25vf016b(), |tickv, key_index, _, buf| {
+        for key in ["net.ip", "net.mask", "net.gw"] {
+            set(tickv, key_index, buf, key, "old").unwrap();
+        }
+    });
+    cut_power_during(
+        &flash,
+        |tickv, key_index, journal, buf| {
+            journal.begin(BADGE, tickv, key_index, buf).unwrap();
+            journal
+                .stage_set(&Key::from("net.ip"), &Value::from("new"))
+                .unwrap();
+            journal
+                .stage_set(&Key::from("net.mask"), &Value::from("new"))
+                .unwrap();
+            journal.stage_invalidate(&Key::from("net.gw")).unwrap();
+            let _ = journal.commit(BADGE, tickv, key_index, buf);
+        },
+        |tickv, key_index, buf, cut| {
+            let ip = get(tickv, key_index, buf, "net.ip").unwrap();
+            let mask = get(tickv, key_index, buf, "net.mask").unwrap();
+            let gw = get(tickv, key_index, buf, "net.gw");
+            let committed = ip == Value::from("new");
+            assert!(
+                committed || cut,
+                "the whole transaction is applied when not cut"
+            );
+            if committed {
+                assert_eq!(mask, Value::from("new"));
+                assert_eq!(gw, Err(ErrorCode::KeyNotFound));
+                assert!(!key_index.contains(&Key::from("net.gw")));
+            } else {
+                assert_eq!(ip, Value::from("old"));
+                assert_eq!(mask, Value::from("old"));
+                assert_eq!(gw, Ok(Value::from("old")));
+            }
+        },
+    );
+}
+
+#[test]
+fn abort_drops_the_staged_changes() {
+    boot(
+        FileFlash::sst25vf016b(),
+        |tickv, key_index, journal, buf| {
+            set(tickv, key_index, buf, "net.ip", "old").unwrap();
+            journal.begin(BADGE, tickv, key_index, buf).unwrap();
+            journal
+                .stage_set(&Key::from("net.ip"), &Value::from("new"))
+                .unwrap();
+            assert_eq!(journal.abort(BADGE), Ok(1));
+            assert_eq!(get(tickv, key_index, buf, "net.ip"), Ok(Value::from("old")));
+        },
+    );
+}
+
+#[test]
+fn staged_changes_count_towards_the_room_for_keys() {
+    boot(
+        FileFlash::sst25vf016b(),
+        |tickv, key_index, journal, buf| {
+            for n in 1..MAX_KEYS {
+                set(tickv, key_index, buf, &format!("net.{}", n), "old").unwrap();
+            }
+            journal.begin(BADGE, tickv, key_index, buf).unwrap();
+            let last = Key::from("net.last");
+            assert_eq!(journal.check_room(key_index, &last), Ok(()));
+            journal.stage_set(&last, &Value::from("new")).unwrap();
+            let extra = Key::from("net.extra");
+            assert_eq!(
+                journal.check_room(key_index, &extra),
+                Err(ErrorCode::FlashFull)
+            );
+            journal.stage_invalidate(&Key::from("net.1")).unwrap();
+            assert_eq!(journal.check_room(key_index, &extra), Ok(()));
+            let staged: Vec<_> = journal.staged().collect();
+            assert_eq!(
+                staged,
+                [(last, Some(&b"new"[..])), (Key::from("net.1"), None)]
+            );
+        },
+    );
+}
+
+/// Stage new values for every key but the last, which is invalidated
+fn stage_new_values(
+    tickv: &TicKV<Controller, REGION_SIZE_BYTES>,
+    key_index: &mut KeyIndex,
+    journal: &mut Journal,
+    buf: &mut ValueBuffer,
+) {
+    journal.begin(BADGE, tickv, key_index, buf).unwrap();
+    journal
+        .stage_set(&Key::from("net.ip"), &Value::from("new"))
+        .unwrap();
+    journal.stage_invalidate(&Key::from("net.gw")).unwrap();
+}
+
+fn store_old_values(flash: FileFlash) -> FileFlash {
+    let (flash, ()) = boot(flash, |tickv, key_index, _, buf| {
+        for key in ["net.ip", "net.gw"] {
+            set(tickv, key_index, buf, key, "old").unwrap();
+        }
+    });
+    flash
+}
+
+#[test]
+fn a_commit_which_fails_part_way_stays_pending_until_recovered() {
+    let flash = store_old_values(FileFlash::sst25vf016b());
+    boot(flash, |tickv, key_index, journal, buf| {
+        stage_new_values(tickv, key_index, journal, buf);
+        // Only the journal gets written
+        tickv.controller.inner().cut_power_after(1);
+        assert!(journal.commit(BADGE, tickv, key_index, buf).is_err());
+        assert!(journal.is_pending());
+        assert_eq!(journal.owner(), None);
+        assert!(journal.begin(BADGE + 1, tickv, key_index, buf).is_err());
+        assert!(journal.is_pending());
+
+        tickv.controller.inner().restore_power();
+        assert_eq!(journal.recover(tickv, key_index, buf), Ok(2));
+        assert!(!journal.is_pending());
+        assert_eq!(get(tickv, key_index, buf, "net.ip"), Ok(Value::from("new")));
+        assert_eq!(
+            get(tickv, key_index, buf, "net.gw"),
+            Err(ErrorCode::KeyNotFound)
+        );
+    });
+}
+
+#[test]
+fn a_commit_whose_journal_isnt_appended_stays_open() {
+    let flash = store_old_values(FileFlash::sst25vf016b());
+    boot(flash, |tickv, key_index, journal, buf| {
+        stage_new_values(tickv, key_index, journal, buf);
+        tickv.controller.inner().cut_power_after(0);
+        assert!(journal.commit(BADGE, tickv, key_index, buf).is_err());
+        assert!(!journal.is_pending());
+        assert!(journal.is_open_by(BADGE));
+
+        tickv.controller.inner().restore_power();
+        assert_eq!(journal.commit(BADGE, tickv, key_index, buf), Ok(2));
+        assert_eq!(get(tickv, key_index, buf, "net.ip"), Ok(Value::from("new")));
+    });
+}
+
+#[test]
+fn an_unreadable_journal_is_dropped() {
+    let flash = store_old_values(FileFlash::sst25vf016b());
+    let (flash, ()) = boot(flash, |tickv, key_index, journal, buf| {
+        stage_new_values(tickv, key_index, journal, buf);
+        tickv.controller.inner().cut_power_after(1);
+        assert!(journal.commit(BADGE, tickv, key_index, buf).is_err());
+    });
+    flash.restore_power();
+
+    // Too small a buffer to read it back in
+    let (flash, ()) = boot_with_journal(flash, 8, |tickv, key_index, journal, buf| {
+        assert!(!journal.is_pending());
+        assert_eq!(get(tickv, key_index, buf, "net.ip"), Ok(Value::from("old")));
+    });
+    boot(flash, |tickv, key_index, _, buf| {
+        assert_eq!(get(tickv, key_index, buf, "net.ip"), Ok(Value::from("old")));
+        assert_eq!(get(tickv, key_index, buf, "net.gw"), Ok(Value::from("old")));
+    });
+}
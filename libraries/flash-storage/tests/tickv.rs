@@ -0,0 +1,137 @@
+mod common;
+
+use common::{controller, mount, REGION_SIZE_BYTES};
+use flash_storage::sim::FileFlash;
+use flash_storage::NorFlash;
+use tickv::ErrorCode;
+
+const REGIONS: usize = 8;
+const PARTITION_SIZE_BYTES: usize = REGIONS * REGION_SIZE_BYTES;
+
+const KEY_ONE: u64 = 0x0000_0000_0000_0001;
+const KEY_TWO: u64 = 0x0000_0000_0000_0002;
+
+#[test]
+fn storage_is_the_last_partition() {
+    let mut scratchpad = [0; 256];
+    let c = controller(FileFlash::sst25vf016b(), &mut scratchpad, REGIONS);
+    assert_eq!(c.partition_size_bytes(), PARTITION_SIZE_BYTES);
+    assert_eq!(c.base_addr(), 2 * 1024 * 1024 - PARTITION_SIZE_BYTES);
+    assert_eq!(c.inner().protected_bytes(), c.base_addr());
+}
+
+#[test]
+fn append_get_invalidate_and_collect() {
+    let mut scratchpad = [0; 256];
+    let mut read_buf = [0; REGION_SIZE_BYTES];
+    let tickv = mount(
+        FileFlash::sst25vf016b(),
+        &mut scratchpad,
+        &mut read_buf,
+        REGIONS,
+    );
+
+    tickv.append_key(KEY_ONE, b"one").unwrap();
+    tickv.append_key(KEY_TWO, b"two").unwrap();
+    assert_eq!(
+        tickv.append_key(KEY_ONE, b"again"),
+        Err(ErrorCode::KeyAlreadyExists)
+    );
+
+    let mut value = [0; 8];
+    tickv.get_key(KEY_ONE, &mut value).unwrap();
+    assert_eq!(&value[..3], b"one");
+
+    tickv.invalidate_key(KEY_ONE).unwrap();
+    assert_eq!(
+        tickv.get_key(KEY_ONE, &mut value),
+        Err(ErrorCode::KeyNotFound)
+    );
+
+    // The region still holds live keys, so nothing is erased
+    let base = tickv.controller.base_addr() as u32;
+    let erases = tickv.controller.inner().erase_count(base);
+    tickv.garbage_collect().unwrap();
+    assert_eq!(tickv.controller.inner().erase_count(base), erases);
+
+    tickv.get_key(KEY_TWO, &mut value).unwrap();
+    assert_eq!(&value[..3], b"two");
+}
+
+#[test]
+fn holds_more_than_a_region() {
+    let mut scratchpad = [0; 256];
+    let mut read_buf = [0; REGION_SIZE_BYTES];
+    let tickv = mount(
+        FileFlash::sst25vf016b(),
+        &mut scratchpad,
+        &mut read_buf,
+        REGIONS,
+    );
+
+    // Twice what fits in one region
+    let keys = 2 * REGION_SIZE_BYTES / 200;
+    for k in 0..keys as u64 {
+        tickv.append_key(0x100 + k, &[k as u8; 200]).unwrap();
+    }
+    let mut value = [0; 200];
+    for k in 0..keys as u64 {
+        tickv.get_key(0x100 + k, &mut value).unwrap();
+        assert_eq!(value, [k as u8; 200]);
+    }
+
+    // Everything stays inside the partition
+    let flash = tickv.controller.inner();
+    let base = tickv.controller.base_addr();
+    assert!(flash.as_bytes()[..base].iter().all(|&b| b == 0xFF));
+    let used = (0..REGIONS)
+        .filter(|r| {
+            let start = base + r * REGION_SIZE_BYTES;
+            flash.as_bytes()[start..start + REGION_SIZE_BYTES]
+                .iter()
+                .any(|&b| b != 0xFF)
+        })
+        .count();
+    assert!(used > 1);
+}
+
+#[test]
+fn wakes_a_powered_down_flash() {
+    let mut scratchpad = [0; 256];
+    let mut read_buf = [0; REGION_SIZE_BYTES];
+    let tickv = mount(
+        FileFlash::sst25vf016b(),
+        &mut scratchpad,
+        &mut read_buf,
+        REGIONS,
+    );
+    tickv.controller.power_down().unwrap();
+    tickv.append_key(KEY_ONE, b"one").unwrap();
+    assert!(!tickv.controller.inner().is_powered_down());
+}
+
+#[test]
+fn survives_a_reload() {
+    let path = std::env::temp_dir().join(format!("flash-storage-tickv-{}.bin", std::process::id()));
+    {
+        let mut scratchpad = [0; 256];
+        let mut read_buf = [0; REGION_SIZE_BYTES];
+        let tickv = mount(
+            FileFlash::sst25vf016b(),
+            &mut scratchpad,
+            &mut read_buf,
+            REGIONS,
+        );
+        tickv.append_key(KEY_ONE, b"kept").unwrap();
+        tickv.controller.inner().save(&path).unwrap();
+    }
+
+    let flash = FileFlash::load(&path, 4096, 256).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let mut scratchpad = [0; 256];
+    let mut read_buf = [0; REGION_SIZE_BYTES];
+    let tickv = mount(flash, &mut scratchpad, &mut read_buf, REGIONS);
+    let mut value = [0; 4];
+    tickv.get_key(KEY_ONE, &mut value).unwrap();
+    assert_eq!(&value, b"kept");
+}
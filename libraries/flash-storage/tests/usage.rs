@@ -0,0 +1,107 @@
+mod common;
+
+use common::{boot, set, Controller, BADGE, REGIONS, REGION_SIZE_BYTES};
+use flash_storage::sim::FileFlash;
+use flash_storage::usage::{self, Usage, OBJECT_OVERHEAD_BYTES};
+use flash_storage::values::VALUE_HEADER_SIZE;
+use flash_storage::{Key, Value};
+use tickv::TicKV;
+
+fn used_bytes(tickv: &TicKV<Controller, REGION_SIZE_BYTES>) -> usize {
+    let flash = &tickv.controller;
+    (0..REGIONS).map(|r| flash.used_bytes(r).unwrap()).sum()
+}
+
+/// Size of the object holding a value of `len` bytes
+fn value_object(len: usize) -> usize {
+    OBJECT_OVERHEAD_BYTES + VALUE_HEADER_SIZE + len
+}
+
+#[test]
+fn object_overhead_matches_tickv() {
+    boot(
+        FileFlash::sst25vf016b(),
+        |tickv, key_index, journal, buf| {
+            // Just the main key
+            assert_eq!(used_bytes(tickv), OBJECT_OVERHEAD_BYTES);
+
+            set(tickv, key_index, buf, "net.ip", "10.0.0.1").unwrap();
+            let index_slot = OBJECT_OVERHEAD_BYTES + 1 + "net.ip".len();
+            assert_eq!(
+                used_bytes(tickv),
+                OBJECT_OVERHEAD_BYTES + index_slot + value_object("10.0.0.1".len())
+            );
+            // Nothing has been invalidated, everything written is live
+            let usage = usage::live(tickv, key_index, journal, buf).unwrap();
+            assert_eq!(
+                usage,
+                Usage {
+                    live_bytes: used_bytes(tickv),
+                    objects: 3,
+                }
+            );
+        },
+    );
+}
+
+#[test]
+fn replaced_values_are_reclaimable() {
+    boot(
+        FileFlash::sst25vf016b(),
+        |tickv, key_index, journal, buf| {
+            set(tickv, key_index, buf, "net.ip", "10.0.0.1").unwrap();
+            // The value moves to the alternate slot
+            set(tickv, key_index, buf, "net.ip", "10.0.0.2").unwrap();
+            let usage = usage::live(tickv, key_index, journal, buf).unwrap();
+            assert_eq!(usage.objects, 3);
+            assert_eq!(
+                used_bytes(tickv) - usage.live_bytes,
+                value_object("10.0.0.1".len())
+            );
+        },
+    );
+}
+
+#[test]
+fn both_slots_count_until_a_set_finishes() {
+    boot(
+        FileFlash::sst25vf016b(),
+        |tickv, key_index, journal, buf| {
+            set(tickv, key_index, buf, "net.ip", "10.0.0.1").unwrap();
+            // The new value is written, the old one isn't invalidated
+            tickv.controller.inner().cut_power_after(1);
+            assert!(set(tickv, key_index, buf, "net.ip", "10.0.0.2").is_err());
+            tickv.controller.inner().restore_power();
+            let usage = usage::live(tickv, key_index, journal, buf).unwrap();
+            assert_eq!(usage.objects, 4);
+            assert_eq!(usage.live_bytes, used_bytes(tickv));
+        },
+    );
+}
+
+#[test]
+fn a_pending_journal_counts_until_applied() {
+    boot(
+        FileFlash::sst25vf016b(),
+        |tickv, key_index, journal, buf| {
+            journal.begin(BADGE, tickv, key_index, buf).unwrap();
+            journal
+                .stage_set(&Key::from("net.ip"), &Value::from("10.0.0.1"))
+                .unwrap();
+            // The journal is appended, none of it is applied
+            tickv.controller.inner().cut_power_after(1);
+            assert!(journal.commit(BADGE, tickv, key_index, buf).is_err());
+            tickv.controller.inner().restore_power();
+            assert!(journal.is_pending());
+            let usage = usage::live(tickv, key_index, journal, buf).unwrap();
+            assert_eq!(usage.objects, 2);
+            assert_eq!(usage.live_bytes, used_bytes(tickv));
+            let journal_object = usage.live_bytes - OBJECT_OVERHEAD_BYTES;
+
+            assert_eq!(journal.recover(tickv, key_index, buf), Ok(1));
+            let usage = usage::live(tickv, key_index, journal, buf).unwrap();
+            assert_eq!(usage.objects, 3);
+            assert_eq!(used_bytes(tickv) - usage.live_bytes, journal_object);
+        },
+    );
+}
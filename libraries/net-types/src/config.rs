@@ -0,0 +1,88 @@
+use crate::{EthernetAddress, Ipv4Address, ParseAddressError};
+use core::fmt;
+
+/// Network interface configuration.
+///
+/// Each field can be overridden at runtime by a persistent storage key,
+/// the values are stored in their textual form.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct NetConfig {
+    /// `net.mac`, e.g. `02:00:00:00:00:01`
+    pub mac_addr: EthernetAddress,
+    /// `net.ip`, e.g. `192.0.2.80`
+    pub ip_addr: Ipv4Address,
+    /// `net.prefix`, e.g. `24`
+    pub prefix_len: u8,
+    /// `net.gateway`, e.g. `192.0.2.1`
+    pub gateway: Option<Ipv4Address>,
+}
+
+impl NetConfig {
+    pub const KEY_MAC: &'static str = "net.mac";
+    pub const KEY_IP: &'static str = "net.ip";
+    pub const KEY_PREFIX: &'static str = "net.prefix";
+    pub const KEY_GATEWAY: &'static str = "net.gateway";
+
+    pub const KEYS: [&'static str; 4] = [
+        Self::KEY_MAC,
+        Self::KEY_IP,
+        Self::KEY_PREFIX,
+        Self::KEY_GATEWAY,
+    ];
+
+    pub const DEFAULT_PREFIX_LEN: u8 = 24;
+
+    /// Configuration with the default prefix length and no gateway
+    pub fn new(mac_addr: EthernetAddress, ip_addr: Ipv4Address) -> Self {
+        NetConfig {
+            mac_addr,
+            ip_addr,
+            prefix_len: Self::DEFAULT_PREFIX_LEN,
+            gateway: None,
+        }
+    }
+
+    /// Override the field associated with `key` by parsing `value`
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), NetConfigError> {
+        match key {
+            Self::KEY_MAC => self.mac_addr = value.parse()?,
+            Self::KEY_IP => self.ip_addr = value.parse()?,
+            Self::KEY_PREFIX => {
+                self.prefix_len = match value.trim().parse() {
+                    Ok(len) if len <= 32 => len,
+                    _ => return Err(NetConfigError::InvalidPrefix),
+                }
+            }
+            Self::KEY_GATEWAY => self.gateway = Some(value.parse()?),
+            _ => return Err(NetConfigError::UnknownKey),
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for NetConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "MAC={} IP={}/{}",
+            self.mac_addr, self.ip_addr, self.prefix_len
+        )?;
+        if let Some(gw) = self.gateway {
+            write!(f, " GW={}", gw)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NetConfigError {
+    UnknownKey,
+    InvalidAddress,
+    InvalidPrefix,
+}
+
+impl From<ParseAddressError> for NetConfigError {
+    fn from(_: ParseAddressError) -> Self {
+        NetConfigError::InvalidAddress
+    }
+}
@@ -1,10 +1,13 @@
 #![no_std]
 
 use core::fmt;
+use core::str::FromStr;
 
+mod config;
 mod frame;
 mod udp_transmit_buffer;
 
+pub use crate::config::*;
 pub use crate::frame::*;
 pub use crate::udp_transmit_buffer::*;
 
@@ -55,6 +58,17 @@ impl fmt::Display for EthernetAddress {
     }
 }
 
+/// Parses the colon separated hex form, e.g. `02:00:00:00:00:01`
+impl FromStr for EthernetAddress {
+    type Err = ParseAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0; 6];
+        parse_octets(s, ':', 16, &mut bytes)?;
+        Ok(EthernetAddress(bytes))
+    }
+}
+
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
 pub struct Ipv4Address(pub [u8; 4]);
 
@@ -76,3 +90,43 @@ impl fmt::Display for Ipv4Address {
         write!(f, "{}.{}.{}.{}", bytes[0], bytes[1], bytes[2], bytes[3])
     }
 }
+
+/// Parses the dotted decimal form, e.g. `192.0.2.80`
+impl FromStr for Ipv4Address {
+    type Err = ParseAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0; 4];
+        parse_octets(s, '.', 10, &mut bytes)?;
+        Ok(Ipv4Address(bytes))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ParseAddressError;
+
+impl fmt::Display for ParseAddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid address syntax")
+    }
+}
+
+fn parse_octets(
+    s: &str,
+    separator: char,
+    radix: u32,
+    bytes: &mut [u8],
+) -> Result<(), ParseAddressError> {
+    let mut parts = s.trim().split(separator);
+    for b in bytes.iter_mut() {
+        let part = parts.next().ok_or(ParseAddressError)?;
+        if part.is_empty() || part.starts_with('+') {
+            return Err(ParseAddressError);
+        }
+        *b = u8::from_str_radix(part, radix).map_err(|_| ParseAddressError)?;
+    }
+    if parts.next().is_some() {
+        return Err(ParseAddressError);
+    }
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use net_types::{EthernetAddress, Ipv4Address, NetConfig, NetConfigError, ParseAddressError};
+
+fn config() -> NetConfig {
+    NetConfig::new(
+        EthernetAddress([0x00, 0xAD, 0xBE, 0xEF, 0xCA, 0xFE]),
+        Ipv4Address([192, 0, 2, 80]),
+    )
+}
+
+#[test]
+fn parses_mac_addresses() {
+    assert_eq!(
+        "02:00:00:00:00:01".parse(),
+        Ok(EthernetAddress([0x02, 0, 0, 0, 0, 0x01]))
+    );
+    assert_eq!(
+        " 00:ad:BE:ef:Ca:fE\n".parse(),
+        Ok(EthernetAddress([0x00, 0xAD, 0xBE, 0xEF, 0xCA, 0xFE]))
+    );
+}
+
+#[test]
+fn rejects_malformed_mac_addresses() {
+    for s in [
+        "",
+        "02:00:00:00:00",
+        "02:00:00:00:00:01:02",
+        "02:00:00:00::01",
+        "02:00:00:00:00:100",
+        "02:00:00:00:00:0g",
+        "02:00:00:00:00:+1",
+        "02-00-00-00-00-01",
+    ] {
+        assert_eq!(
+            s.parse::<EthernetAddress>(),
+            Err(ParseAddressError),
+            "{:?}",
+            s
+        );
+    }
+}
+
+#[test]
+fn parses_ipv4_addresses() {
+    assert_eq!("192.0.2.80".parse(), Ok(Ipv4Address([192, 0, 2, 80])));
+    assert_eq!(" 0.0.0.0 ".parse(), Ok(Ipv4Address([0, 0, 0, 0])));
+    assert_eq!(
+        "255.255.255.255".parse(),
+        Ok(Ipv4Address([255, 255, 255, 255]))
+    );
+}
+
+#[test]
+fn rejects_malformed_ipv4_addresses() {
+    for s in [
+        "",
+        "192.0.2",
+        "192.0.2.80.1",
+        "192.0..80",
+        "192.0.2.256",
+        "192.0.2.-1",
+        "192.0.2.+1",
+        "192.0.2.0x1",
+    ] {
+        assert_eq!(s.parse::<Ipv4Address>(), Err(ParseAddressError), "{:?}", s);
+    }
+}
+
+#[test]
+fn displays_in_parseable_form() {
+    let c = config();
+    assert_eq!(c.mac_addr.to_string().parse(), Ok(c.mac_addr));
+    assert_eq!(c.ip_addr.to_string().parse(), Ok(c.ip_addr));
+}
+
+#[test]
+fn set_overrides_each_key() {
+    let mut c = config();
+    c.set(NetConfig::KEY_MAC, "02:00:00:00:00:01").unwrap();
+    c.set(NetConfig::KEY_IP, "10.0.0.2").unwrap();
+    c.set(NetConfig::KEY_PREFIX, "8").unwrap();
+    c.set(NetConfig::KEY_GATEWAY, "10.0.0.1").unwrap();
+    assert_eq!(
+        c,
+        NetConfig {
+            mac_addr: EthernetAddress([0x02, 0, 0, 0, 0, 0x01]),
+            ip_addr: Ipv4Address([10, 0, 0, 2]),
+            prefix_len: 8,
+            gateway: Some(Ipv4Address([10, 0, 0, 1])),
+        }
+    );
+    assert_eq!(
+        c.to_string(),
+        "MAC=02:00:00:00:00:01 IP=10.0.0.2/8 GW=10.0.0.1"
+    );
+}
+
+#[test]
+fn set_leaves_the_config_unchanged_on_error() {
+    let mut c = config();
+    assert_eq!(
+        c.set(NetConfig::KEY_MAC, "02:00:00"),
+        Err(NetConfigError::InvalidAddress)
+    );
+    assert_eq!(
+        c.set(NetConfig::KEY_GATEWAY, "10.0.0"),
+        Err(NetConfigError::InvalidAddress)
+    );
+    assert_eq!(
+        c.set(NetConfig::KEY_PREFIX, "33"),
+        Err(NetConfigError::InvalidPrefix)
+    );
+    assert_eq!(
+        c.set(NetConfig::KEY_PREFIX, "-1"),
+        Err(NetConfigError::InvalidPrefix)
+    );
+    assert_eq!(
+        c.set("net.dns", "10.0.0.1"),
+        Err(NetConfigError::UnknownKey)
+    );
+    assert_eq!(c, config());
+}
+
+#[test]
+fn prefix_bounds() {
+    let mut c = config();
+    c.set(NetConfig::KEY_PREFIX, "0").unwrap();
+    assert_eq!(c.prefix_len, 0);
+    c.set(NetConfig::KEY_PREFIX, " 32 ").unwrap();
+    assert_eq!(c.prefix_len, 32);
+}
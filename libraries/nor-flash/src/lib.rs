@@ -0,0 +1,50 @@
+//! The NOR flash interface
+//!
+//! Kept apart from flash-storage so flash drivers can implement it without
+//! depending on TicKV.
+
+#![no_std]
+
+use core::fmt;
+
+/// NOR flash semantics, programming only clears bits and erasing sets a
+/// whole sector to 0xFF
+pub trait NorFlash {
+    type Error: fmt::Debug;
+
+    fn capacity_bytes(&self) -> usize;
+
+    fn page_size_bytes(&self) -> usize;
+
+    /// Size of the smallest erasable area
+    fn sector_size_bytes(&self) -> usize;
+
+    fn read(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Program `data` a page at a time, `addr` doesn't need to be page
+    /// aligned
+    fn write(&mut self, addr: u32, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Erase `len` bytes from `addr`, both sector aligned
+    fn erase(&mut self, addr: u32, len: usize) -> Result<(), Self::Error>;
+
+    /// Read back `data` from `addr` a `buf` at a time and compare
+    fn verify(&mut self, addr: u32, data: &[u8], buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Refuse to program or erase the lower `kib` KiB
+    fn protect_lower(&mut self, kib: usize) -> Result<(), Self::Error>;
+
+    /// Enter deep power-down, only `release_power_down` is accepted until
+    /// then
+    fn power_down(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn release_power_down(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn is_powered_down(&self) -> bool {
+        false
+    }
+}
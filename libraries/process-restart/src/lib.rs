@@ -0,0 +1,87 @@
+//! In-place restart support for supervised processes
+//!
+//! The root task restarts a faulted process by resuming its thread at the
+//! ELF entry point, on a fresh stack with its `ProcParams` written back,
+//! but it has no access to the frames backing the process' writable
+//! segments. So the process has to put its statics back to their
+//! load-time values itself, `reset_statics` must be the first thing its
+//! `_start` does.
+//!
+//! On the first start `.data` is copied into a snapshot kept in `.bss`,
+//! on every later start `.data` is copied back from the snapshot and the
+//! rest of `.bss` is zeroed.
+//!
+//! The section bounds are the symbols provided by the default GNU ld
+//! script for ARM ELF targets.
+
+#![no_std]
+
+use core::mem::size_of;
+use core::ptr;
+
+/// Largest `.data` section that can be snapshotted
+pub const MAX_DATA_BYTES: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The process' `.data` doesn't fit in the snapshot
+    DataTooLarge(usize),
+}
+
+#[repr(C)]
+struct Snapshot {
+    /// Non-zero once `.data` has been captured, zero in a freshly
+    /// loaded `.bss`
+    captured: usize,
+    data: [u8; MAX_DATA_BYTES],
+}
+
+static mut SNAPSHOT: Snapshot = Snapshot {
+    captured: 0,
+    data: [0; MAX_DATA_BYTES],
+};
+
+extern "C" {
+    static mut __data_start: u8;
+    static mut _edata: u8;
+    static mut __bss_start__: u8;
+    static mut __bss_end__: u8;
+}
+
+/// Returns true if the process was restarted, its statics have been reset
+/// to their load-time values.
+///
+/// # Safety
+/// Must be called at the very start of `_start`, before any static is
+/// read or written, and never again afterwards.
+pub unsafe fn reset_statics() -> Result<bool, Error> {
+    let data_start = ptr::addr_of_mut!(__data_start);
+    let data_len = ptr::addr_of_mut!(_edata) as usize - data_start as usize;
+    if data_len > MAX_DATA_BYTES {
+        return Err(Error::DataTooLarge(data_len));
+    }
+
+    let snapshot = ptr::addr_of_mut!(SNAPSHOT);
+    if ptr::read_volatile(ptr::addr_of!((*snapshot).captured)) == 0 {
+        ptr::copy_nonoverlapping(data_start, (*snapshot).data.as_mut_ptr(), data_len);
+        ptr::write_volatile(ptr::addr_of_mut!((*snapshot).captured), 1);
+        return Ok(false);
+    }
+
+    ptr::copy_nonoverlapping((*snapshot).data.as_ptr(), data_start, data_len);
+
+    // Zero .bss on either side of the snapshot, which lives in it
+    let bss_start = ptr::addr_of_mut!(__bss_start__);
+    let bss_end = ptr::addr_of_mut!(__bss_end__);
+    let snapshot_start = snapshot as *mut u8;
+    let snapshot_end = snapshot_start.add(size_of::<Snapshot>());
+    zero(bss_start, snapshot_start);
+    zero(snapshot_end, bss_end);
+    Ok(true)
+}
+
+unsafe fn zero(start: *mut u8, end: *mut u8) {
+    if end > start {
+        ptr::write_bytes(start, 0, end as usize - start as usize);
+    }
+}
@@ -1,63 +1,42 @@
 use ferros_build::*;
 use std::path::Path;
 
+#[path = "build/codegen.rs"]
+mod codegen;
+#[path = "build/manifest.rs"]
+mod manifest;
+
 fn main() {
     let out_dir = Path::new(&std::env::var_os("OUT_DIR").unwrap()).to_owned();
     let bin_dir = out_dir.join("..").join("..").join("..");
     let resources = out_dir.join("resources.rs");
-
-    let iomux = ElfResource {
-        path: bin_dir.join("iomux"),
-        image_name: "iomux".to_owned(),
-        type_name: "Iomux".to_owned(),
-        stack_size_bits: Some(14),
-    };
-    println!("cargo:rerun-if-changed={}", iomux.path.display());
-
-    let enet = ElfResource {
-        path: bin_dir.join("enet"),
-        image_name: "enet".to_owned(),
-        type_name: "Enet".to_owned(),
-        stack_size_bits: Some(16),
-    };
-    println!("cargo:rerun-if-changed={}", enet.path.display());
-
-    let tcpip = ElfResource {
-        path: bin_dir.join("tcpip"),
-        image_name: "tcpip".to_owned(),
-        type_name: "TcpIp".to_owned(),
-        stack_size_bits: Some(16),
-    };
-    println!("cargo:rerun-if-changed={}", tcpip.path.display());
-
-    let persistent_storage = ElfResource {
-        path: bin_dir.join("persistent-storage"),
-        image_name: "persistent-storage".to_owned(),
-        type_name: "PersistentStorage".to_owned(),
-        stack_size_bits: Some(14),
-    };
-    println!(
-        "cargo:rerun-if-changed={}",
-        persistent_storage.path.display()
-    );
-
-    let console = ElfResource {
-        path: bin_dir.join("console"),
-        image_name: "console".to_owned(),
-        type_name: "Console".to_owned(),
-        stack_size_bits: Some(15),
-    };
-    println!("cargo:rerun-if-changed={}", console.path.display());
-
-    let procs = vec![
-        &iomux as &dyn Resource,
-        &enet as &dyn Resource,
-        &tcpip as &dyn Resource,
-        &persistent_storage as &dyn Resource,
-        &console as &dyn Resource,
-    ];
+    let manifest_dir = Path::new(&std::env::var_os("CARGO_MANIFEST_DIR").unwrap()).to_owned();
+    let system_manifest = manifest_dir.join("..").join("system.toml");
+
+    println!("cargo:rerun-if-changed={}", system_manifest.display());
+    println!("cargo:rerun-if-changed=build");
+    let system = manifest::System::load(&system_manifest).unwrap_or_else(|e| panic!("{}", e));
+
+    let elfs: Vec<ElfResource> = system
+        .processes
+        .iter()
+        .map(|p| ElfResource {
+            path: bin_dir.join(&p.name),
+            image_name: p.name.clone(),
+            type_name: p.type_name.clone(),
+            stack_size_bits: Some(p.stack_size_bits),
+        })
+        .collect();
+    for elf in elfs.iter() {
+        println!("cargo:rerun-if-changed={}", elf.path.display());
+    }
+
+    let procs = elfs.iter().map(|elf| elf as &dyn Resource).collect();
 
     embed_resources(&resources, procs);
 
+    codegen::generate(&system, &out_dir.join("system.rs"))
+        .expect("Failed to generate the system setup code");
+
     built::write_built_file().expect("Failed to acquire build-time information")
 }
@@ -0,0 +1,676 @@
+//! Generates the root task's process setup code from the system manifest
+//!
+//! The output is a single block expression, included by `run()` in
+//! `root-task/src/main.rs`, which evaluates to the fault `Supervisor` once all
+//! processes have been started.
+//!
+//! The generated code expects the following bindings from `run()` in scope:
+//! `archive`, `local_slots`, `uts`, `root_cnode`, `root_vspace`,
+//! `asid_control`, `user_image`, `irq_control`, `dev_allocator`, `tpa`,
+//! `wdog2` and `boot_timer`, along with anything referenced by the manifest's value
+//! expressions (e.g. `mac_address`).
+
+use crate::manifest::{Endpoint, FaultPolicy, Process, System};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const UNCACHED_ATTRS: &str = "arch::vm_attributes::DEFAULT & !arch::vm_attributes::PAGE_CACHEABLE";
+const CACHED_ATTRS: &str = "arch::vm_attributes::DEFAULT";
+
+/// Milliseconds to wait for a process to report ready
+const DEFAULT_READY_TIMEOUT_MS: u32 = 5_000;
+
+pub fn generate(system: &System, path: &Path) -> io::Result<()> {
+    // The first pass counts the allocations from each process's child CNode
+    // slots, so the second knows which rebinding of the remainder is the last
+    let totals = emit(system, Emitter::default())?.child_slot_allocs;
+    let e = emit(
+        system,
+        Emitter {
+            child_slot_totals: totals,
+            ..Default::default()
+        },
+    )?;
+    fs::write(path, e.code)
+}
+
+fn emit(system: &System, mut e: Emitter) -> io::Result<Emitter> {
+    e.line("// Generated by root-task/build.rs from system.toml, do not edit");
+    e.open("{");
+
+    for p in system.processes.iter() {
+        let ident = p.ident();
+        e.line(&format!(
+            "let {}_elf_data = archive.file(resources::{}::IMAGE_NAME)?;",
+            ident, p.type_name
+        ));
+        e.line(&format!(
+            "log::debug!(\"[root-task] Found {} ELF data size={{}}\", {}_elf_data.len());",
+            p.name, ident
+        ));
+    }
+    e.line("");
+
+    for c in system.call_channels.iter() {
+        if let Some(names) = &c.caller_names {
+            let callers: Vec<String> = c
+                .callers
+                .iter()
+                .map(|ep| format!("{:?}", ep.process))
+                .collect();
+            e.line(&format!(
+                "// {} must name the callers of {} in badge order",
+                names, c.responder
+            ));
+            e.line(&format!(
+                "static_assertions::const_assert!(badged_ipc::names_match(&{}, &[{}]));",
+                names,
+                callers.join(", ")
+            ));
+            e.line("");
+        }
+    }
+
+    e.open("smart_alloc!(|slots: local_slots, ut: uts| {");
+    e.line("let (asid_pool, _asid_control) = asid_control.allocate_asid_pool(ut, slots)?;");
+    e.line("");
+    e.line("let ut_for_scratch: LocalCap<Untyped<U12>> = ut;");
+    e.line("let sacrificial_page = ut_for_scratch.retype(slots)?;");
+    e.line("let reserved_for_scratch = root_vspace.reserve(sacrificial_page)?;");
+    e.line("let mut scratch = reserved_for_scratch.as_scratch(&root_vspace).unwrap();");
+    e.line("");
+    e.line("let fault_sink_setup = FaultSinkSetup::new(&root_cnode, ut, slots, slots)?;");
+    e.line("let boot_notification = badged_ipc::Notification::new(ut, slots)?;");
+    e.line("let boot_waiter = boot_notification.create_waiter(&root_cnode, slots)?;");
+
+    for (idx, p) in system.processes.iter().enumerate() {
+        let ident = p.ident();
+        let asid_pool = if idx + 1 == system.processes.len() {
+            "_asid_pool"
+        } else {
+            "asid_pool"
+        };
+
+        e.section(&format!("{} setup", p.name));
+        e.line(&format!(
+            "log::debug!(\"[root-task] Setting up {}\");",
+            p.name
+        ));
+        e.line(&format!("let (asid, {}) = asid_pool.alloc();", asid_pool));
+        e.line("let vspace_slots: LocalCNodeSlots<ferros::arch::CodePageCount> = slots;");
+        e.line("let vspace_ut: LocalCap<Untyped<U16>> = ut;");
+        e.open(&format!(
+            "let mut {}_vspace = VSpace::new_from_elf::<resources::{}>(",
+            ident, p.type_name
+        ));
+        e.line("retype(ut, slots)?, // paging_root");
+        e.line("asid,");
+        e.line("vspace_slots.weaken(), // slots");
+        e.line("vspace_ut.weaken(), // paging_untyped");
+        e.line(&format!("{}_elf_data,", ident));
+        e.line("slots, // page_slots");
+        e.line("ut, // elf_writable_mem");
+        e.line("&user_image,");
+        e.line("&root_cnode,");
+        e.line("&mut scratch,");
+        e.close(")?;");
+        e.line(&format!(
+            "let ({0}_cnode, {0}_slots) = retype_cnode::<U{1}>(ut, slots)?;",
+            ident, p.cnode_size_bits
+        ));
+        e.child_slots(&ident, "fault_slots");
+        e.line(&format!(
+            "let {}_fault_source = fault_sink_setup.add_fault_source(&root_cnode, fault_slots, Badge::from({}))?;",
+            ident,
+            system.badge(&p.name).unwrap()
+        ));
+        e.child_slots(&ident, "boot_slots");
+        e.line(&format!(
+            "let {}_boot_status = boot_notification.create_boot_status(&root_cnode, boot_slots, {})?;",
+            ident,
+            system.badge(&p.name).unwrap()
+        ));
+        for i in p.interrupts.iter() {
+            e.child_slots(&ident, "slots_c");
+            e.line(&format!(
+                "let ({0}_{1}, _{0}_{1}_token): (InterruptConsumer<{2}, _>, _) =",
+                ident, i.field, i.irq
+            ));
+            e.line(
+                "    InterruptConsumer::new(ut, &mut irq_control, &root_cnode, slots, slots_c)?;",
+            );
+        }
+        if system.has_bound_notification(p) {
+            e.line(&format!(
+                "let {}_bound_notification = badged_ipc::Notification::new(ut, slots)?;",
+                ident
+            ));
+        }
+        for (bit, i) in p.bound_interrupts.iter().enumerate() {
+            e.child_slots(&ident, "irq_slots");
+            e.open(&format!(
+                "let {0}_{1} = {0}_bound_notification.create_irq_handler::<{2}, _>(",
+                ident, i.field, i.irq
+            ));
+            e.line("&mut irq_control,");
+            e.line("&root_cnode,");
+            e.line("slots,");
+            e.line("slots,");
+            e.line("irq_slots,");
+            e.line(&format!("1 << {},", bit));
+            e.close(")?;");
+        }
+        for i in p.irq_handlers.iter() {
+            e.line(&format!(
+                "let {0}_{1}_notification = badged_ipc::Notification::new(ut, slots)?;",
+                ident, i.field
+            ));
+            e.child_slots(&ident, "irq_slots");
+            e.open(&format!(
+                "let {0}_{1} = {0}_{1}_notification.create_irq_handler::<{2}, _>(",
+                ident, i.field, i.irq
+            ));
+            e.line("&mut irq_control,");
+            e.line("&root_cnode,");
+            e.line("slots,");
+            e.line("slots,");
+            e.line("irq_slots,");
+            e.line("0,");
+            e.close(")?;");
+        }
+    }
+
+    e.section("IPC setup");
+
+    for c in system.call_channels.iter() {
+        let responder = var(&c.responder);
+        e.line(&format!("// {} <- {}", c.responder, list(&c.callers)));
+        if c.badged {
+            e.line(&format!(
+                "let {}_setup = badged_ipc::CallChannelSetup::new(ut, slots)?;",
+                responder
+            ));
+            e.child_slots(&ident(&c.responder), "ipc_slots");
+            e.line(&format!(
+                "let {} = {}_setup.create_responder(&root_cnode, ipc_slots)?;",
+                responder, responder
+            ));
+            for (idx, caller) in c.callers.iter().enumerate() {
+                e.child_slots(&ident(caller), "ipc_slots");
+                e.line(&format!(
+                    "let {} = {}_setup.create_caller(&root_cnode, ipc_slots, Badge::from({}))?;",
+                    var(caller),
+                    responder,
+                    idx + 1
+                ));
+            }
+        } else {
+            e.child_slots(&ident(&c.responder), "ipc_slots");
+            e.line(&format!(
+                "let ({}_ipc_setup, {}) = call_channel(ut, &root_cnode, slots, ipc_slots)?;",
+                responder, responder
+            ));
+            for caller in c.callers.iter() {
+                e.child_slots(&ident(caller), "ipc_slots");
+                e.line(&format!(
+                    "let {} = {}_ipc_setup.create_caller(ipc_slots)?;",
+                    var(caller),
+                    responder
+                ));
+            }
+        }
+        e.line("");
+    }
+
+    for svc in system.services.iter() {
+        let server = var(&svc.server);
+        if svc.clients.is_empty() {
+            e.line(&format!("// {}, no clients yet", svc.server));
+        } else {
+            e.line(&format!("// {} <- {}", svc.server, list(&svc.clients)));
+        }
+        e.line(&format!(
+            "let {}_setup = badged_ipc::ServiceSetup::new(ut, slots)?;",
+            server
+        ));
+        e.child_slots(&ident(&svc.server), "ipc_slots");
+        e.line(&format!(
+            "let {}{} = {}_setup.create_server(&root_cnode, ipc_slots)?;",
+            if svc.clients.is_empty() { "" } else { "mut " },
+            server,
+            server
+        ));
+        for (idx, client) in svc.clients.iter().enumerate() {
+            let badge = idx + 1;
+            e.child_slots(&ident(client), "ipc_slots");
+            e.child_slots(&ident(&svc.server), "event_slots");
+            e.open(&format!(
+                "let ({}, {}_events) = {}_setup.create_client(",
+                var(client),
+                var(client),
+                server
+            ));
+            e.line("ut,");
+            e.line("slots,");
+            e.line("&root_cnode,");
+            e.line("ipc_slots,");
+            e.line("event_slots,");
+            e.line(&format!("Badge::from({}),", badge));
+            e.close(")?;");
+            e.line(&format!(
+                "{}.add_client({}, {}_events);",
+                server,
+                badge,
+                var(client)
+            ));
+        }
+        e.line("");
+    }
+
+    for q in system.interrupt_queues.iter() {
+        let consumer = var(&q.consumer);
+        let setup = producer_setup(&consumer, &q.producers);
+        e.line(&format!("// {} <- {}", q.consumer, list(&q.producers)));
+        e.child_slots(&ident(&q.consumer), "slots_c");
+        e.line(&format!(
+            "let ({0}_int_consumer, mut {0}_int_consumer_token): (InterruptConsumer<{1}, _>, _) =",
+            consumer, q.irq
+        ));
+        e.line("    InterruptConsumer::new(ut, &mut irq_control, &root_cnode, slots, slots_c)?;");
+        e.open(&format!(
+            "let ({0}, {1}) = {0}_int_consumer.add_queue::<{2}, {3}, {4}, _>(",
+            consumer, setup, q.item, q.depth, q.page_bits
+        ));
+        e.line(&format!("&mut {}_int_consumer_token,", consumer));
+        e.line("ut,");
+        e.line("&mut scratch,");
+        e.line(&format!("&mut {}_vspace,", ident(&q.consumer)));
+        e.line("&root_cnode,");
+        e.line("slots,");
+        e.line("slots,");
+        e.close(")?;");
+        producers(&mut e, &setup, &q.producers);
+        e.line("");
+    }
+
+    for q in system.queues.iter() {
+        let consumer = var(&q.consumer);
+        let setup = producer_setup(&consumer, &q.producers);
+        e.line(&format!("// {} <- {}", q.consumer, list(&q.producers)));
+        e.child_slots(&ident(&q.consumer), "slots_c");
+        e.open(&format!(
+            "let ({0}, _{0}_token, {1}, _{0}_waker_setup) = Consumer1::new::<{2}, {3}, _>(",
+            consumer, setup, q.depth, q.page_bits
+        ));
+        e.line("ut,");
+        e.line("ut,");
+        e.line("&mut scratch,");
+        e.line(&format!("&mut {}_vspace,", ident(&q.consumer)));
+        e.line("&root_cnode,");
+        e.line("slots,");
+        e.line("slots,");
+        e.line("slots,");
+        e.line("slots_c,");
+        e.close(")?;");
+        producers(&mut e, &setup, &q.producers);
+        e.line("");
+    }
+
+    for b in system.broadcasts.iter() {
+        let source = var(&b.source);
+        e.line(&format!("// {} -> {}", b.source, b.sinks.join(", ")));
+        e.line(&format!(
+            "let {}{} = badged_ipc::Broadcast::default();",
+            if b.sinks.is_empty() { "" } else { "mut " },
+            source
+        ));
+        for sink in b.sinks.iter() {
+            e.child_slots(&ident(&b.source), "signal_slots");
+            e.open(&format!(
+                "{}.add({}_bound_notification.create_signaller(",
+                source,
+                sink.replace('-', "_")
+            ));
+            e.line("&root_cnode,");
+            e.line("signal_slots,");
+            e.line("Badge::from(badged_ipc::NOTIFICATION_BADGE | badged_ipc::BROADCAST_BADGE),");
+            e.close(")?);");
+        }
+        e.line("");
+    }
+
+    for s in system.shared_memory.iter() {
+        let attrs = if s.cacheable {
+            CACHED_ATTRS
+        } else {
+            UNCACHED_ATTRS
+        };
+        e.line(&format!("// {} shared by {}", s.name, list(&s.mappings)));
+        e.line(&format!(
+            "let {}_unmapped: UnmappedMemoryRegion<{}, _> = UnmappedMemoryRegion::new(ut, slots)?;",
+            s.name, s.size_bits
+        ));
+        e.line(&format!(
+            "let {0}_shared = {0}_unmapped.to_shared();",
+            s.name
+        ));
+        for m in s.mappings.iter() {
+            e.line(&format!(
+                "let {} = {}_vspace.map_shared_region(&{}_shared, CapRights::RW, {}, slots, &root_cnode)?;",
+                var(m),
+                ident(m),
+                s.name,
+                attrs
+            ));
+        }
+        e.line("");
+    }
+
+    for p in system.processes.iter() {
+        let ident = p.ident();
+        let mut fields: Vec<(String, String)> = Vec::new();
+
+        e.section(&format!("{} process", p.name));
+
+        for d in p.devices.iter() {
+            let v = format!("{}_{}", ident, d.field);
+            e.line(&format!("let {}_ut = dev_allocator", v));
+            e.line("    .get_untyped_by_address_range_slot_infallible(");
+            e.line(&format!(
+                "        PageAlignedAddressRange::new_by_size({0}::PADDR as _, {0}::SIZE)?,",
+                d.ty
+            ));
+            e.line("        slots,");
+            e.line("    )?");
+            e.line("    .as_strong::<arch::PageBits>()");
+            e.line("    .expect(\"Device untyped was not the right size!\");");
+            e.line(&format!(
+                "let {0}_mem = {1}_vspace.map_region(UnmappedMemoryRegion::new_device({0}_ut, slots)?, CapRights::RW, {2})?;",
+                v, ident, UNCACHED_ATTRS
+            ));
+            fields.push((
+                d.field.clone(),
+                format!("unsafe {{ {}::from_vaddr({}_mem.vaddr() as _) }}", d.ty, v),
+            ));
+        }
+
+        for i in p
+            .interrupts
+            .iter()
+            .chain(p.bound_interrupts.iter())
+            .chain(p.irq_handlers.iter())
+        {
+            fields.push((i.field.clone(), format!("{}_{}", ident, i.field)));
+        }
+
+        for m in p.memory.iter() {
+            let v = format!("{}_{}", ident, m.field);
+            let attrs = if m.cacheable {
+                CACHED_ATTRS
+            } else {
+                UNCACHED_ATTRS
+            };
+            e.line(&format!(
+                "let {}_unmapped: UnmappedMemoryRegion<{}, _> = UnmappedMemoryRegion::new(ut, slots)?;",
+                v, m.size_bits
+            ));
+            e.child_slots(&ident, "mem_slots");
+            e.line(&format!(
+                "let {0} = {1}_vspace.map_region_and_move({0}_unmapped, CapRights::RW, {2}, &root_cnode, mem_slots)?;",
+                v, ident, attrs
+            ));
+            fields.push((m.field.clone(), v));
+        }
+
+        for v in p.values.iter() {
+            fields.push((v.field.clone(), v.expr.clone()));
+        }
+
+        fields.push(("boot_status".to_owned(), format!("{}_boot_status", ident)));
+
+        for ep in system.endpoints().filter(|ep| ep.process == p.name) {
+            fields.push((ep.field.clone(), var(ep)));
+        }
+
+        e.open(&format!("let {}_params = {} {{", ident, p.params));
+        for (field, value) in fields.iter() {
+            e.line(&format!("{}: {},", field, value));
+        }
+        e.close("};");
+        if p.fault_policy == FaultPolicy::Restart {
+            restart_stack(&mut e, p);
+        }
+        e.line(&format!(
+            "let {}_stack_mem: UnmappedMemoryRegion<<resources::{} as ElfProc>::StackSizeBits, _> =",
+            ident, p.type_name
+        ));
+        e.line("    UnmappedMemoryRegion::new(ut, slots).unwrap();");
+        e.line(&format!(
+            "let {0}_stack_mem = root_vspace.map_region({0}_stack_mem, CapRights::RW, {1})?;",
+            ident, CACHED_ATTRS
+        ));
+        e.open(&format!(
+            "let mut {}_process = StandardProcess::new::<{}<_>, _>(",
+            ident, p.params
+        ));
+        e.line(&format!("&mut {}_vspace,", ident));
+        e.line(&format!("{}_cnode,", ident));
+        e.line(&format!("{}_stack_mem,", ident));
+        e.line("&root_cnode,");
+        e.line(&format!("{}_elf_data,", ident));
+        e.line(&format!("{}_params,", ident));
+        e.line("ut, // ipc_buffer_ut");
+        e.line("ut, // tcb_ut");
+        e.line("slots,");
+        e.line("&tpa, // priority_authority");
+        e.line(&format!("Some({}_fault_source),", ident));
+        e.close(")?;");
+        if system.has_bound_notification(p) {
+            e.line(&format!(
+                "{0}_bound_notification.bind({0}_process.unsafe_get_tcb_cptr())?;",
+                ident
+            ));
+        }
+    }
+
+    e.close("});");
+    e.line("");
+    e.line("let mut supervisor = Supervisor::new(fault_sink_setup.sink(), wdog2);");
+
+    e.line("let mut boot_monitor = BootMonitor::new(boot_waiter, boot_timer);");
+
+    // Processes already known to be ready at this point in the generated code
+    let mut ready: Vec<&str> = Vec::new();
+
+    let order = system
+        .start_order()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    for p in order.iter() {
+        let ident = p.ident();
+        let badge = system.badge(&p.name).unwrap();
+        e.line("");
+        for dep in p.depends_on.iter() {
+            if !ready.contains(&dep.as_str()) {
+                wait_ready(&mut e, system, dep);
+                ready.push(dep);
+            }
+        }
+        e.line(&format!(
+            "{}_process.set_name(\"{}\");",
+            ident,
+            p.thread_name()
+        ));
+        if let Some(affinity) = p.affinity {
+            e.line(&format!(
+                "unsafe {{ selfe_sys::seL4_TCB_SetAffinity({}_process.unsafe_get_tcb_cptr(), {}) }};",
+                ident, affinity
+            ));
+        }
+        e.line(&format!(
+            "supervisor.supervise({0}, \"{1}\", FaultPolicy::{2:?}, {3}_process.unsafe_get_tcb_cptr(), {4})?;",
+            badge,
+            p.thread_name(),
+            p.fault_policy,
+            ident,
+            if p.fault_policy == FaultPolicy::Restart {
+                format!("Some({}_restart_stack)", ident)
+            } else {
+                "None".to_owned()
+            }
+        ));
+        e.line(&format!("{}_process.start()?;", ident));
+    }
+
+    // Nothing depends on the remaining processes, still report any
+    // that fail to come up
+    e.line("");
+    for p in order.iter() {
+        if !ready.contains(&p.name.as_str()) {
+            wait_ready(&mut e, system, &p.name);
+        }
+    }
+    e.line("log::debug!(\"[root-task] All processes are ready\");");
+
+    e.line("");
+    e.line("supervisor");
+    e.close("}");
+    Ok(e)
+}
+
+fn wait_ready(e: &mut Emitter, system: &System, name: &str) {
+    let p = system.process(name).unwrap();
+    e.line(&format!(
+        "boot_monitor.wait_ready({}, \"{}\", {})?;",
+        system.badge(name).unwrap(),
+        p.thread_name(),
+        p.ready_timeout_ms.unwrap_or(DEFAULT_READY_TIMEOUT_MS)
+    ));
+}
+
+/// A second stack the size of the process' own, mapped in the root task
+/// too, for the supervisor to restart the process on with a copy of its
+/// params, see `RestartStack`
+fn restart_stack(e: &mut Emitter, p: &Process) {
+    let ident = p.ident();
+    e.line(&format!(
+        "let {}_restart_stack: UnmappedMemoryRegion<<resources::{} as ElfProc>::StackSizeBits, _> =",
+        ident, p.type_name
+    ));
+    e.line("    UnmappedMemoryRegion::new(ut, slots)?;");
+    e.line(&format!(
+        "let {0}_restart_stack = {0}_restart_stack.to_shared();",
+        ident
+    ));
+    e.line(&format!(
+        "let {0}_restart_stack_child = {0}_vspace.map_shared_region(&{0}_restart_stack, CapRights::RW, {1}, slots, &root_cnode)?;",
+        ident, CACHED_ATTRS
+    ));
+    e.line(&format!(
+        "let {0}_restart_stack_root = root_vspace.map_shared_region(&{0}_restart_stack, CapRights::RW, {1}, slots, &root_cnode)?;",
+        ident, CACHED_ATTRS
+    ));
+    e.open(&format!(
+        "let {}_restart_stack = unsafe {{ RestartStack::new(",
+        ident
+    ));
+    e.line(&format!("&{}_params,", ident));
+    e.line(&format!("{}_restart_stack_root.vaddr(),", ident));
+    e.line(&format!("{}_restart_stack_child.vaddr(),", ident));
+    e.line(&format!("{}_restart_stack_root.size_bytes(),", ident));
+    e.close(") }?;");
+}
+
+fn ident(ep: &Endpoint) -> String {
+    ep.process.replace('-', "_")
+}
+
+fn var(ep: &Endpoint) -> String {
+    format!("{}_{}", ident(ep), ep.field)
+}
+
+fn list(eps: &[Endpoint]) -> String {
+    eps.iter()
+        .map(|ep| ep.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn producer_setup(consumer: &str, producers: &[Endpoint]) -> String {
+    if producers.is_empty() {
+        format!("_{}_producer_setup", consumer)
+    } else {
+        format!("{}_producer_setup", consumer)
+    }
+}
+
+fn producers(e: &mut Emitter, setup: &str, producers: &[Endpoint]) {
+    for p in producers.iter() {
+        e.child_slots(&ident(p), "slots_p");
+        e.line(&format!(
+            "let {} = Producer::new(&{}, slots_p, &mut {}_vspace, &root_cnode, slots)?;",
+            var(p),
+            setup,
+            ident(p)
+        ));
+    }
+}
+
+#[derive(Default)]
+struct Emitter {
+    code: String,
+    indent: usize,
+    /// Number of allocations from each process's child CNode slots so far
+    child_slot_allocs: HashMap<String, usize>,
+    /// Total number of allocations from each process's child CNode slots,
+    /// empty on the counting pass
+    child_slot_totals: HashMap<String, usize>,
+}
+
+impl Emitter {
+    fn line(&mut self, s: &str) {
+        if s.is_empty() && self.code.ends_with("\n\n") {
+            return;
+        }
+        if !s.is_empty() {
+            self.code.push_str(&"    ".repeat(self.indent));
+            self.code.push_str(s);
+        }
+        self.code.push('\n');
+    }
+
+    fn open(&mut self, s: &str) {
+        self.line(s);
+        self.indent += 1;
+    }
+
+    fn close(&mut self, s: &str) {
+        self.indent -= 1;
+        self.line(s);
+    }
+
+    fn section(&mut self, title: &str) {
+        self.line("");
+        self.line("//");
+        self.line(&format!("// {}", title));
+        self.line("//");
+        self.line("");
+    }
+
+    /// Allocate from a process's child CNode slots, the final rebinding of
+    /// the remaining slots is unused
+    fn child_slots(&mut self, ident: &str, var: &str) {
+        let allocs = self.child_slot_allocs.entry(ident.to_owned()).or_default();
+        *allocs += 1;
+        let unused = if self.child_slot_totals.get(ident) == Some(&*allocs) {
+            "_"
+        } else {
+            ""
+        };
+        self.line(&format!(
+            "let ({}, {}{}_slots) = {2}_slots.alloc();",
+            var, unused, ident
+        ));
+    }
+}
@@ -0,0 +1,460 @@
+//! System manifest, see `system.toml` at the workspace root
+
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Must not exceed the number of processes the root task's supervisor can track
+const MAX_PROCESSES: usize = 8;
+
+/// See `badged_ipc::MAX_CLIENTS`
+const MAX_SERVICE_CLIENTS: usize = 8;
+
+/// One badge bit each, below `badged_ipc::BROADCAST_BADGE`
+const MAX_BOUND_INTERRUPTS: usize = 26;
+
+/// See `badged_ipc::MAX_BROADCAST_SIGNALLERS`
+const MAX_BROADCAST_SINKS: usize = 8;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct System {
+    #[serde(rename = "process")]
+    pub processes: Vec<Process>,
+    #[serde(default, rename = "call_channel")]
+    pub call_channels: Vec<CallChannel>,
+    #[serde(default, rename = "service")]
+    pub services: Vec<Service>,
+    #[serde(default, rename = "queue")]
+    pub queues: Vec<Queue>,
+    #[serde(default, rename = "interrupt_queue")]
+    pub interrupt_queues: Vec<InterruptQueue>,
+    #[serde(default, rename = "shared_memory")]
+    pub shared_memory: Vec<SharedMemory>,
+    #[serde(default, rename = "broadcast")]
+    pub broadcasts: Vec<Broadcast>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Process {
+    /// Package and ELF image name
+    pub name: String,
+    /// Name of the generated `ElfProc` resource type
+    pub type_name: String,
+    /// Thread name, defaults to the process name
+    pub thread_name: Option<String>,
+    /// Path to the process's `ProcParams` type, without the role parameter
+    pub params: String,
+    pub stack_size_bits: usize,
+    #[serde(default = "default_cnode_size_bits")]
+    pub cnode_size_bits: usize,
+    pub affinity: Option<usize>,
+    pub fault_policy: FaultPolicy,
+    /// Processes that must report ready before this one is started
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Milliseconds the root task waits for this process to report ready
+    pub ready_timeout_ms: Option<u32>,
+    #[serde(default, rename = "device")]
+    pub devices: Vec<Device>,
+    /// Interrupt consumers without a queue
+    #[serde(default, rename = "interrupt")]
+    pub interrupts: Vec<Interrupt>,
+    /// Interrupts signalled on a notification bound to the process's thread
+    #[serde(default, rename = "bound_interrupt")]
+    pub bound_interrupts: Vec<Interrupt>,
+    /// Interrupts signalled on a notification of their own, waited on with
+    /// `IrqHandler::wait`
+    #[serde(default, rename = "irq_handler")]
+    pub irq_handlers: Vec<Interrupt>,
+    #[serde(default, rename = "memory")]
+    pub memory: Vec<Memory>,
+    #[serde(default, rename = "value")]
+    pub values: Vec<Value>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+pub enum FaultPolicy {
+    Ignore,
+    Restart,
+    Reboot,
+}
+
+/// Memory mapped device, the type must provide `PADDR`, `SIZE` and `from_vaddr`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Device {
+    pub field: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+/// Interrupt delivered to a `ProcParams` field
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Interrupt {
+    pub field: String,
+    pub irq: String,
+}
+
+/// Memory region private to the process
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Memory {
+    pub field: String,
+    pub size_bits: String,
+    #[serde(default = "default_cacheable")]
+    pub cacheable: bool,
+}
+
+/// Arbitrary expression evaluated in the root task
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Value {
+    pub field: String,
+    pub expr: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CallChannel {
+    pub responder: Endpoint,
+    pub callers: Vec<Endpoint>,
+    /// Mint the callers with badges 1, 2, ... in order, the responder is
+    /// handed the caller's badge with each request, see
+    /// `badged_ipc::CallChannelSetup`
+    #[serde(default)]
+    pub badged: bool,
+    /// Path of a const array naming the callers' processes in badge order,
+    /// for a responder that looks its callers up by badge. Checked against
+    /// `callers` at build time, see `badged_ipc::names_match`
+    pub caller_names: Option<String>,
+}
+
+/// Badged request/response service with client event notifications
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Service {
+    pub server: Endpoint,
+    pub clients: Vec<Endpoint>,
+}
+
+/// Queue with a notification-only waker
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Queue {
+    pub consumer: Endpoint,
+    pub producers: Vec<Endpoint>,
+    pub depth: String,
+    pub page_bits: String,
+}
+
+/// Queue whose consumer also waits on an interrupt
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InterruptQueue {
+    pub consumer: Endpoint,
+    pub producers: Vec<Endpoint>,
+    pub irq: String,
+    pub item: String,
+    pub depth: String,
+    pub page_bits: String,
+}
+
+/// Memory region mapped into several processes
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SharedMemory {
+    pub name: String,
+    pub size_bits: String,
+    #[serde(default = "default_cacheable")]
+    pub cacheable: bool,
+    pub mappings: Vec<Endpoint>,
+}
+
+/// Signal from one process to the notifications bound to the threads of
+/// others, see `badged_ipc::Broadcast`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Broadcast {
+    pub source: Endpoint,
+    /// Process names, a process without bound interrupts is given a bound
+    /// notification for the broadcast
+    pub sinks: Vec<String>,
+}
+
+/// A `ProcParams` field of a process, written as "<process>.<field>"
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Endpoint {
+    pub process: String,
+    pub field: String,
+}
+
+fn default_cnode_size_bits() -> usize {
+    12
+}
+
+fn default_cacheable() -> bool {
+    true
+}
+
+impl TryFrom<String> for Endpoint {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.split_once('.') {
+            Some((process, field)) if !process.is_empty() && !field.is_empty() => Ok(Endpoint {
+                process: process.to_owned(),
+                field: field.to_owned(),
+            }),
+            _ => Err(format!(
+                "Invalid endpoint '{}', expected '<process>.<field>'",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.process, self.field)
+    }
+}
+
+impl Process {
+    /// Identifier prefix used for the process's variables in the generated code
+    pub fn ident(&self) -> String {
+        self.name.replace('-', "_")
+    }
+
+    pub fn thread_name(&self) -> &str {
+        self.thread_name.as_deref().unwrap_or(&self.name)
+    }
+}
+
+impl System {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+        let system: System = toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse '{}': {}", path.display(), e))?;
+        system.validate()?;
+        Ok(system)
+    }
+
+    pub fn process(&self, name: &str) -> Option<&Process> {
+        self.processes.iter().find(|p| p.name == name)
+    }
+
+    /// Fault source and boot status badge of a process, zero is reserved
+    pub fn badge(&self, name: &str) -> Option<usize> {
+        self.processes
+            .iter()
+            .position(|p| p.name == name)
+            .map(|idx| idx + 1)
+    }
+
+    /// The process's thread is bound to a notification, for its bound
+    /// interrupts or broadcasts it receives
+    pub fn has_bound_notification(&self, p: &Process) -> bool {
+        !p.bound_interrupts.is_empty()
+            || self
+                .broadcasts
+                .iter()
+                .any(|b| b.sinks.iter().any(|s| s == &p.name))
+    }
+
+    /// Processes ordered such that each one comes after its dependencies,
+    /// otherwise in declaration order
+    pub fn start_order(&self) -> Result<Vec<&Process>, String> {
+        let mut order: Vec<&Process> = Vec::with_capacity(self.processes.len());
+        while order.len() < self.processes.len() {
+            let next = self.processes.iter().find(|p| {
+                !order.iter().any(|o| o.name == p.name)
+                    && p.depends_on
+                        .iter()
+                        .all(|d| order.iter().any(|o| &o.name == d))
+            });
+            match next {
+                Some(p) => order.push(p),
+                None => {
+                    let remaining: Vec<&str> = self
+                        .processes
+                        .iter()
+                        .filter(|p| !order.iter().any(|o| o.name == p.name))
+                        .map(|p| p.name.as_str())
+                        .collect();
+                    return Err(format!(
+                        "Dependency cycle between processes: {}",
+                        remaining.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    /// Every IPC and shared memory endpoint, in declaration order
+    pub fn endpoints(&self) -> impl Iterator<Item = &Endpoint> {
+        self.call_channels
+            .iter()
+            .flat_map(|c| Some(&c.responder).into_iter().chain(c.callers.iter()))
+            .chain(
+                self.services
+                    .iter()
+                    .flat_map(|s| Some(&s.server).into_iter().chain(s.clients.iter())),
+            )
+            .chain(
+                self.interrupt_queues
+                    .iter()
+                    .flat_map(|q| Some(&q.consumer).into_iter().chain(q.producers.iter())),
+            )
+            .chain(
+                self.queues
+                    .iter()
+                    .flat_map(|q| Some(&q.consumer).into_iter().chain(q.producers.iter())),
+            )
+            .chain(self.shared_memory.iter().flat_map(|s| s.mappings.iter()))
+            .chain(self.broadcasts.iter().map(|b| &b.source))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.processes.is_empty() {
+            return Err("No processes declared".to_owned());
+        }
+        if self.processes.len() > MAX_PROCESSES {
+            return Err(format!(
+                "Too many processes ({}), the supervisor supports up to {}",
+                self.processes.len(),
+                MAX_PROCESSES
+            ));
+        }
+
+        let mut names = HashSet::new();
+        let mut fields = HashSet::new();
+        let mut add_field = |process: &str, field: &str| {
+            if fields.insert((process.to_owned(), field.to_owned())) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Field '{}.{}' is assigned more than once",
+                    process, field
+                ))
+            }
+        };
+
+        for p in self.processes.iter() {
+            if !names.insert(p.ident()) {
+                return Err(format!("Process '{}' is declared more than once", p.name));
+            }
+            for d in p.depends_on.iter() {
+                if self.process(d).is_none() {
+                    return Err(format!(
+                        "Process '{}' depends on unknown process '{}'",
+                        p.name, d
+                    ));
+                }
+            }
+            add_field(&p.name, "boot_status")?;
+            for d in p.devices.iter() {
+                add_field(&p.name, &d.field)?;
+            }
+            for i in p.interrupts.iter() {
+                add_field(&p.name, &i.field)?;
+            }
+            if p.bound_interrupts.len() > MAX_BOUND_INTERRUPTS {
+                return Err(format!(
+                    "Process '{}' has too many bound interrupts ({}), up to {} are supported",
+                    p.name,
+                    p.bound_interrupts.len(),
+                    MAX_BOUND_INTERRUPTS
+                ));
+            }
+            for i in p.bound_interrupts.iter().chain(p.irq_handlers.iter()) {
+                add_field(&p.name, &i.field)?;
+            }
+            for m in p.memory.iter() {
+                add_field(&p.name, &m.field)?;
+            }
+            for v in p.values.iter() {
+                add_field(&p.name, &v.field)?;
+            }
+        }
+
+        for e in self.endpoints() {
+            if self.process(&e.process).is_none() {
+                return Err(format!("Endpoint '{}' refers to an unknown process", e));
+            }
+            add_field(&e.process, &e.field)?;
+        }
+
+        for c in self.call_channels.iter() {
+            if c.caller_names.is_some() && !c.badged {
+                return Err(format!(
+                    "Call channel '{}' names its callers but isn't badged",
+                    c.responder
+                ));
+            }
+        }
+
+        for c in self.call_channels.iter().filter(|c| c.badged) {
+            if c.callers.len() > MAX_SERVICE_CLIENTS {
+                return Err(format!(
+                    "Call channel '{}' has too many callers ({}), up to {} are supported",
+                    c.responder,
+                    c.callers.len(),
+                    MAX_SERVICE_CLIENTS
+                ));
+            }
+        }
+
+        for s in self.services.iter() {
+            if s.clients.len() > MAX_SERVICE_CLIENTS {
+                return Err(format!(
+                    "Service '{}' has too many clients ({}), up to {} are supported",
+                    s.server,
+                    s.clients.len(),
+                    MAX_SERVICE_CLIENTS
+                ));
+            }
+        }
+
+        for b in self.broadcasts.iter() {
+            if b.sinks.len() > MAX_BROADCAST_SINKS {
+                return Err(format!(
+                    "Broadcast '{}' has too many sinks ({}), up to {} are supported",
+                    b.source,
+                    b.sinks.len(),
+                    MAX_BROADCAST_SINKS
+                ));
+            }
+            for sink in b.sinks.iter() {
+                if self.process(sink).is_none() {
+                    return Err(format!(
+                        "Broadcast '{}' signals unknown process '{}'",
+                        b.source, sink
+                    ));
+                }
+            }
+        }
+
+        let mut shared_names = HashSet::new();
+        for s in self.shared_memory.iter() {
+            if !shared_names.insert(&s.name) {
+                return Err(format!(
+                    "Shared memory region '{}' is declared more than once",
+                    s.name
+                ));
+            }
+        }
+
+        self.start_order()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+//! Boot readiness monitor
+//!
+//! Processes report their boot status by signalling a notification, using
+//! their fault source badge to pick their bits. The root task waits for a
+//! process's dependencies to be ready before starting it.
+
+use crate::error::TopLevelError;
+use badged_ipc::{BootStatus, Waiter};
+use ferros::cap::role;
+use imx6_hal::delay::Delay;
+use imx6_hal::pac::epit::epit2::EPIT2;
+
+pub struct BootMonitor {
+    waiter: Waiter<role::Local>,
+    timer: Delay<EPIT2>,
+    /// Signal bits accumulated so far, a poll clears them in the notification
+    bits: usize,
+}
+
+impl BootMonitor {
+    pub fn new(waiter: Waiter<role::Local>, timer: Delay<EPIT2>) -> Self {
+        BootMonitor {
+            waiter,
+            timer,
+            bits: 0,
+        }
+    }
+
+    /// Wait for the process with `badge` to report ready, giving up after
+    /// `timeout_ms` milliseconds
+    pub fn wait_ready(
+        &mut self,
+        badge: usize,
+        name: &'static str,
+        timeout_ms: u32,
+    ) -> Result<(), TopLevelError> {
+        self.timer.start_ms(timeout_ms);
+        loop {
+            if let Some(bits) = self.waiter.poll() {
+                self.bits |= bits;
+            }
+
+            match BootStatus::from_bits(badge, self.bits) {
+                Some(BootStatus::Ready) => {
+                    log::debug!("[root-task] '{}' reported ready", name);
+                    return Ok(());
+                }
+                Some(BootStatus::Failed) => {
+                    log::error!("[root-task] '{}' failed to start", name);
+                    return Err(TopLevelError::BootFailed(name));
+                }
+                None => (),
+            }
+
+            if self.timer.is_expired() {
+                log::error!("[root-task] '{}' did not report ready in time", name);
+                return Err(TopLevelError::BootTimeout(name));
+            }
+            unsafe { selfe_sys::seL4_Yield() };
+        }
+    }
+}
@@ -27,6 +27,17 @@ pub enum TopLevelError {
     RetypeError(RetypeError),
     ArchiveReadError(ArchiveReadError),
     SetLoggerError(SetLoggerError),
+    BadgedIpcError(badged_ipc::Error),
+    SupervisorFull,
+    SupervisorRegisterAccess(usize),
+    /// A process with the `Restart` policy was supervised without a
+    /// restart stack
+    SupervisorNoRestartStack(&'static str),
+    /// This many bytes of a process' `ProcParams` passed on the stack
+    /// don't fit in its restart stack
+    SupervisorParamsTooLarge(usize),
+    BootFailed(&'static str),
+    BootTimeout(&'static str),
 }
 
 impl From<AllocError> for TopLevelError {
@@ -112,3 +123,9 @@ impl From<SetLoggerError> for TopLevelError {
         TopLevelError::SetLoggerError(e)
     }
 }
+
+impl From<badged_ipc::Error> for TopLevelError {
+    fn from(e: badged_ipc::Error) -> Self {
+        TopLevelError::BadgedIpcError(e)
+    }
+}
@@ -0,0 +1,336 @@
+//! Fault supervisor
+//!
+//! Every child process is given a badged fault source, the root task
+//! then waits on the fault sink and applies the policy configured for the
+//! faulting process.
+
+use crate::error::TopLevelError;
+use core::{mem, ptr};
+use ferros::cap::role;
+use ferros::userland::{Fault, FaultSink};
+use imx6_hal::pac::wdog::{wdog2::WDOG2, Control};
+use selfe_sys::{seL4_UserContext, seL4_Word};
+
+/// Maximum number of processes the supervisor can track
+const MAX_SUPERVISED: usize = 8;
+
+/// Number of in-place restarts allowed before a process is considered
+/// unrecoverable and the policy escalates to a reboot
+const MAX_RESTARTS: usize = 3;
+
+/// Number of words in a thread's register context
+const CONTEXT_WORDS: usize = mem::size_of::<seL4_UserContext>() / mem::size_of::<seL4_Word>();
+
+/// Bytes of the `ProcParams` passed in r0-r3, per the AAPCS the rest are
+/// passed on the stack
+const PARAM_REGISTER_BYTES: usize = 4 * mem::size_of::<seL4_Word>();
+
+/// Most bytes of `ProcParams` passed on the stack a restart can write back
+const MAX_STACK_PARAM_BYTES: usize = 256;
+
+/// AAPCS stack alignment at a public interface
+const STACK_ALIGN: usize = 8;
+
+/// What to do when a supervised process faults
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FaultPolicy {
+    /// Log the fault and leave the process stopped
+    Ignore,
+    /// Log the fault and restart the process from its entry point on a
+    /// fresh stack, see `RestartStack`. The process must reset its statics
+    /// with `process_restart::reset_statics`
+    Restart,
+    /// Log the fault and reset the system
+    Reboot,
+}
+
+struct Supervised {
+    badge: usize,
+    name: &'static str,
+    policy: FaultPolicy,
+    tcb: usize,
+    /// Register context captured before the process was first started
+    initial_context: seL4_UserContext,
+    restart_stack: Option<RestartStack>,
+    restarts: usize,
+}
+
+/// Stack a process is restarted on, mapped in both the root task and the
+/// process. The process' own stack is abandoned once it faults, and the
+/// `ProcParams` passed on the stack are written back to this one from the
+/// root task's copy on every restart
+pub struct RestartStack {
+    /// The stack as mapped in the root task
+    root_vaddr: usize,
+    /// The same stack as mapped in the process
+    child_vaddr: usize,
+    size_bytes: usize,
+    /// The bytes of `ProcParams` passed on the stack
+    params: [u8; MAX_STACK_PARAM_BYTES],
+    params_len: usize,
+}
+
+impl RestartStack {
+    /// Keep a copy of the `params` the process is started with
+    ///
+    /// # Safety
+    /// `size_bytes` from `root_vaddr` must be mapped read/write in the root
+    /// task, to the same memory as from `child_vaddr` in the process, for
+    /// as long as the process is supervised
+    pub unsafe fn new<P>(
+        params: &P,
+        root_vaddr: usize,
+        child_vaddr: usize,
+        size_bytes: usize,
+    ) -> Result<Self, TopLevelError> {
+        let len = mem::size_of::<P>().saturating_sub(PARAM_REGISTER_BYTES);
+        if len > MAX_STACK_PARAM_BYTES || round_up(len, STACK_ALIGN) > size_bytes {
+            return Err(TopLevelError::SupervisorParamsTooLarge(len));
+        }
+        let mut stack = RestartStack {
+            root_vaddr,
+            child_vaddr,
+            size_bytes,
+            params: [0; MAX_STACK_PARAM_BYTES],
+            params_len: len,
+        };
+        if len != 0 {
+            let src = (params as *const P as *const u8).add(PARAM_REGISTER_BYTES);
+            ptr::copy_nonoverlapping(src, stack.params.as_mut_ptr(), len);
+        }
+        Ok(stack)
+    }
+
+    /// Write the stack passed `ProcParams` back to the top of the stack,
+    /// returns the process' stack pointer to them
+    fn reset(&self) -> usize {
+        let offset = self.size_bytes - round_up(self.params_len, STACK_ALIGN);
+        unsafe {
+            ptr::copy_nonoverlapping(
+                self.params.as_ptr(),
+                (self.root_vaddr + offset) as *mut u8,
+                self.params_len,
+            );
+        }
+        self.child_vaddr + offset
+    }
+}
+
+fn round_up(n: usize, align: usize) -> usize {
+    (n + align - 1) & !(align - 1)
+}
+
+pub struct Supervisor {
+    sink: FaultSink<role::Local>,
+    wdog: WDOG2,
+    processes: [Option<Supervised>; MAX_SUPERVISED],
+}
+
+impl Supervisor {
+    pub fn new(sink: FaultSink<role::Local>, wdog: WDOG2) -> Self {
+        Supervisor {
+            sink,
+            wdog,
+            processes: Default::default(),
+        }
+    }
+
+    /// Start supervising a process whose fault source was minted with `badge`.
+    ///
+    /// Must be called before the process is started, its initial register
+    /// context is captured here so it can be restarted in-place later.
+    /// Processes with the `Restart` policy must be given a `restart_stack`.
+    pub fn supervise(
+        &mut self,
+        badge: usize,
+        name: &'static str,
+        policy: FaultPolicy,
+        tcb: usize,
+        restart_stack: Option<RestartStack>,
+    ) -> Result<(), TopLevelError> {
+        let slot = self
+            .processes
+            .iter_mut()
+            .find(|p| p.is_none())
+            .ok_or(TopLevelError::SupervisorFull)?;
+        if policy == FaultPolicy::Restart && restart_stack.is_none() {
+            return Err(TopLevelError::SupervisorNoRestartStack(name));
+        }
+
+        let mut initial_context: seL4_UserContext = unsafe { mem::zeroed() };
+        let err = unsafe {
+            selfe_sys::seL4_TCB_ReadRegisters(
+                tcb,
+                0, // suspend_source
+                0, // arch_flags
+                CONTEXT_WORDS as _,
+                &mut initial_context,
+            )
+        };
+        if err != 0 {
+            return Err(TopLevelError::SupervisorRegisterAccess(err as _));
+        }
+
+        log::trace!(
+            "[supervisor] Supervising '{}' badge={} policy={:?} pc=0x{:X} sp=0x{:X}",
+            name,
+            badge,
+            policy,
+            initial_context.pc,
+            initial_context.sp,
+        );
+
+        *slot = Some(Supervised {
+            badge,
+            name,
+            policy,
+            tcb,
+            initial_context,
+            restart_stack,
+            restarts: 0,
+        });
+        Ok(())
+    }
+
+    /// Wait for faults, forever
+    pub fn run(mut self) -> ! {
+        log::debug!("[supervisor] Waiting for faults");
+        loop {
+            let fault = self.sink.wait_for_fault();
+            self.handle_fault(&fault);
+        }
+    }
+
+    fn handle_fault(&mut self, fault: &Fault) {
+        let badge: usize = fault.sender().into();
+        let (kind, addr) = match fault {
+            Fault::VMFault(f) => ("VMFault", Some(f.address)),
+            Fault::CapFault(f) => ("CapFault", Some(f.cap_address)),
+            Fault::UnknownSyscall(_) => ("UnknownSyscall", None),
+            Fault::UserException(_) => ("UserException", None),
+            Fault::NullFault(_) => ("NullFault", None),
+            _ => ("UnidentifiedFault", None),
+        };
+
+        let proc = match self
+            .processes
+            .iter_mut()
+            .flatten()
+            .find(|p| p.badge == badge)
+        {
+            Some(p) => p,
+            None => {
+                log::error!(
+                    "[supervisor] {} from an unknown process badge={}",
+                    kind,
+                    badge
+                );
+                return;
+            }
+        };
+
+        // The faulting thread is blocked on the fault endpoint, so its
+        // registers still reflect the faulting instruction
+        let mut context: seL4_UserContext = unsafe { mem::zeroed() };
+        let pc = match unsafe {
+            selfe_sys::seL4_TCB_ReadRegisters(proc.tcb, 0, 0, CONTEXT_WORDS as _, &mut context)
+        } {
+            0 => Some(context.pc),
+            _ => None,
+        };
+
+        log::error!(
+            "[supervisor] '{}' faulted kind={} addr={} pc={}",
+            proc.name,
+            kind,
+            OptHex(addr),
+            OptHex(pc.map(|pc| pc as usize)),
+        );
+
+        let mut policy = proc.policy;
+        if policy == FaultPolicy::Restart && proc.restarts >= MAX_RESTARTS {
+            log::error!(
+                "[supervisor] '{}' exceeded {} restarts",
+                proc.name,
+                MAX_RESTARTS
+            );
+            policy = FaultPolicy::Reboot;
+        }
+
+        match policy {
+            FaultPolicy::Ignore => {
+                log::warn!("[supervisor] '{}' left stopped", proc.name);
+            }
+            FaultPolicy::Restart => {
+                proc.restarts += 1;
+                log::warn!(
+                    "[supervisor] Restarting '{}' ({}/{})",
+                    proc.name,
+                    proc.restarts,
+                    MAX_RESTARTS
+                );
+                if let Err(e) = proc.restart() {
+                    log::error!("[supervisor] Failed to restart '{}' err={}", proc.name, e);
+                }
+            }
+            FaultPolicy::Reboot => {
+                log::error!("[supervisor] Rebooting the system");
+                self.reboot();
+            }
+        }
+    }
+
+    /// Assert the WDOG software reset signal, which resets the whole chip
+    fn reboot(&mut self) -> ! {
+        self.wdog.wcr.modify(Control::SwResetSignal::AssertReset);
+        loop {
+            unsafe { selfe_sys::seL4_Yield() };
+        }
+    }
+}
+
+impl Supervised {
+    /// Suspend the thread and resume it with its initial register context,
+    /// on the restart stack.
+    ///
+    /// The process re-enters its ELF entry point with its `ProcParams`, the
+    /// ones passed in registers are in the initial context and the rest are
+    /// written back to the restart stack. Its `.data` and `.bss` are put
+    /// back to their load-time values by the process itself on entry, see
+    /// `process_restart`.
+    fn restart(&mut self) -> Result<(), seL4_Word> {
+        let err = unsafe { selfe_sys::seL4_TCB_Suspend(self.tcb) };
+        if err != 0 {
+            return Err(err as _);
+        }
+        let mut context = self.initial_context;
+        // Always given with the `Restart` policy, see `supervise`
+        if let Some(stack) = &self.restart_stack {
+            context.sp = stack.reset() as _;
+        }
+        let err = unsafe {
+            selfe_sys::seL4_TCB_WriteRegisters(
+                self.tcb,
+                1, // resume_target
+                0, // arch_flags
+                CONTEXT_WORDS as _,
+                &mut context,
+            )
+        };
+        if err != 0 {
+            return Err(err as _);
+        }
+        Ok(())
+    }
+}
+
+struct OptHex(Option<usize>);
+
+impl core::fmt::Display for OptHex {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            Some(v) => write!(f, "0x{:X}", v),
+            None => write!(f, "n/a"),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+#!/usr/bin/env bash
+# Host-side tests of the persistent storage on the file-backed flash simulator
+
+set -e
+
+ROOT="$(cd "$(dirname "$0")/.." && pwd)"
+TOOLCHAIN="$(cat "$ROOT/rust-toolchain")"
+
+# Run outside the tree so the target and build-std settings in
+# .cargo/config.toml don't apply
+cd "$(mktemp -d)"
+
+cargo +"$TOOLCHAIN" test \
+    --manifest-path "$ROOT/libraries/flash-storage/Cargo.toml" \
+    --features std \
+    "$@"
+
+exit 0
@@ -13,6 +13,12 @@ if [ -z ${SEL4_PLATFORM+x} ]; then
 fi
 
 # build all packages in the right order, so binary packaging works as expected.
+echo "======================= building watchdog ======================"
+cargo build -p watchdog $@;
+
+echo "======================= building gpio ======================"
+cargo build -p gpio $@;
+
 echo "======================= building iomux ======================"
 cargo build -p iomux $@;
 
@@ -22,6 +28,9 @@ cargo build -p enet $@;
 echo "======================= building tcpip ======================"
 cargo build -p tcpip $@;
 
+echo "======================= building spi-bus ======================"
+cargo build -p spi-bus $@;
+
 echo "======================= building persistent-storage ======================"
 cargo build -p persistent-storage $@;
 